@@ -0,0 +1,15 @@
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+
+    #[cfg(feature = "protobuf")]
+    {
+        println!("cargo:rerun-if-changed=proto/playlist4_external.proto");
+
+        std::env::set_var(
+            "PROTOC",
+            protoc_bin_vendored::protoc_bin_path().expect("no vendored protoc for this platform"),
+        );
+        prost_build::compile_protos(&["proto/playlist4_external.proto"], &["proto/"])
+            .expect("failed to compile protos");
+    }
+}
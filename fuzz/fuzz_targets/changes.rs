@@ -0,0 +1,22 @@
+//! Changes are applied locally to simulate them, whatever the server or a user sends
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use spotify_private_api::{Changes, RootList};
+
+const ROOT_LIST: &str = r#"{"revision":"AAAAELqqrKuzaoeUKYP7gEzCzrx3h0rD","length":4,"contents":{"pos":0,"truncated":false,"items":[{"uri":"spotify:start-group:123456789abcdefa:Work"},{"uri":"spotify:playlist:5aNzxEEkRE9MgNkiuXmpOR"},{"uri":"spotify:end-group:123456789abcdefa"},{"uri":"spotify:playlist:3FKTkhbClLGgKdPpbx3aHy"}],"metaItems":[{},{},{},{}]}}"#;
+
+fuzz_target!(|data: &str| {
+    let Ok(changes) = serde_json::from_str::<Changes>(data) else {
+        return;
+    };
+
+    let json = serde_json::to_string(&changes).expect("failed to serialize changes");
+    let reparsed: Changes =
+        serde_json::from_str(&json).expect("failed to parse serialized changes");
+    assert_eq!(reparsed.ops().count(), changes.ops().count());
+
+    let mut root_list: RootList =
+        serde_json::from_str(ROOT_LIST).expect("failed to parse root list");
+    let _ = root_list.apply_changes(&changes);
+});
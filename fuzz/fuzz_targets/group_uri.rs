@@ -0,0 +1,37 @@
+//! Folder ids and names are split out of `start-group:<id>:<name>` uris
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use spotify_private_api::{RootList, SpotifyUri};
+
+fuzz_target!(|data: &str| {
+    for uri in [
+        data.to_string(),
+        format!("spotify:start-group:{}", data),
+        format!("spotify:end-group:{}", data),
+    ] {
+        if let Ok(parsed) = uri.parse::<SpotifyUri>() {
+            assert_eq!(
+                parsed.to_string().parse::<SpotifyUri>().ok(),
+                Some(parsed),
+                "{} doesn't round trip",
+                uri
+            );
+        }
+
+        let json = serde_json::json!({
+            "revision": "AAAA",
+            "contents": { "items": [{ "uri": uri }] }
+        });
+        let root_list: RootList = serde_json::from_value(json).expect("failed to build root list");
+        for entry in root_list.entries() {
+            let _ = entry.entry.name();
+            if let Ok(id) = data.parse() {
+                let _ = root_list.find_by_folder_id(&id);
+            }
+            if let Ok(id) = data.parse() {
+                let _ = root_list.find_by_playlist_id(&id);
+            }
+        }
+    }
+});
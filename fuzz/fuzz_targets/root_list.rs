@@ -0,0 +1,23 @@
+//! Root lists come from the server and from backups and layouts on disk
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use spotify_private_api::RootList;
+
+fuzz_target!(|data: &str| {
+    let Ok(root_list) = serde_json::from_str::<RootList>(data) else {
+        return;
+    };
+
+    let mut depth = 0;
+    for entry in root_list.entries() {
+        assert!(entry.depth <= depth, "depth jumped at {}", entry.index);
+        depth = entry.depth + 1;
+        let _ = entry.entry.name();
+    }
+
+    let json = serde_json::to_string(&root_list).expect("failed to serialize root list");
+    let reparsed: RootList =
+        serde_json::from_str(&json).expect("failed to parse serialized root list");
+    assert_eq!(reparsed.len(), root_list.len());
+});
@@ -0,0 +1,70 @@
+//! Folder trees are rebuilt from the flat root list, also when the markers don't balance
+#![no_main]
+
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+use spotify_private_api::RootList;
+
+#[derive(Arbitrary, Debug)]
+enum Item {
+    Start(u8, String),
+    End(u8),
+    Playlist(u8),
+    Unknown(String),
+}
+
+impl Item {
+    fn uri(&self) -> String {
+        match self {
+            Item::Start(id, name) => format!("spotify:start-group:{:016x}:{}", id, name),
+            Item::End(id) => format!("spotify:end-group:{:016x}", id),
+            Item::Playlist(id) => format!("spotify:playlist:{:0>22}", id),
+            Item::Unknown(uri) => uri.clone(),
+        }
+    }
+}
+
+fn root_list(uris: &[String]) -> RootList {
+    let items: Vec<_> = uris
+        .iter()
+        .map(|uri| serde_json::json!({ "uri": uri }))
+        .collect();
+
+    serde_json::from_value(serde_json::json!({
+        "revision": "AAAA",
+        "contents": { "items": items, "metaItems": [] }
+    }))
+    .expect("failed to build root list")
+}
+
+fuzz_target!(|items: Vec<Item>| {
+    let uris: Vec<String> = items.iter().map(Item::uri).collect();
+    let target = root_list(&uris);
+
+    let starts = target
+        .entries()
+        .filter(|entry| matches!(entry.entry, spotify_private_api::Entry::FolderStart { .. }))
+        .count();
+    assert!(target.entries().all(|entry| entry.depth <= starts));
+
+    let mut reversed = uris.clone();
+    reversed.reverse();
+    let mut current = root_list(&reversed);
+    let _ = current.diff(&target);
+
+    let changes = target.backup().restore(&current).build();
+    current
+        .apply_changes(&changes)
+        .expect("failed to apply restore");
+
+    let mut unique = uris.clone();
+    unique.sort();
+    unique.dedup();
+    if unique.len() == uris.len() {
+        let restored: Vec<&str> = current.entries().map(|entry| entry.raw_uri()).collect();
+        assert_eq!(
+            restored,
+            uris.iter().map(String::as_str).collect::<Vec<_>>()
+        );
+    }
+});
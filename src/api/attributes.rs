@@ -0,0 +1,302 @@
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use serde_with::formats::Flexible;
+use serde_with::TimestampMilliSeconds;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct FormatAttribute {
+    pub key: String,
+    pub value: String,
+}
+
+/// The `formatAttributes` of a list or an item
+///
+/// Lists with an entry that isn't just a `key` and a `value` string are kept as they are in
+/// `Other`, so an unexpected entry neither fails the parse nor gets lost.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+#[serde(untagged)]
+pub enum FormatAttributes {
+    List(Vec<FormatAttribute>),
+    Other(Value),
+}
+
+/// Attributes of an item inside a list, e.g. a playlist inside the root list
+///
+/// Keys this crate doesn't know about are kept in `extra`.
+#[serde_with::serde_as]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
+pub struct ItemAttributes {
+    #[serde(rename = "addedBy", default, skip_serializing_if = "Option::is_none")]
+    pub added_by: Option<String>,
+
+    #[serde_as(as = "Option<TimestampMilliSeconds<String, Flexible>>")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<SystemTime>,
+
+    #[serde_as(as = "Option<TimestampMilliSeconds<String, Flexible>>")]
+    #[serde(rename = "seenAt", default, skip_serializing_if = "Option::is_none")]
+    pub seen_at: Option<SystemTime>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub public: Option<bool>,
+
+    #[serde(
+        rename = "formatAttributes",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub format_attributes: Option<FormatAttributes>,
+
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// Attributes of a list, e.g. the name and description of a playlist
+///
+/// Keys this crate doesn't know about are kept in `extra`.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
+pub struct ListAttributes {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+
+    /// Image id of a custom playlist picture
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub picture: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub collaborative: Option<bool>,
+
+    #[serde(
+        rename = "deletedByOwner",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub deleted_by_owner: Option<bool>,
+
+    #[serde(
+        rename = "formatAttributes",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub format_attributes: Option<FormatAttributes>,
+
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// Attribute of a list that [`ListAttributesUpdate`] can clear
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub enum ListAttributeKind {
+    #[serde(rename = "LIST_NAME")]
+    Name,
+    #[serde(rename = "LIST_DESCRIPTION")]
+    Description,
+    #[serde(rename = "LIST_PICTURE")]
+    Picture,
+    #[serde(rename = "LIST_COLLABORATIVE")]
+    Collaborative,
+    #[serde(rename = "LIST_DELETED_BY_OWNER")]
+    DeletedByOwner,
+    #[serde(untagged)]
+    Other(String),
+}
+
+/// Attribute of an item that [`ItemAttributesUpdate`] can clear
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub enum ItemAttributeKind {
+    #[serde(rename = "ITEM_ADDED_BY")]
+    AddedBy,
+    #[serde(rename = "ITEM_TIMESTAMP")]
+    Timestamp,
+    #[serde(rename = "ITEM_SEEN_AT")]
+    SeenAt,
+    #[serde(rename = "ITEM_PUBLIC")]
+    Public,
+    #[serde(untagged)]
+    Other(String),
+}
+
+/// Partial update of [`ListAttributes`]
+///
+/// Attributes set in `values` are changed, the ones listed in `no_value` are cleared and
+/// everything else is left as it is.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
+pub struct ListAttributesUpdate {
+    #[serde(default)]
+    pub values: ListAttributes,
+    #[serde(rename = "noValue", default)]
+    pub no_value: Vec<ListAttributeKind>,
+}
+
+/// Partial update of [`ItemAttributes`]
+///
+/// Attributes set in `values` are changed, the ones listed in `no_value` are cleared and
+/// everything else is left as it is.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
+pub struct ItemAttributesUpdate {
+    #[serde(default)]
+    pub values: ItemAttributes,
+    #[serde(rename = "noValue", default)]
+    pub no_value: Vec<ItemAttributeKind>,
+}
+
+impl ListAttributes {
+    /// Applies `update` the way the server does
+    pub fn update(&mut self, update: &ListAttributesUpdate) {
+        let values = &update.values;
+        merge(&mut self.name, &values.name);
+        merge(&mut self.description, &values.description);
+        merge(&mut self.picture, &values.picture);
+        merge(&mut self.collaborative, &values.collaborative);
+        merge(&mut self.deleted_by_owner, &values.deleted_by_owner);
+        merge(&mut self.format_attributes, &values.format_attributes);
+        self.extra.extend(values.extra.clone());
+
+        for kind in &update.no_value {
+            match kind {
+                ListAttributeKind::Name => self.name = None,
+                ListAttributeKind::Description => self.description = None,
+                ListAttributeKind::Picture => self.picture = None,
+                ListAttributeKind::Collaborative => self.collaborative = None,
+                ListAttributeKind::DeletedByOwner => self.deleted_by_owner = None,
+                ListAttributeKind::Other(_) => {}
+            }
+        }
+    }
+}
+
+impl ItemAttributes {
+    /// Applies `update` the way the server does
+    pub fn update(&mut self, update: &ItemAttributesUpdate) {
+        let values = &update.values;
+        merge(&mut self.added_by, &values.added_by);
+        merge(&mut self.timestamp, &values.timestamp);
+        merge(&mut self.seen_at, &values.seen_at);
+        merge(&mut self.public, &values.public);
+        merge(&mut self.format_attributes, &values.format_attributes);
+        self.extra.extend(values.extra.clone());
+
+        for kind in &update.no_value {
+            match kind {
+                ItemAttributeKind::AddedBy => self.added_by = None,
+                ItemAttributeKind::Timestamp => self.timestamp = None,
+                ItemAttributeKind::SeenAt => self.seen_at = None,
+                ItemAttributeKind::Public => self.public = None,
+                ItemAttributeKind::Other(_) => {}
+            }
+        }
+    }
+}
+
+fn merge<T: Clone>(value: &mut Option<T>, new_value: &Option<T>) {
+    if new_value.is_some() {
+        value.clone_from(new_value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use super::{
+        FormatAttribute, FormatAttributes, ItemAttributes, ListAttributeKind, ListAttributes,
+        ListAttributesUpdate,
+    };
+
+    #[test]
+    fn test_item_attributes_des() {
+        let attributes: ItemAttributes = serde_json::from_str(
+            r#"{"addedBy":"user","timestamp":"1665495078416","seenAt":"0","public":true,"formatAttributes":[{"key":"k","value":"v"}],"unknown":1}"#,
+        )
+        .expect("Couldn't parse attributes");
+
+        assert_eq!(attributes.added_by.as_deref(), Some("user"));
+        assert_eq!(
+            attributes.timestamp,
+            Some(UNIX_EPOCH + Duration::from_millis(1665495078416))
+        );
+        assert_eq!(attributes.seen_at, Some(UNIX_EPOCH));
+        assert_eq!(attributes.public, Some(true));
+        assert_eq!(
+            attributes.format_attributes,
+            Some(FormatAttributes::List(vec![FormatAttribute {
+                key: "k".to_string(),
+                value: "v".to_string()
+            }]))
+        );
+        assert_eq!(attributes.extra["unknown"], 1);
+    }
+
+    #[test]
+    fn test_unexpected_format_attributes() {
+        let json = r#"{"timestamp":"1665495078416","formatAttributes":[{"key":"k","value":"v"},{"key":"n","value":1,"scope":"x"},"flag"]}"#;
+        let attributes: ItemAttributes = serde_json::from_str(json).expect("Couldn't parse");
+
+        assert!(matches!(
+            attributes.format_attributes,
+            Some(FormatAttributes::Other(_))
+        ));
+        assert_eq!(
+            serde_json::to_value(&attributes).expect("Couldn't serialize"),
+            serde_json::from_str::<serde_json::Value>(json).unwrap()
+        );
+
+        let list: ListAttributes =
+            serde_json::from_str(r#"{"name":"Mix","formatAttributes":{"key":"k"}}"#)
+                .expect("Couldn't parse");
+        assert_eq!(list.name.as_deref(), Some("Mix"));
+    }
+
+    #[test]
+    fn test_item_attributes_ser() {
+        let json = r#"{"timestamp":"1665495078416","seenAt":"0","public":false,"unknown":"kept"}"#;
+        let attributes: ItemAttributes = serde_json::from_str(json).expect("Couldn't parse");
+
+        assert_eq!(
+            serde_json::to_value(&attributes).expect("Couldn't serialize"),
+            serde_json::from_str::<serde_json::Value>(json).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_list_attributes_des() {
+        let attributes: ListAttributes = serde_json::from_str(
+            r#"{"name":"My Playlist #2","description":"desc","picture":"ab67706c","collaborative":true,"deletedByOwner":false,"newKey":[]}"#,
+        )
+        .expect("Couldn't parse attributes");
+
+        assert_eq!(attributes.name.as_deref(), Some("My Playlist #2"));
+        assert_eq!(attributes.description.as_deref(), Some("desc"));
+        assert_eq!(attributes.picture.as_deref(), Some("ab67706c"));
+        assert_eq!(attributes.collaborative, Some(true));
+        assert_eq!(attributes.deleted_by_owner, Some(false));
+        assert!(attributes.extra.contains_key("newKey"));
+    }
+
+    #[test]
+    fn test_list_attributes_update() {
+        let mut attributes: ListAttributes =
+            serde_json::from_str(r#"{"name":"Old","description":"desc","collaborative":true}"#)
+                .expect("Couldn't parse attributes");
+        let update: ListAttributesUpdate = serde_json::from_str(
+            r#"{"values":{"name":"New"},"noValue":["LIST_DESCRIPTION","LIST_SOMETHING_NEW"]}"#,
+        )
+        .expect("Couldn't parse update");
+
+        assert_eq!(
+            update.no_value[1],
+            ListAttributeKind::Other("LIST_SOMETHING_NEW".to_string())
+        );
+
+        attributes.update(&update);
+        assert_eq!(attributes.name.as_deref(), Some("New"));
+        assert_eq!(attributes.description, None);
+        assert_eq!(attributes.collaborative, Some(true));
+    }
+}
@@ -0,0 +1,516 @@
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use base64::Engine;
+use futures_util::{SinkExt, Stream, StreamExt};
+use serde::Deserialize;
+use serde_json::Value;
+use tokio::net::TcpStream;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+
+use crate::Result;
+
+pub(crate) const PING_INTERVAL: Duration = Duration::from_secs(30);
+
+const MIN_RECONNECT_DELAY: Duration = Duration::from_secs(1);
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(60);
+
+const PING: &str = r#"{"type":"ping"}"#;
+
+const PUSHER_CONNECTIONS: &str = "hm://pusher/v1/connections/";
+
+/// Where a [`RootListSubscription`](crate::RootListSubscription) gets the access token of
+/// every connection from, so a reconnect doesn't use one that expired in the meantime
+pub trait AccessTokenSource: Debug + Send + Sync {
+    fn access_token(&self) -> Pin<Box<dyn Future<Output = Result<String>> + Send + '_>>;
+}
+
+/// The root list changed on another device
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RootListEvent {
+    /// Revision of the root list after the change, `None` if the notification couldn't be
+    /// decoded. Fetch the root list to get it in that case
+    pub revision: Option<String>,
+}
+
+/// Stream of [`RootListEvent`]s, see [`Session::subscribe_rootlist`](crate::Session::subscribe_rootlist)
+///
+/// The connection to the dealer is kept alive and reconnected in the background until the
+/// subscription is dropped.
+#[derive(Debug)]
+pub struct RootListSubscription {
+    events: mpsc::Receiver<RootListEvent>,
+    task: JoinHandle<()>,
+}
+
+impl Stream for RootListSubscription {
+    type Item = RootListEvent;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.events.poll_recv(cx)
+    }
+}
+
+impl Drop for RootListSubscription {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// The dealer and the endpoint the dealer connections are registered at
+#[derive(Debug, Clone)]
+pub(crate) struct DealerEndpoints {
+    pub(crate) dealer: String,
+    /// Only connections registered here get the notifications of the user
+    pub(crate) notifications: String,
+}
+
+pub(crate) fn subscribe_rootlist(
+    endpoints: DealerEndpoints,
+    http_client: reqwest::Client,
+    tokens: Arc<dyn AccessTokenSource>,
+    user_id: &str,
+    ping_interval: Duration,
+) -> RootListSubscription {
+    let (sender, events) = mpsc::channel(16);
+    let rootlist_uri = format!("hm://playlist/v2/user/{}/rootlist", user_id);
+
+    let task = tokio::spawn(async move {
+        let mut reconnect_delay = MIN_RECONNECT_DELAY;
+
+        loop {
+            // A separate statement, so the error isn't held across the awaits below
+            let access_token = tokens.access_token().await.ok();
+            if let Some(access_token) = access_token {
+                let url = dealer_url(&endpoints.dealer, &access_token).ok();
+                let connected = match url {
+                    Some(url) => tokio_tungstenite::connect_async(url.as_str()).await.ok(),
+                    None => None,
+                };
+
+                if let Some((socket, _)) = connected {
+                    reconnect_delay = MIN_RECONNECT_DELAY;
+
+                    let connection = Connection {
+                        http_client: &http_client,
+                        notifications: &endpoints.notifications,
+                        access_token: &access_token,
+                        rootlist_uri: &rootlist_uri,
+                    };
+                    if listen(socket, &connection, &sender, ping_interval).await == Listen::Closed {
+                        return;
+                    }
+                }
+            }
+
+            tokio::time::sleep(reconnect_delay).await;
+            reconnect_delay = (reconnect_delay * 2).min(MAX_RECONNECT_DELAY);
+        }
+    });
+
+    RootListSubscription { events, task }
+}
+
+/// The dealer url with the access token in its query, encoded
+fn dealer_url(dealer: &str, access_token: &str) -> Result<reqwest::Url> {
+    Ok(reqwest::Url::parse_with_params(
+        &format!("{}/", dealer),
+        [("access_token", access_token)],
+    )?)
+}
+
+/// What a single dealer connection needs besides the socket
+struct Connection<'a> {
+    http_client: &'a reqwest::Client,
+    notifications: &'a str,
+    access_token: &'a str,
+    rootlist_uri: &'a str,
+}
+
+impl Connection<'_> {
+    /// Registers the connection for the notifications of the user, the dealer announces its id
+    /// right after connecting and sends nothing else until it is registered
+    async fn register(&self, connection_id: &str) -> reqwest::Result<()> {
+        self.http_client
+            .put(self.notifications)
+            .query(&[("connection_id", connection_id)])
+            .header("authorization", format!("Bearer {}", self.access_token))
+            .header("content-length", "0")
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Listen {
+    /// The connection dropped, try again
+    Reconnect,
+    /// The subscription was dropped, stop
+    Closed,
+}
+
+async fn listen(
+    socket: WebSocketStream<MaybeTlsStream<TcpStream>>,
+    connection: &Connection<'_>,
+    sender: &mpsc::Sender<RootListEvent>,
+    ping_interval: Duration,
+) -> Listen {
+    let (mut write, mut read) = socket.split();
+    let mut ping = tokio::time::interval(ping_interval);
+    let mut waiting_for_pong = false;
+
+    loop {
+        tokio::select! {
+            _ = ping.tick() => {
+                if waiting_for_pong || write.send(Message::Text(PING.to_string())).await.is_err() {
+                    return Listen::Reconnect;
+                }
+                waiting_for_pong = true;
+            }
+            message = read.next() => {
+                let message = match message {
+                    Some(Ok(message)) => message,
+                    _ => return Listen::Reconnect,
+                };
+
+                match message {
+                    Message::Text(text) => match parse_message(&text, connection.rootlist_uri) {
+                        Some(DealerMessage::Pong) => waiting_for_pong = false,
+                        Some(DealerMessage::Connection(connection_id)) => {
+                            if let Err(e) = connection.register(&connection_id).await {
+                                log::warn!("failed to register the dealer connection: {}", e);
+                                return Listen::Reconnect;
+                            }
+                        }
+                        Some(DealerMessage::RootList(event)) => {
+                            let Ok(()) = sender.send(event).await else {
+                                return Listen::Closed;
+                            };
+                        }
+                        None => {}
+                    },
+                    Message::Ping(payload) => {
+                        let Ok(()) = write.send(Message::Pong(payload)).await else {
+                            return Listen::Reconnect;
+                        };
+                    }
+                    Message::Close(_) => return Listen::Reconnect,
+                    _ => {}
+                }
+            }
+            _ = sender.closed() => return Listen::Closed,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct RawDealerMessage {
+    #[serde(rename = "type")]
+    kind: String,
+    uri: Option<String>,
+    #[serde(default)]
+    headers: HashMap<String, String>,
+    #[serde(default)]
+    payloads: Vec<Value>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum DealerMessage {
+    Pong,
+    /// Id of the connection, to register it with
+    Connection(String),
+    RootList(RootListEvent),
+}
+
+fn parse_message(text: &str, rootlist_uri: &str) -> Option<DealerMessage> {
+    let message: RawDealerMessage = serde_json::from_str(text).ok()?;
+
+    match message.kind.as_str() {
+        "pong" => Some(DealerMessage::Pong),
+        "message"
+            if message
+                .uri
+                .as_deref()
+                .is_some_and(|uri| uri.starts_with(PUSHER_CONNECTIONS)) =>
+        {
+            let connection_id = message.headers.get("Spotify-Connection-Id").cloned();
+
+            connection_id.map(DealerMessage::Connection)
+        }
+        "message" if message.uri.as_deref() == Some(rootlist_uri) => {
+            let revision = message
+                .payloads
+                .first()
+                .and_then(Value::as_str)
+                .and_then(|payload| {
+                    base64::engine::general_purpose::STANDARD
+                        .decode(payload)
+                        .ok()
+                })
+                .and_then(|payload| {
+                    new_revision(&payload)
+                        .map(|revision| base64::engine::general_purpose::STANDARD.encode(revision))
+                });
+
+            Some(DealerMessage::RootList(RootListEvent { revision }))
+        }
+        _ => None,
+    }
+}
+
+/// Reads `new_revision` (field 2) out of a `PlaylistModificationInfo` protobuf message
+fn new_revision(mut message: &[u8]) -> Option<&[u8]> {
+    while !message.is_empty() {
+        let key = read_varint(&mut message)?;
+
+        match key & 0x7 {
+            0 => {
+                read_varint(&mut message)?;
+            }
+            1 => message = message.get(8..)?,
+            2 => {
+                let length = usize::try_from(read_varint(&mut message)?).ok()?;
+                let value = message.get(..length)?;
+                message = &message[length..];
+
+                if key >> 3 == 2 {
+                    return Some(value);
+                }
+            }
+            5 => message = message.get(4..)?,
+            _ => return None,
+        }
+    }
+
+    None
+}
+
+fn read_varint(message: &mut &[u8]) -> Option<u64> {
+    let mut value = 0u64;
+
+    for shift in (0..64).step_by(7) {
+        let (byte, rest) = message.split_first()?;
+        *message = rest;
+        value |= u64::from(byte & 0x7f) << shift;
+
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use base64::Engine;
+    use futures_util::{SinkExt, StreamExt};
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio_tungstenite::tungstenite::handshake::server::{Request, Response};
+    use tokio_tungstenite::tungstenite::Message;
+    use tokio_tungstenite::WebSocketStream;
+
+    use super::{
+        dealer_url, parse_message, subscribe_rootlist, AccessTokenSource, DealerEndpoints,
+        DealerMessage, RootListEvent,
+    };
+    use crate::Result;
+
+    const ROOTLIST_URI: &str = "hm://playlist/v2/user/user/rootlist";
+
+    /// Hands out `token1`, `token2`, ...
+    #[derive(Debug, Default)]
+    struct CountingTokens(AtomicUsize);
+
+    impl AccessTokenSource for CountingTokens {
+        fn access_token(&self) -> Pin<Box<dyn Future<Output = Result<String>> + Send + '_>> {
+            let n = self.0.fetch_add(1, Ordering::SeqCst) + 1;
+
+            Box::pin(async move { Ok(format!("token{}", n)) })
+        }
+    }
+
+    fn rootlist_message(revision: &[u8]) -> String {
+        // PlaylistModificationInfo { uri: "spotify:user:user:rootlist", new_revision }
+        let uri = b"spotify:user:user:rootlist";
+        let mut payload = vec![0x0a, uri.len() as u8];
+        payload.extend_from_slice(uri);
+        payload.extend_from_slice(&[0x12, revision.len() as u8]);
+        payload.extend_from_slice(revision);
+
+        format!(
+            r#"{{"type":"message","uri":"{}","headers":{{"Content-Type":"application/x-protobuf"}},"payloads":["{}"]}}"#,
+            ROOTLIST_URI,
+            base64::engine::general_purpose::STANDARD.encode(payload)
+        )
+    }
+
+    fn connection_message(id: &str) -> String {
+        format!(
+            r#"{{"type":"message","uri":"hm://pusher/v1/connections/{}","headers":{{"Spotify-Connection-Id":"{}"}}}}"#,
+            id, id
+        )
+    }
+
+    /// Accepts a websocket connection, along with the uri it was opened with
+    // The callback's error type is tungstenite's
+    #[allow(clippy::result_large_err)]
+    async fn accept(listener: &TcpListener) -> (WebSocketStream<TcpStream>, String) {
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut uri = String::new();
+        let socket = tokio_tungstenite::accept_hdr_async(stream, |req: &Request, res: Response| {
+            uri = req.uri().to_string();
+            Ok(res)
+        })
+        .await
+        .unwrap();
+
+        (socket, uri)
+    }
+
+    #[test]
+    fn test_parse_message() {
+        assert_eq!(
+            parse_message(&rootlist_message(&[0, 0, 0, 1, 0xff]), ROOTLIST_URI),
+            Some(DealerMessage::RootList(RootListEvent {
+                revision: Some("AAAAAf8=".to_string())
+            }))
+        );
+        assert_eq!(
+            parse_message(
+                r#"{"type":"message","uri":"hm://playlist/v2/user/user/rootlist","payloads":["not base64"]}"#,
+                ROOTLIST_URI
+            ),
+            Some(DealerMessage::RootList(RootListEvent { revision: None }))
+        );
+        assert_eq!(
+            parse_message(r#"{"type":"pong"}"#, ROOTLIST_URI),
+            Some(DealerMessage::Pong)
+        );
+        assert_eq!(
+            parse_message(&connection_message("abc%3D"), ROOTLIST_URI),
+            Some(DealerMessage::Connection("abc%3D".to_string()))
+        );
+        assert_eq!(
+            parse_message(
+                r#"{"type":"message","uri":"hm://pusher/v1/connections/abc","headers":{}}"#,
+                ROOTLIST_URI
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn test_dealer_url() {
+        assert_eq!(
+            dealer_url("wss://dealer.spotify.com", "BQ+a/b=")
+                .unwrap()
+                .as_str(),
+            "wss://dealer.spotify.com/?access_token=BQ%2Ba%2Fb%3D"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_rootlist() {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("failed to bind");
+        let dealer = format!("ws://{}", listener.local_addr().unwrap());
+
+        let mut api = mockito::Server::new_async().await;
+        let mut registrations = Vec::new();
+        for (connection_id, token) in [("abc", "token1"), ("def", "token2")] {
+            let registration = api
+                .mock("PUT", "/v1/me/notifications/user")
+                .match_query(mockito::Matcher::UrlEncoded(
+                    "connection_id".to_string(),
+                    connection_id.to_string(),
+                ))
+                .match_header("authorization", format!("Bearer {}", token).as_str())
+                .create_async()
+                .await;
+            registrations.push(registration);
+        }
+
+        let server = tokio::spawn(async move {
+            // First connection is registered, sends a change and drops
+            let (mut socket, uri) = accept(&listener).await;
+            assert_eq!(uri, "/?access_token=token1");
+            socket
+                .send(Message::Text(connection_message("abc")))
+                .await
+                .unwrap();
+            socket
+                .send(Message::Text(rootlist_message(&[0, 0, 0, 1])))
+                .await
+                .unwrap();
+            drop(socket);
+
+            // The client reconnects with a new token and keeps the connection alive with pings
+            let (mut socket, uri) = accept(&listener).await;
+            assert_eq!(uri, "/?access_token=token2");
+            socket
+                .send(Message::Text(connection_message("def")))
+                .await
+                .unwrap();
+            let ping = socket.next().await.unwrap().unwrap();
+            assert_eq!(ping, Message::Text(r#"{"type":"ping"}"#.to_string()));
+            socket
+                .send(Message::Text(r#"{"type":"pong"}"#.to_string()))
+                .await
+                .unwrap();
+            socket
+                .send(Message::Text(rootlist_message(&[0, 0, 0, 2])))
+                .await
+                .unwrap();
+
+            socket
+        });
+
+        let mut subscription = subscribe_rootlist(
+            DealerEndpoints {
+                dealer,
+                notifications: format!("{}/v1/me/notifications/user", api.url()),
+            },
+            reqwest::Client::new(),
+            Arc::new(CountingTokens::default()),
+            "user",
+            Duration::from_millis(50),
+        );
+
+        assert_eq!(
+            subscription.next().await,
+            Some(RootListEvent {
+                revision: Some("AAAAAQ==".to_string())
+            })
+        );
+        assert_eq!(
+            subscription.next().await,
+            Some(RootListEvent {
+                revision: Some("AAAAAg==".to_string())
+            })
+        );
+        for registration in registrations {
+            registration.assert_async().await;
+        }
+
+        server.await.unwrap();
+    }
+}
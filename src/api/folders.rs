@@ -1,108 +1,487 @@
 use std::cell::RefCell;
-use std::collections::HashMap;
+use std::sync::Arc;
 use std::time::SystemTime;
 use std::time::UNIX_EPOCH;
 
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
-
-#[cfg(not(test))]
-fn now() -> SystemTime {
-    SystemTime::now()
-}
-
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
+use serde_json::{Map, Value};
+use serde_with::formats::Flexible;
+use serde_with::TimestampMilliSeconds;
+
+use crate::api::attributes::{
+    FormatAttributes, ItemAttributes, ItemAttributesUpdate, ListAttributes, ListAttributesUpdate,
+};
+use crate::entries::Entry;
+use crate::generators::{is_folder_id, Clock, Generators, IdGenerator, SystemClock};
+use crate::uri::{FolderId, SpotifyId, SpotifyUri};
+use crate::Result;
+
+/// How many ids [`RootList::generate_folder_id`] tries before giving up on finding a free one
+const FOLDER_ID_ATTEMPTS: usize = 100;
+
+#[serde_with::serde_as]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct RootList {
     pub revision: String,
-    length: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) length: Option<u32>,
 
-    attributes: HashMap<String, Value>,
-    timestamp: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) attributes: Option<ListAttributes>,
+    #[serde_as(as = "Option<TimestampMilliSeconds<String, Flexible>>")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) timestamp: Option<SystemTime>,
 
-    contents: RootListContent,
+    pub(crate) contents: ListContent,
 
     #[serde(skip)]
-    generated_uris: RefCell<Vec<String>>,
+    generated_ids: RefCell<Vec<FolderId>>,
+    #[serde(skip)]
+    pub(crate) generators: Generators,
 }
 
 impl RootList {
-    pub fn generate_folder_uri(&self) -> String {
-        let generated_uri = {
-            let generated_uris_borrowed = self.generated_uris.borrow();
-
-            let all_uris: Vec<&String> = self
-                .contents
-                .items
-                .iter()
-                .map(|x| &x.uri)
-                .chain(generated_uris_borrowed.iter())
+    /// Clock for the timestamps of the items added by [`RootList::new_request`]
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.generators.clock = clock;
+    }
+
+    /// Generator of the ids returned by [`RootList::generate_folder_id`]
+    pub fn set_id_generator(&mut self, ids: Arc<dyn IdGenerator>) {
+        self.generators.ids = ids;
+    }
+
+    /// A new folder id, different from the ones in the root list and the ones generated before
+    ///
+    /// Fails if the [`IdGenerator`] returns a malformed id, or keeps returning taken ones.
+    pub fn generate_folder_id(&self) -> Result<FolderId> {
+        let generated_id = {
+            let generated_ids_borrowed = self.generated_ids.borrow();
+
+            let taken: Vec<&str> = self
+                .entries()
+                .filter_map(|entry| match entry.entry {
+                    Entry::FolderStart { id, .. } | Entry::FolderEnd { id } => Some(id),
+                    _ => None,
+                })
+                .chain(generated_ids_borrowed.iter().map(FolderId::as_str))
                 .collect();
 
-            generate_folder_uri(all_uris)
+            generate_folder_id(self.generators.ids.as_ref(), &taken)?
         };
 
-        self.generated_uris.borrow_mut().push(generated_uri.clone());
+        self.generated_ids.borrow_mut().push(generated_id.clone());
 
-        generated_uri
+        Ok(generated_id)
     }
 
     pub fn new_request(&self) -> FolderRequest {
-        FolderRequest::new(&self.revision)
+        let mut request = FolderRequest::new(&self.revision);
+        request.set_clock(self.generators.clock.clone());
+
+        request
+    }
+
+    /// Applies `changes` locally, the same way the server applies them to the root list.
+    /// The revision is left untouched
+    pub fn apply_changes(&mut self, changes: &Changes) -> Result<()> {
+        self.apply_ops(changes.ops())
+    }
+
+    pub(crate) fn apply_ops<'a>(&mut self, ops: impl Iterator<Item = &'a Operation>) -> Result<()> {
+        self.contents
+            .apply_all(ops, &mut self.attributes, &mut self.length)
+    }
+}
+
+impl PagedList for RootList {
+    #[cfg(feature = "protobuf")]
+    fn from_protobuf(bytes: &[u8]) -> Result<Self> {
+        let parts = crate::api::protobuf::decode_list(bytes)?;
+
+        Ok(Self {
+            revision: parts.revision,
+            length: parts.length,
+            attributes: parts.attributes,
+            timestamp: parts.timestamp,
+            contents: parts.contents,
+            generated_ids: RefCell::default(),
+            generators: Generators::default(),
+        })
+    }
+
+    fn revision(&self) -> &str {
+        &self.revision
+    }
+
+    fn contents(&self) -> &ListContent {
+        &self.contents
+    }
+
+    fn contents_mut(&mut self) -> &mut ListContent {
+        &mut self.contents
+    }
+}
+
+/// A list the server hands out in pages, the root list or a playlist
+pub(crate) trait PagedList: serde::de::DeserializeOwned {
+    /// Decodes a `SelectedListContent` message
+    #[cfg(feature = "protobuf")]
+    fn from_protobuf(bytes: &[u8]) -> Result<Self>;
+
+    fn revision(&self) -> &str;
+    fn contents(&self) -> &ListContent;
+    fn contents_mut(&mut self) -> &mut ListContent;
+
+    /// Index of the first item that is missing from a truncated list
+    fn next_page_index(&self) -> Option<u32> {
+        let contents = self.contents();
+
+        contents
+            .truncated
+            .then(|| contents.pos + contents.items.len() as u32)
+    }
+
+    /// Appends the items of the next page, failing if the list changed in between
+    fn merge_page(&mut self, mut page: Self) -> Result<()> {
+        if page.revision() != self.revision() {
+            return Err(format!(
+                "list revision changed while paging ({} -> {})",
+                self.revision(),
+                page.revision()
+            )
+            .into());
+        }
+
+        let expected_pos = self.next_page_index().ok_or("list is not truncated")?;
+        let page = page.contents_mut();
+        if page.pos != expected_pos {
+            return Err(format!("expected list page at {}, got {}", expected_pos, page.pos).into());
+        }
+
+        if page.truncated && page.items.is_empty() {
+            return Err(format!("list page at {} is empty", expected_pos).into());
+        }
+
+        let contents = self.contents_mut();
+        contents.items.append(&mut page.items);
+        contents.meta_items.append(&mut page.meta_items);
+        contents.truncated = page.truncated;
+
+        Ok(())
     }
 }
 
-const URI_LENGTH: usize = 16;
-const URI_CHARSET: &str = "abcdef1234567890";
+#[derive(Deserialize, Debug)]
+pub(crate) struct ChangesResponse {
+    pub(crate) revision: String,
+}
 
-fn generate_folder_uri(uris: Vec<&String>) -> String {
-    loop {
-        let rand_uri = random_string::generate(URI_LENGTH, URI_CHARSET);
+#[derive(Deserialize, Debug)]
+pub(crate) struct RootListDiffResponse {
+    pub(crate) diff: Option<RootListSync>,
+}
+
+/// Operations that were applied to a root list between two revisions
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct RootListSync {
+    #[serde(rename = "fromRevision")]
+    pub from_revision: String,
+    #[serde(rename = "toRevision")]
+    pub to_revision: String,
+
+    #[serde(default)]
+    ops: Vec<Operation>,
+}
 
-        if !uris.contains(&&rand_uri) {
-            return rand_uri;
+impl RootListSync {
+    /// Brings `root_list` from `from_revision` up to `to_revision`
+    pub fn apply(&self, root_list: &mut RootList) -> Result<()> {
+        if root_list.revision != self.from_revision {
+            return Err(format!(
+                "root list is at revision {}, diff starts at {}",
+                root_list.revision, self.from_revision
+            )
+            .into());
         }
+
+        root_list.apply_ops(self.ops.iter())?;
+        root_list.revision = self.to_revision.clone();
+
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
-struct RootListContent {
-    pos: u32,
-    truncated: bool,
-    items: Vec<RootListItem>,
+fn generate_folder_id(ids: &dyn IdGenerator, taken: &[&str]) -> Result<FolderId> {
+    for _ in 0..FOLDER_ID_ATTEMPTS {
+        let id = ids.folder_id();
+        if !is_folder_id(&id) {
+            return Err(
+                format!("generated folder id {:?} isn't 16 lowercase hex digits", id).into(),
+            );
+        }
+
+        if !taken.contains(&id.as_str()) {
+            return Ok(id.parse()?);
+        }
+    }
+
+    Err(format!(
+        "no free folder id after {} generated ones",
+        FOLDER_ID_ATTEMPTS
+    )
+    .into())
+}
 
-    #[serde(rename = "metaItems")]
-    meta_items: Vec<RootListMetaItem>,
+/// Items of a list with their meta items, the root list and playlists share it
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub(crate) struct ListContent {
+    #[serde(default)]
+    pub(crate) pos: u32,
+    #[serde(default)]
+    pub(crate) truncated: bool,
+    pub(crate) items: Vec<ListItem>,
+
+    #[serde(rename = "metaItems", default)]
+    pub(crate) meta_items: Vec<RootListMetaItem>,
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
-struct RootListItem {
-    uri: String,
-    attributes: HashMap<String, Value>,
+impl ListContent {
+    /// Applies `ops` in order, keeping the list's `length` in step if it is known
+    pub(crate) fn apply_all<'a>(
+        &mut self,
+        ops: impl Iterator<Item = &'a Operation>,
+        attributes: &mut Option<ListAttributes>,
+        length: &mut Option<u32>,
+    ) -> Result<()> {
+        for op in ops {
+            self.apply(op, attributes)?;
+        }
+
+        if length.is_some() {
+            *length = Some(self.items.len() as u32);
+        }
+
+        Ok(())
+    }
+
+    /// Applies `op`, list attribute updates go to `attributes`
+    pub(crate) fn apply(
+        &mut self,
+        op: &Operation,
+        attributes: &mut Option<ListAttributes>,
+    ) -> Result<()> {
+        let len = self.items.len() as u32;
+        // Meta items are only kept in sync when the server sent one per item
+        let has_meta_items = self.meta_items.len() == self.items.len();
+
+        match op {
+            Operation::Add(AddOperation { add, .. }) => {
+                let index = if add.add_first {
+                    0
+                } else if add.add_last {
+                    len
+                } else {
+                    add.from_index
+                };
+                if index > len {
+                    return Err(format!("ADD index {} is out of bounds ({})", index, len).into());
+                }
+
+                let index = index as usize;
+                self.items
+                    .splice(index..index, add.items.iter().map(ListItem::from));
+                if has_meta_items {
+                    self.meta_items.splice(
+                        index..index,
+                        add.items.iter().map(|_| RootListMetaItem::default()),
+                    );
+                }
+            }
+            Operation::Rem(RemoveOperation { rem, .. }) if rem.items_as_key => {
+                let keys: Vec<&str> = rem
+                    .items
+                    .iter()
+                    .filter_map(|item| item.get("uri")?.as_str())
+                    .collect();
+
+                for index in (0..self.items.len()).rev() {
+                    if keys.contains(&self.items[index].uri.as_str()) {
+                        self.items.remove(index);
+                        if has_meta_items {
+                            self.meta_items.remove(index);
+                        }
+                    }
+                }
+            }
+            Operation::Rem(RemoveOperation { rem, .. }) => {
+                let end = rem.from_index.checked_add(rem.length).ok_or_else(|| {
+                    format!("REM range {}+{} overflows", rem.from_index, rem.length)
+                })?;
+                if end > len {
+                    return Err(format!(
+                        "REM range {}..{} is out of bounds ({})",
+                        rem.from_index, end, len
+                    )
+                    .into());
+                }
+
+                let range = rem.from_index as usize..end as usize;
+                self.items.drain(range.clone());
+                if has_meta_items {
+                    self.meta_items.drain(range);
+                }
+            }
+            Operation::Mov(MoveOperation { mov, .. }) => {
+                let end = mov.from_index.checked_add(mov.length).ok_or_else(|| {
+                    format!("MOV range {}+{} overflows", mov.from_index, mov.length)
+                })?;
+                if end > len || mov.to_index > len {
+                    return Err(format!(
+                        "MOV {}..{} to {} is out of bounds ({})",
+                        mov.from_index, end, mov.to_index, len
+                    )
+                    .into());
+                }
+
+                // `toIndex` is the index before the moved range is taken out
+                if (mov.from_index..=end).contains(&mov.to_index) {
+                    return Ok(());
+                }
+                let to_index = if mov.to_index > end {
+                    mov.to_index - mov.length
+                } else {
+                    mov.to_index
+                } as usize;
+
+                let range = mov.from_index as usize..end as usize;
+                let moved: Vec<ListItem> = self.items.drain(range.clone()).collect();
+                self.items.splice(to_index..to_index, moved);
+                if has_meta_items {
+                    let moved: Vec<RootListMetaItem> = self.meta_items.drain(range).collect();
+                    self.meta_items.splice(to_index..to_index, moved);
+                }
+            }
+            Operation::UpdateItemAttributes(UpdateItemAttributesOperation {
+                update_item_attributes: update,
+                ..
+            }) => {
+                let item = self.items.get_mut(update.index as usize).ok_or_else(|| {
+                    format!(
+                        "UPDATE_ITEM_ATTRIBUTES index {} is out of bounds ({})",
+                        update.index, len
+                    )
+                })?;
+
+                item.attributes.update(&update.new_attributes);
+            }
+            Operation::UpdateListAttributes(UpdateListAttributesOperation {
+                update_list_attributes: update,
+                ..
+            }) => {
+                attributes
+                    .get_or_insert_with(ListAttributes::default)
+                    .update(&update.new_attributes);
+            }
+            Operation::Unknown(op) => {
+                return Err(format!(
+                    "can't apply unknown operation kind {}",
+                    op.get("kind").unwrap_or(&Value::Null)
+                )
+                .into());
+            }
+        }
+
+        Ok(())
+    }
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
-struct RootListMetaItem {
-    revision: Option<String>,
-    attributes: Option<HashMap<String, Value>>,
-    length: Option<u32>,
-    timestamp: Option<String>,
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub(crate) struct ListItem {
+    pub(crate) uri: String,
+    #[serde(default)]
+    pub(crate) attributes: ItemAttributes,
+}
+
+impl From<&OperationItem> for ListItem {
+    fn from(item: &OperationItem) -> Self {
+        Self {
+            uri: item.uri.clone(),
+            attributes: item.attributes.clone(),
+        }
+    }
+}
+
+/// Details about the list behind a root list item, empty for folders
+#[serde_with::serde_as]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
+pub struct RootListMetaItem {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub revision: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub attributes: Option<ListAttributes>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub length: Option<u32>,
+    #[serde_as(as = "Option<TimestampMilliSeconds<String, Flexible>>")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<SystemTime>,
 
     #[serde(rename = "ownerUsername")]
-    owner_username: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub owner_username: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
+impl RootListMetaItem {
+    pub fn name(&self) -> Option<&str> {
+        self.attributes.as_ref()?.name.as_deref()
+    }
+}
+
+/// A changes request, built by [`FolderRequest`] or [`PlaylistRequest`](crate::PlaylistRequest)
+///
+/// Payloads captured from the web player parse into it as well. Fields this crate doesn't know
+/// about are kept in `extra` and serialized after the known ones, so a parsed payload is
+/// emitted byte for byte as long as the known fields came first.
+/// That only holds for JSON, the protobuf encoding drops most of the `extra` keys of
+/// attributes.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct Changes {
     #[serde(rename = "baseRevision")]
-    base_revision: String,
-    deltas: Vec<Delta>,
+    pub base_revision: String,
+    pub deltas: Vec<Delta>,
 
     // Unknown Features
-    #[serde(rename = "wantResultingRevisions")]
-    want_resulting_revisions: bool,
-    #[serde(rename = "wantSyncResult")]
-    want_sync_result: bool,
-    nonces: Vec<Value>,
+    #[serde(rename = "wantResultingRevisions", default)]
+    pub want_resulting_revisions: bool,
+    #[serde(rename = "wantSyncResult", default)]
+    pub want_sync_result: bool,
+    #[serde(default)]
+    pub nonces: Vec<Value>,
+
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl Changes {
+    pub(crate) fn new(base_revision: &str, ops: Vec<Operation>) -> Self {
+        Self {
+            base_revision: base_revision.to_string(),
+            deltas: vec![Delta {
+                ops,
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    /// Operations of all deltas, in the order the server applies them
+    pub fn ops(&self) -> impl Iterator<Item = &Operation> {
+        self.deltas.iter().flat_map(|delta| delta.ops.iter())
+    }
 }
 
 impl Default for Changes {
@@ -113,30 +492,39 @@ impl Default for Changes {
             want_resulting_revisions: false,
             want_sync_result: false,
             nonces: vec![],
+            extra: Map::new(),
         }
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq, Default)]
-struct Delta {
-    ops: Vec<Operation>,
-    info: DeltaInfo,
-}
-
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
-struct DeltaInfo {
-    user: String,
-    timestamp: String,
-    admin: bool,
-    undo: bool,
-    redo: bool,
-    merge: bool,
-    compressed: bool,
-    migration: bool,
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
+pub struct Delta {
+    pub ops: Vec<Operation>,
+    #[serde(default)]
+    pub info: DeltaInfo,
+
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[serde(default)]
+pub struct DeltaInfo {
+    pub user: String,
+    pub timestamp: String,
+    pub admin: bool,
+    pub undo: bool,
+    pub redo: bool,
+    pub merge: bool,
+    pub compressed: bool,
+    pub migration: bool,
     #[serde(rename = "splitId")]
-    split_id: i32,
+    pub split_id: i32,
+
+    pub source: DeltaInfoSource,
 
-    source: DeltaInfoSource,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 impl Default for DeltaInfo {
@@ -152,16 +540,21 @@ impl Default for DeltaInfo {
             migration: false,
             split_id: 0i32,
             source: DeltaInfoSource::default(),
+            extra: Map::new(),
         }
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
-struct DeltaInfoSource {
-    client: String,
-    app: String,
-    source: String,
-    version: String,
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[serde(default)]
+pub struct DeltaInfoSource {
+    pub client: String,
+    pub app: String,
+    pub source: String,
+    pub version: String,
+
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 impl Default for DeltaInfoSource {
@@ -171,133 +564,288 @@ impl Default for DeltaInfoSource {
             app: "".to_string(),
             source: "".to_string(),
             version: "".to_string(),
+            extra: Map::new(),
         }
     }
 }
 
+/// A single edit of a list, tagged by `kind` on the wire
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 #[serde(tag = "kind")]
-enum Operation {
+pub enum Operation {
     #[serde(rename = "ADD")]
     Add(AddOperation),
     #[serde(rename = "REM")]
     Rem(RemoveOperation),
     #[serde(rename = "MOV")]
     Mov(MoveOperation),
+    #[serde(rename = "UPDATE_ITEM_ATTRIBUTES")]
+    UpdateItemAttributes(UpdateItemAttributesOperation),
+    #[serde(rename = "UPDATE_LIST_ATTRIBUTES")]
+    UpdateListAttributes(UpdateListAttributesOperation),
+    /// An operation of a kind this crate doesn't know, kept as it was received
+    #[serde(untagged)]
+    Unknown(Value),
+}
+
+impl Operation {
+    pub(crate) fn add(from_index: u32, items: Vec<OperationItem>) -> Self {
+        Operation::Add(AddOperation {
+            add: AddOperationParams {
+                from_index,
+                items,
+                ..Default::default()
+            },
+            extra: Map::new(),
+        })
+    }
+
+    pub(crate) fn remove(from_index: u32, length: u32) -> Self {
+        Operation::Rem(RemoveOperation {
+            rem: RemoveOperationParams {
+                from_index,
+                length,
+                ..Default::default()
+            },
+            extra: Map::new(),
+        })
+    }
+
+    /// Removes every item with one of `uris`, wherever it is
+    pub(crate) fn remove_uris(uris: &[SpotifyUri]) -> Self {
+        Operation::Rem(RemoveOperation {
+            rem: RemoveOperationParams {
+                from_index: 0,
+                length: uris.len() as u32,
+                items: uris
+                    .iter()
+                    .map(|uri| serde_json::json!({ "uri": uri.to_string() }))
+                    .collect(),
+                items_as_key: true,
+                extra: Map::new(),
+            },
+            extra: Map::new(),
+        })
+    }
+
+    pub(crate) fn mov(from_index: u32, to_index: u32, length: u32) -> Self {
+        Operation::Mov(MoveOperation {
+            mov: MoveOperationParams {
+                from_index,
+                length,
+                to_index,
+                extra: Map::new(),
+            },
+            extra: Map::new(),
+        })
+    }
+
+    pub(crate) fn update_item_attributes(index: u32, new_attributes: ItemAttributesUpdate) -> Self {
+        Operation::UpdateItemAttributes(UpdateItemAttributesOperation {
+            update_item_attributes: UpdateItemAttributesParams {
+                index,
+                new_attributes,
+                old_attributes: None,
+                extra: Map::new(),
+            },
+            extra: Map::new(),
+        })
+    }
+
+    pub(crate) fn update_list_attributes(new_attributes: ListAttributesUpdate) -> Self {
+        Operation::UpdateListAttributes(UpdateListAttributesOperation {
+            update_list_attributes: UpdateListAttributesParams {
+                new_attributes,
+                old_attributes: None,
+                extra: Map::new(),
+            },
+            extra: Map::new(),
+        })
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
-struct AddOperation {
-    add: AddOperationParams,
+pub struct AddOperation {
+    pub add: AddOperationParams,
+
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
-struct AddOperationParams {
-    #[serde(rename = "fromIndex")]
-    from_index: u32,
+pub struct AddOperationParams {
+    #[serde(rename = "fromIndex", default)]
+    pub from_index: u32,
 
-    items: Vec<OperationItem>,
+    pub items: Vec<OperationItem>,
 
     // Unknown Features
-    #[serde(rename = "addLast")]
-    add_last: bool,
-    #[serde(rename = "addFirst")]
-    add_first: bool,
+    #[serde(rename = "addLast", default)]
+    pub add_last: bool,
+    #[serde(rename = "addFirst", default)]
+    pub add_first: bool,
+
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
-struct RemoveOperation {
-    rem: RemoveOperationParams,
+pub struct RemoveOperation {
+    pub rem: RemoveOperationParams,
+
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
-struct RemoveOperationParams {
-    #[serde(rename = "fromIndex")]
-    from_index: u32,
-    length: u32,
+pub struct RemoveOperationParams {
+    #[serde(rename = "fromIndex", default)]
+    pub from_index: u32,
+    #[serde(default)]
+    pub length: u32,
+
+    /// Items to remove by uri when `items_as_key` is set, `from_index` and `length` are
+    /// ignored then
+    #[serde(default)]
+    pub items: Vec<Value>,
+    #[serde(rename = "itemsAsKey", default)]
+    pub items_as_key: bool,
+
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
 
-    // Unknown Features
-    items: Vec<Value>,
-    #[serde(rename = "itemsAsKey")]
-    items_as_key: bool,
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct MoveOperation {
+    pub mov: MoveOperationParams,
+
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct MoveOperationParams {
+    #[serde(rename = "fromIndex", default)]
+    pub from_index: u32,
+
+    #[serde(default)]
+    pub length: u32,
+
+    /// Index before the moved items are taken out
+    #[serde(rename = "toIndex", default)]
+    pub to_index: u32,
+
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct UpdateItemAttributesOperation {
+    #[serde(rename = "updateItemAttributes")]
+    pub update_item_attributes: UpdateItemAttributesParams,
+
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
-struct MoveOperation {
-    mov: MoveOperationParams,
+pub struct UpdateItemAttributesParams {
+    #[serde(default)]
+    pub index: u32,
+
+    #[serde(rename = "newAttributes")]
+    pub new_attributes: ItemAttributesUpdate,
+    #[serde(
+        rename = "oldAttributes",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub old_attributes: Option<ItemAttributesUpdate>,
+
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
-struct MoveOperationParams {
-    #[serde(rename = "fromIndex")]
-    from_index: u32,
+pub struct UpdateListAttributesOperation {
+    #[serde(rename = "updateListAttributes")]
+    pub update_list_attributes: UpdateListAttributesParams,
 
-    #[serde(rename = "toIndex")]
-    to_index: u32,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
 
-    length: u32,
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct UpdateListAttributesParams {
+    #[serde(rename = "newAttributes")]
+    pub new_attributes: ListAttributesUpdate,
+    #[serde(
+        rename = "oldAttributes",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub old_attributes: Option<ListAttributesUpdate>,
+
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
-struct OperationItem {
-    uri: String,
+pub struct OperationItem {
+    pub uri: String,
+
+    #[serde(default)]
+    pub attributes: ItemAttributes,
 
-    attributes: OperationItemAttrs,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 impl OperationItem {
-    #[allow(dead_code)] // TODO: remove this after we test it
-    fn new_playlist(uri: String) -> Self {
+    /// An item added at `now`
+    pub(crate) fn new(uri: &str, now: SystemTime) -> Self {
         Self {
-            uri: format!("spotify:playlist:{}", uri),
-            attributes: OperationItemAttrs::default(),
+            uri: uri.to_string(),
+            attributes: new_item_attributes(now),
+            extra: Map::new(),
         }
     }
 
-    fn new_start_folder(uri: &str, folder_name: &str) -> Self {
-        Self {
-            uri: format!("spotify:start-group:{}:{}", uri, folder_name),
-            attributes: OperationItemAttrs::default(),
-        }
+    pub(crate) fn from_uri(uri: &SpotifyUri, now: SystemTime) -> Self {
+        Self::new(&uri.to_string(), now)
     }
 
-    fn new_end_folder(uri: &str) -> Self {
-        Self {
-            uri: format!("spotify:end-group:{}", uri),
-            attributes: OperationItemAttrs::default(),
-        }
+    fn new_start_folder(id: &FolderId, folder_name: &str, now: SystemTime) -> Self {
+        Self::from_uri(
+            &SpotifyUri::StartGroup {
+                id: id.clone(),
+                name: folder_name.to_string(),
+            },
+            now,
+        )
     }
-}
 
-#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
-struct OperationItemAttrs {
-    #[serde(rename = "addedBy")]
-    added_by: String,
-
-    timestamp: String,
-
-    #[serde(rename = "seenAt")]
-    seen_at: String,
+    fn new_end_folder(id: &FolderId, now: SystemTime) -> Self {
+        Self::from_uri(&SpotifyUri::EndGroup { id: id.clone() }, now)
+    }
 
-    public: bool,
+    /// Whether the item shows up on the user's public profile
+    pub(crate) fn public(mut self, public: bool) -> Self {
+        self.attributes.public = Some(public);
 
-    #[serde(rename = "formatAttributes")]
-    format_attributes: Vec<Value>,
+        self
+    }
 }
 
-impl Default for OperationItemAttrs {
-    fn default() -> Self {
-        let timestamp = now()
-            .duration_since(UNIX_EPOCH)
-            .expect("Time went backwards");
-
-        Self {
-            added_by: "".to_string(),
-            timestamp: timestamp.as_millis().to_string(),
-            seen_at: "0".to_string(),
-            public: false,
-            format_attributes: vec![],
-        }
+/// Attributes the web player sends for newly added items
+fn new_item_attributes(now: SystemTime) -> ItemAttributes {
+    ItemAttributes {
+        added_by: Some("".to_string()),
+        timestamp: Some(now),
+        seen_at: Some(UNIX_EPOCH),
+        public: Some(false),
+        format_attributes: Some(FormatAttributes::List(vec![])),
+        ..Default::default()
     }
 }
 
@@ -305,6 +853,7 @@ impl Default for OperationItemAttrs {
 pub struct FolderRequest {
     revision: String,
     ops: Vec<Operation>,
+    clock: Arc<dyn Clock>,
 }
 
 impl FolderRequest {
@@ -312,60 +861,118 @@ impl FolderRequest {
         Self {
             revision: revision.to_string(),
             ops: vec![],
+            clock: Arc::new(SystemClock),
         }
     }
 
+    /// Clock for the timestamps of added items, the system time by default
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) -> &mut Self {
+        self.clock = clock;
+
+        self
+    }
+
+    /// Time an item added now gets
+    pub(crate) fn now(&self) -> SystemTime {
+        self.clock.now()
+    }
+
     pub fn build(&self) -> Changes {
-        Changes {
-            base_revision: self.revision.clone(),
-            deltas: vec![Delta {
-                ops: self.ops.clone(),
-                ..Default::default()
-            }],
-            ..Default::default()
+        Changes::new(&self.revision, self.ops.clone())
+    }
+
+    /// Adds the folder `id` around the items from `start_index` up to `end_index`, the end
+    /// index counts the folder's start as already inserted
+    ///
+    /// Fails if the end doesn't come after the start.
+    pub fn add(
+        &mut self,
+        name: &str,
+        id: &FolderId,
+        start_index: u32,
+        end_index: u32,
+    ) -> Result<&mut Self> {
+        if end_index <= start_index {
+            return Err(format!(
+                "folder end {} doesn't come after its start {}",
+                end_index, start_index
+            )
+            .into());
         }
+
+        let now = self.now();
+        self.ops.push(Operation::add(
+            start_index,
+            vec![OperationItem::new_start_folder(id, name, now)],
+        ));
+        self.ops.push(Operation::add(
+            end_index,
+            vec![OperationItem::new_end_folder(id, now)],
+        ));
+
+        Ok(self)
     }
 
-    pub fn add(&mut self, name: &str, uri: &str, start_index: u32, end_index: u32) -> &Self {
-        self.ops.push(Operation::Add(AddOperation {
-            add: AddOperationParams {
-                from_index: start_index,
-                items: vec![OperationItem::new_start_folder(uri, name)],
-                ..Default::default()
-            },
-        }));
+    /// Follows the playlist `id` and places it at `index`
+    pub fn add_playlist(&mut self, id: &SpotifyId, index: u32) -> &mut Self {
+        let item = OperationItem::from_uri(&SpotifyUri::Playlist(*id), self.now());
 
-        self.ops.push(Operation::Add(AddOperation {
-            add: AddOperationParams {
-                from_index: end_index,
-                items: vec![OperationItem::new_end_folder(uri)],
-                ..Default::default()
-            },
-        }));
+        self.add_item(item, index)
+    }
+
+    pub(crate) fn add_item(&mut self, item: OperationItem, index: u32) -> &mut Self {
+        self.ops.push(Operation::add(index, vec![item]));
 
         self
     }
 
-    pub fn remove(&mut self, start_index: u32, length: u32) -> &Self {
-        self.ops.push(Operation::Rem(RemoveOperation {
-            rem: RemoveOperationParams {
-                from_index: start_index,
-                length,
+    pub fn remove(&mut self, start_index: u32, length: u32) -> &mut Self {
+        self.ops.push(Operation::remove(start_index, length));
+
+        self
+    }
+
+    /// Moves `length` items starting at `from_index` in front of the item currently at `to_index`
+    pub fn mov(&mut self, from_index: u32, to_index: u32, length: u32) -> &mut Self {
+        self.ops.push(Operation::mov(from_index, to_index, length));
+
+        self
+    }
+
+    pub fn update_item_attributes(
+        &mut self,
+        index: u32,
+        update: ItemAttributesUpdate,
+    ) -> &mut Self {
+        self.ops
+            .push(Operation::update_item_attributes(index, update));
+
+        self
+    }
+
+    /// Shows or hides the playlist at `index` on the user's public profile
+    pub fn set_public(&mut self, index: u32, public: bool) -> &mut Self {
+        self.update_item_attributes(
+            index,
+            ItemAttributesUpdate {
+                values: ItemAttributes {
+                    public: Some(public),
+                    ..Default::default()
+                },
                 ..Default::default()
             },
-        }));
+        )
+    }
+
+    pub fn update_list_attributes(&mut self, update: ListAttributesUpdate) -> &mut Self {
+        self.ops.push(Operation::update_list_attributes(update));
 
         self
     }
 
-    pub fn mov(&mut self, from_index: u32, to_index: u32, length: u32) -> &Self {
-        self.ops.push(Operation::Mov(MoveOperation {
-            mov: MoveOperationParams {
-                from_index,
-                to_index,
-                length,
-            },
-        }));
+    /// Appends the ops of `other`, which has to be built against the result of these ops
+    pub(crate) fn extend(&mut self, other: &FolderRequest) -> &mut Self {
+        self.ops.extend(other.ops.iter().cloned());
 
         self
     }
@@ -373,39 +980,203 @@ impl FolderRequest {
 
 #[cfg(test)]
 mod tests {
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, UNIX_EPOCH};
 
-    use crate::api::folders::mock_time::set_mock_time;
-
-    use super::{generate_folder_uri, FolderRequest, RootList};
+    use super::{
+        generate_folder_id, Changes, FolderRequest, Operation, PagedList, RootList,
+        FOLDER_ID_ATTEMPTS,
+    };
+    use crate::api::attributes::{ListAttributeKind, ListAttributesUpdate};
+    use crate::generators::{FixedClock, IdGenerator, SeededIds};
 
     const REV: &str = "AAAAELqqrKuzaoeUKYP7gEzCzrx3h0rD";
+    const ADD: &str = include_str!("fixtures/changes/add.json");
+    const REM: &str = include_str!("fixtures/changes/rem.json");
+    const MOV: &str = include_str!("fixtures/changes/mov.json");
+    const UPDATE_ATTRIBUTES: &str = include_str!("fixtures/changes/update_attributes.json");
+    const UNKNOWN_FIELDS: &str = include_str!("fixtures/changes/unknown_fields.json");
+    const ROOT_LIST: &str = r#"{"revision":"AAAAELqqrKuzaoeUKYP7gEzCzrx3h0rD","length":4,"attributes":{},"contents":{"pos":0,"truncated":false,"items":[{"uri":"spotify:start-group:123456789abcdefa:Abablagan","attributes":{"timestamp":"1665495078416","seenAt":"0","public":false}},{"uri":"spotify:end-group:123456789abcdefa","attributes":{"timestamp":"1665495078416","seenAt":"0","public":false}},{"uri":"spotify:playlist:5aNzxEEkRE9MgNkiuXmpOR","attributes":{"timestamp":"1665486971754","seenAt":"0","public":false}},{"uri":"spotify:playlist:3FKTkhbClLGgKdPpbx3aHy","attributes":{"timestamp":"1665486908663","seenAt":"0","public":false}}],"metaItems":[{},{},{"revision":"AAAAAX9FIoTlMkv9e4zCryuZtD/yioLv","attributes":{"name":"My Playlist #2"},"length":0,"timestamp":"1665486971670","ownerUsername":"31h5mfzvglpwfevvaens2flw7smu"},{"revision":"AAAAAvZixvi5cLYefOMaVOKtGZUJS5pE","attributes":{"name":"My Playlist #1"},"length":1,"timestamp":"1665486922515","ownerUsername":"31h5mfzvglpwfevvaens2flw7smu"}]},"timestamp":"1665495078416"}"#;
 
     #[test]
     fn test_root_list_des() {
-        let api_response = r#"{"revision":"AAAAELqqrKuzaoeUKYP7gEzCzrx3h0rD","length":4,"attributes":{},"contents":{"pos":0,"truncated":false,"items":[{"uri":"spotify:start-group:123456789abcdefa:Abablagan","attributes":{"timestamp":"1665495078416","seenAt":"0","public":false}},{"uri":"spotify:end-group:123456789abcdefa","attributes":{"timestamp":"1665495078416","seenAt":"0","public":false}},{"uri":"spotify:playlist:5aNzxEEkRE9MgNkiuXmpOR","attributes":{"timestamp":"1665486971754","seenAt":"0","public":false}},{"uri":"spotify:playlist:3FKTkhbClLGgKdPpbx3aHy","attributes":{"timestamp":"1665486908663","seenAt":"0","public":false}}],"metaItems":[{},{},{"revision":"AAAAAX9FIoTlMkv9e4zCryuZtD/yioLv","attributes":{"name":"My Playlist #2"},"length":0,"timestamp":"1665486971670","ownerUsername":"31h5mfzvglpwfevvaens2flw7smu"},{"revision":"AAAAAvZixvi5cLYefOMaVOKtGZUJS5pE","attributes":{"name":"My Playlist #1"},"length":1,"timestamp":"1665486922515","ownerUsername":"31h5mfzvglpwfevvaens2flw7smu"}]},"timestamp":"1665495078416"}"#;
+        let rl: RootList = serde_json::from_str(ROOT_LIST).expect("Couldn't parse rootlist");
+
+        assert_eq!(rl.revision, REV);
+        rl.generate_folder_id().unwrap();
+    }
+
+    #[test]
+    fn test_root_list_des_revision_only() {
+        let api_response = r#"{"revision":"AAAAELqqrKuzaoeUKYP7gEzCzrx3h0rD","contents":{"pos":0,"truncated":false,"items":[{"uri":"spotify:playlist:5aNzxEEkRE9MgNkiuXmpOR"}]}}"#;
 
         let rl: RootList = serde_json::from_str(api_response).expect("Couldn't parse rootlist");
 
         assert_eq!(rl.revision, REV);
-        rl.generate_folder_uri();
+        assert_eq!(rl.length, None);
+        assert_eq!(rl.timestamp(), None);
+        assert!(rl.contents.meta_items.is_empty());
     }
 
     #[test]
     fn test_root_list_ser() {
-        // TODO: Implement
+        let rl: RootList = serde_json::from_str(ROOT_LIST).expect("Couldn't parse rootlist");
+
+        let serialized = serde_json::to_value(&rl).expect("Couldn't serialize rootlist");
+        let expected: serde_json::Value =
+            serde_json::from_str(ROOT_LIST).expect("Couldn't parse expected json");
+
+        assert_eq!(serialized, expected);
+    }
+
+    #[test]
+    fn test_apply_changes() {
+        let mut rl: RootList = serde_json::from_str(ROOT_LIST).expect("Couldn't parse rootlist");
+
+        let changes = rl
+            .new_request()
+            .mov(3, 0, 1)
+            .add("Inner", &"fedcba9876543210".parse().unwrap(), 2, 3)
+            .unwrap()
+            .add_playlist(&"37i9dQZF1DXcBWIGoYBM5M".parse().unwrap(), 6)
+            .mov(5, 3, 1)
+            .build();
+        rl.apply_changes(&changes).expect("Couldn't apply changes");
+
+        let uris: Vec<&str> = rl.contents.items.iter().map(|x| x.uri.as_str()).collect();
+        assert_eq!(
+            uris,
+            vec![
+                "spotify:playlist:3FKTkhbClLGgKdPpbx3aHy",
+                "spotify:start-group:123456789abcdefa:Abablagan",
+                "spotify:start-group:fedcba9876543210:Inner",
+                "spotify:playlist:5aNzxEEkRE9MgNkiuXmpOR",
+                "spotify:end-group:fedcba9876543210",
+                "spotify:end-group:123456789abcdefa",
+                "spotify:playlist:37i9dQZF1DXcBWIGoYBM5M",
+            ]
+        );
+        assert_eq!(rl.length, Some(7));
+        assert_eq!(rl.contents.meta_items.len(), 7);
+        assert_eq!(
+            rl.contents.meta_items[3].attributes.as_ref().unwrap().name,
+            Some("My Playlist #2".to_string())
+        );
+
+        let out_of_bounds = rl.new_request().remove(6, 2).build();
+        assert!(rl.apply_changes(&out_of_bounds).is_err());
+    }
+
+    #[test]
+    fn test_apply_overflowing_ranges() {
+        let mut rl: RootList = serde_json::from_str(ROOT_LIST).expect("Couldn't parse rootlist");
+        let before = rl.contents.items.len();
+
+        for op in [
+            r#"{"kind":"REM","rem":{"fromIndex":4294967295,"length":2}}"#,
+            r#"{"kind":"MOV","mov":{"fromIndex":4294967295,"length":2,"toIndex":0}}"#,
+            r#"{"kind":"MOV","mov":{"fromIndex":1,"length":4294967295,"toIndex":0}}"#,
+        ] {
+            let changes = Changes::new(
+                &rl.revision,
+                vec![serde_json::from_str(op).expect("Couldn't parse op")],
+            );
+            assert!(rl.apply_changes(&changes).is_err(), "{} applied", op);
+        }
+        assert_eq!(rl.contents.items.len(), before);
+    }
+
+    fn root_list_page(revision: &str, pos: u32, truncated: bool, uris: &[&str]) -> RootList {
+        let items: Vec<String> = uris
+            .iter()
+            .map(|uri| format!(r#"{{"uri":"{}","attributes":{{}}}}"#, uri))
+            .collect();
+        let meta_items: Vec<&str> = uris.iter().map(|_| "{}").collect();
+
+        serde_json::from_str(&format!(
+            r#"{{"revision":"{}","length":3,"attributes":{{}},"contents":{{"pos":{},"truncated":{},"items":[{}],"metaItems":[{}]}},"timestamp":"0"}}"#,
+            revision,
+            pos,
+            truncated,
+            items.join(","),
+            meta_items.join(",")
+        ))
+        .expect("Couldn't parse rootlist")
+    }
+
+    #[test]
+    fn test_merge_pages() {
+        let mut rl = root_list_page(REV, 0, true, &["spotify:playlist:1", "spotify:playlist:2"]);
+        assert_eq!(rl.next_page_index(), Some(2));
+
+        rl.merge_page(root_list_page(REV, 2, false, &["spotify:playlist:3"]))
+            .expect("Couldn't merge page");
+
+        assert_eq!(rl.next_page_index(), None);
+        assert_eq!(
+            rl,
+            root_list_page(
+                REV,
+                0,
+                false,
+                &[
+                    "spotify:playlist:1",
+                    "spotify:playlist:2",
+                    "spotify:playlist:3"
+                ]
+            )
+        );
+    }
+
+    #[test]
+    fn test_merge_pages_revision_changed() {
+        let mut rl = root_list_page(REV, 0, true, &["spotify:playlist:1"]);
+
+        let page = root_list_page("AAAAEXqqrKuzaoeUKYP7gEzCzrx3h0rD", 1, false, &[]);
+        assert!(rl.merge_page(page).is_err());
+    }
+
+    #[test]
+    fn test_merge_pages_wrong_pos() {
+        let mut rl = root_list_page(REV, 0, true, &["spotify:playlist:1"]);
+
+        assert!(rl
+            .merge_page(root_list_page(REV, 0, false, &["spotify:playlist:1"]))
+            .is_err());
+        assert!(rl.merge_page(root_list_page(REV, 1, true, &[])).is_err());
     }
 
     #[test]
     fn test_add_des() {
-        // TODO: Implement
+        let changes: Changes = serde_json::from_str(ADD).expect("Couldn't parse changes");
+
+        assert_eq!(changes.base_revision, REV);
+        let ops: Vec<&Operation> = changes.ops().collect();
+        let Operation::Add(start) = ops[0] else {
+            panic!("expected ADD, got {:?}", ops[0]);
+        };
+        assert_eq!(start.add.from_index, 0);
+        assert_eq!(
+            start.add.items[0].uri,
+            "spotify:start-group:123456789abcdefa:TestFolder"
+        );
+        assert_eq!(
+            start.add.items[0].attributes.timestamp,
+            Some(UNIX_EPOCH + Duration::from_millis(1665582465479))
+        );
+        let Operation::Add(end) = ops[1] else {
+            panic!("expected ADD, got {:?}", ops[1]);
+        };
+        assert_eq!(end.add.from_index, 2);
+        assert_eq!(end.add.items[0].uri, "spotify:end-group:123456789abcdefa");
     }
 
     #[test]
     fn test_add_ser() {
-        set_mock_time(1665582465479);
-
         let changes = FolderRequest::new(REV)
-            .add("TestFolder", "123456789abcdefa", 0, 2)
+            .set_clock(Arc::new(FixedClock::from_millis(1665582465479)))
+            .add("TestFolder", &"123456789abcdefa".parse().unwrap(), 0, 2)
+            .unwrap()
             .build();
 
         let expected = serde_json::from_str(r#"{"baseRevision":"AAAAELqqrKuzaoeUKYP7gEzCzrx3h0rD","deltas":[{"ops":[{"kind":"ADD","add":{"fromIndex":0,"items":[{"uri":"spotify:start-group:123456789abcdefa:TestFolder","attributes":{"addedBy":"","timestamp":"1665582465479","seenAt":"0","public":false,"formatAttributes":[]}}],"addLast":false,"addFirst":false}},{"kind":"ADD","add":{"fromIndex":2,"items":[{"uri":"spotify:end-group:123456789abcdefa","attributes":{"addedBy":"","timestamp":"1665582465479","seenAt":"0","public":false,"formatAttributes":[]}}],"addLast":false,"addFirst":false}}],"info":{"user":"","timestamp":"0","admin":false,"undo":false,"redo":false,"merge":false,"compressed":false,"migration":false,"splitId":0,"source":{"client":"WEBPLAYER","app":"","source":"","version":""}}}],"wantResultingRevisions":false,"wantSyncResult":false,"nonces":[]}"#).expect("Coudln't parse expected json");
@@ -415,7 +1186,14 @@ mod tests {
 
     #[test]
     fn test_rem_des() {
-        // TODO: Implement
+        let changes: Changes = serde_json::from_str(REM).expect("Couldn't parse changes");
+
+        let ops: Vec<&Operation> = changes.ops().collect();
+        let [Operation::Rem(rem)] = ops[..] else {
+            panic!("expected a single REM, got {:?}", ops);
+        };
+        assert_eq!((rem.rem.from_index, rem.rem.length), (23, 2));
+        assert!(!rem.rem.items_as_key);
     }
 
     #[test]
@@ -429,7 +1207,51 @@ mod tests {
 
     #[test]
     fn test_mov_des() {
-        // TODO: Implement
+        let changes: Changes = serde_json::from_str(MOV).expect("Couldn't parse changes");
+
+        let ops: Vec<&Operation> = changes.ops().collect();
+        let [Operation::Mov(mov)] = ops[..] else {
+            panic!("expected a single MOV, got {:?}", ops);
+        };
+        assert_eq!(
+            (mov.mov.from_index, mov.mov.length, mov.mov.to_index),
+            (6, 1, 8)
+        );
+        assert_eq!(changes.deltas[0].info.source.client, "WEBPLAYER");
+    }
+
+    #[test]
+    fn test_unknown_des() {
+        let changes: Changes =
+            serde_json::from_str(UNKNOWN_FIELDS).expect("Couldn't parse changes");
+
+        let ops: Vec<&Operation> = changes.ops().collect();
+        let Operation::Mov(mov) = ops[0] else {
+            panic!("expected MOV, got {:?}", ops[0]);
+        };
+        assert_eq!(mov.mov.extra["itemsHash"], "abc");
+        assert_eq!(mov.extra["opId"], 7);
+        let Operation::Unknown(unknown) = ops[1] else {
+            panic!("expected an unknown operation, got {:?}", ops[1]);
+        };
+        assert_eq!(unknown["kind"], "SET_PICTURE");
+        assert_eq!(changes.deltas[0].info.extra["reason"], "user");
+        assert_eq!(changes.extra["parentRevision"], "AAAAA");
+
+        let mut rl: RootList = serde_json::from_str(ROOT_LIST).expect("Couldn't parse rootlist");
+        assert!(rl.apply_changes(&changes).is_err());
+    }
+
+    #[test]
+    fn test_changes_round_trip() {
+        for fixture in [ADD, REM, MOV, UPDATE_ATTRIBUTES, UNKNOWN_FIELDS] {
+            let changes: Changes = serde_json::from_str(fixture).expect("Couldn't parse changes");
+
+            assert_eq!(
+                serde_json::to_string(&changes).expect("Couldn't serialize changes"),
+                fixture.trim()
+            );
+        }
     }
 
     #[test]
@@ -442,40 +1264,93 @@ mod tests {
     }
 
     #[test]
-    fn test_gen_uri() {
-        generate_folder_uri(vec![&"123456789abcdefa".to_string()]);
+    fn test_update_item_attributes_ser() {
+        let changes = FolderRequest::new(REV).set_public(2, true).build();
+
+        let expected = serde_json::from_str(r#"{"baseRevision":"AAAAELqqrKuzaoeUKYP7gEzCzrx3h0rD","deltas":[{"ops":[{"kind":"UPDATE_ITEM_ATTRIBUTES","updateItemAttributes":{"index":2,"newAttributes":{"values":{"public":true},"noValue":[]}}}],"info":{"user":"","timestamp":"0","admin":false,"undo":false,"redo":false,"merge":false,"compressed":false,"migration":false,"splitId":0,"source":{"client":"WEBPLAYER","app":"","source":"","version":""}}}],"wantResultingRevisions":false,"wantSyncResult":false,"nonces":[]}"#).expect("Coudln't parse expected json");
+
+        assert_eq!(changes, expected);
     }
-}
 
-#[cfg(test)]
-pub mod mock_time {
-    use super::*;
-    use std::{cell::RefCell, time::Duration};
+    #[test]
+    fn test_update_list_attributes_ser() {
+        let changes = FolderRequest::new(REV)
+            .update_list_attributes(ListAttributesUpdate {
+                no_value: vec![ListAttributeKind::Picture],
+                ..Default::default()
+            })
+            .build();
+
+        let expected = serde_json::from_str(r#"{"baseRevision":"AAAAELqqrKuzaoeUKYP7gEzCzrx3h0rD","deltas":[{"ops":[{"kind":"UPDATE_LIST_ATTRIBUTES","updateListAttributes":{"newAttributes":{"values":{},"noValue":["LIST_PICTURE"]}}}],"info":{"user":"","timestamp":"0","admin":false,"undo":false,"redo":false,"merge":false,"compressed":false,"migration":false,"splitId":0,"source":{"client":"WEBPLAYER","app":"","source":"","version":""}}}],"wantResultingRevisions":false,"wantSyncResult":false,"nonces":[]}"#).expect("Coudln't parse expected json");
 
-    thread_local! {
-        static MOCK_TIME: RefCell<Option<SystemTime>> = RefCell::new(None);
+        assert_eq!(changes, expected);
     }
 
-    pub fn now() -> SystemTime {
-        MOCK_TIME.with(|cell| {
-            cell.borrow()
-                .as_ref()
-                .cloned()
-                .unwrap_or_else(SystemTime::now)
-        })
+    #[test]
+    fn test_gen_id() {
+        let taken = SeededIds::new(42).folder_id();
+
+        let id = generate_folder_id(&SeededIds::new(42), &[&taken]).unwrap();
+        assert_ne!(id.as_str(), taken);
+
+        // The ids of the folders in the root list are taken as well
+        let mut rl: RootList = serde_json::from_str(ROOT_LIST).expect("Couldn't parse rootlist");
+        rl.set_id_generator(Arc::new(ListedIds(Mutex::new(vec![
+            "fedcba9876543210".to_string(),
+            "123456789abcdefa".to_string(),
+        ]))));
+        assert_eq!(
+            rl.generate_folder_id().unwrap().as_str(),
+            "fedcba9876543210"
+        );
     }
 
-    pub fn set_mock_time(epoch: u64) {
-        let time: SystemTime = SystemTime::UNIX_EPOCH
-            .checked_add(Duration::from_millis(epoch))
-            .expect("couldn't create time");
-        MOCK_TIME.with(|cell| *cell.borrow_mut() = Some(time));
+    #[test]
+    fn test_gen_id_errors() {
+        let malformed = ListedIds(Mutex::new(vec!["Not-Hex".to_string()]));
+        assert!(generate_folder_id(&malformed, &[]).is_err());
+
+        // A generator stuck on a taken id gives up instead of looping forever
+        let taken = "fedcba9876543210".to_string();
+        let stuck = ListedIds(Mutex::new(vec![taken.clone(); FOLDER_ID_ATTEMPTS]));
+        assert!(generate_folder_id(&stuck, &[&taken]).is_err());
     }
 
-    pub fn clear_mock_time() {
-        MOCK_TIME.with(|cell| *cell.borrow_mut() = None);
+    #[test]
+    fn test_add_folder_backwards() {
+        let id = "123456789abcdefa".parse().unwrap();
+        assert!(FolderRequest::new(REV).add("Empty", &id, 2, 2).is_err());
+        assert!(FolderRequest::new(REV).add("Backwards", &id, 3, 1).is_err());
     }
-}
 
-#[cfg(test)]
-pub use mock_time::now;
+    /// Hands out the listed ids, last first
+    #[derive(Debug)]
+    struct ListedIds(Mutex<Vec<String>>);
+
+    impl IdGenerator for ListedIds {
+        fn folder_id(&self) -> String {
+            self.0.lock().unwrap().pop().expect("out of ids")
+        }
+    }
+
+    #[test]
+    fn test_seeded_root_list() {
+        let mut rl: RootList = serde_json::from_str(ROOT_LIST).expect("Couldn't parse rootlist");
+        rl.set_clock(Arc::new(FixedClock::from_millis(1665582465479)));
+        rl.set_id_generator(Arc::new(SeededIds::new(42)));
+
+        let ids = SeededIds::new(42);
+        let id = rl.generate_folder_id().unwrap();
+        assert_eq!(id.as_str(), ids.folder_id());
+        assert_eq!(rl.generate_folder_id().unwrap().as_str(), ids.folder_id());
+
+        let changes = rl.new_request().add("Seeded", &id, 0, 1).unwrap().build();
+        let Operation::Add(add) = changes.ops().next().unwrap() else {
+            panic!("expected ADD");
+        };
+        assert_eq!(
+            add.add.items[0].attributes.timestamp,
+            Some(UNIX_EPOCH + Duration::from_millis(1665582465479))
+        );
+    }
+}
@@ -1,2 +1,8 @@
+pub mod attributes;
+pub mod dealer;
 pub mod folders;
+pub mod playlist;
+#[cfg(feature = "protobuf")]
+pub(crate) mod protobuf;
+pub mod query;
 pub mod session;
@@ -0,0 +1,555 @@
+use std::fmt;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use serde_with::formats::Flexible;
+use serde_with::TimestampMilliSeconds;
+
+use crate::api::attributes::{
+    FormatAttributes, ItemAttributes, ItemAttributesUpdate, ListAttributeKind, ListAttributes,
+    ListAttributesUpdate,
+};
+use crate::api::folders::{Changes, ListContent, Operation, OperationItem, PagedList, RootList};
+use crate::generators::{Clock, Generators, SystemClock};
+use crate::uri::{FolderId, SpotifyUri};
+use crate::Result;
+
+/// A playlist as returned by the playlist v2 endpoint, see
+/// [`Session::get_playlist`](crate::Session::get_playlist)
+#[serde_with::serde_as]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct Playlist {
+    pub revision: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    length: Option<u32>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    attributes: Option<ListAttributes>,
+    #[serde_as(as = "Option<TimestampMilliSeconds<String, Flexible>>")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    timestamp: Option<SystemTime>,
+    #[serde(rename = "ownerUsername", default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    owner_username: Option<String>,
+
+    pub(crate) contents: ListContent,
+
+    #[serde(skip)]
+    pub(crate) generators: Generators,
+}
+
+impl Playlist {
+    /// Clock for the timestamps of the items added by [`Playlist::new_request`]
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.generators.clock = clock;
+    }
+
+    pub fn items(&self) -> impl Iterator<Item = PlaylistItem<'_>> {
+        self.contents
+            .items
+            .iter()
+            .enumerate()
+            .map(|(index, item)| PlaylistItem {
+                index,
+                uri: &item.uri,
+                attributes: &item.attributes,
+            })
+    }
+
+    /// Number of fetched items
+    pub fn len(&self) -> usize {
+        self.contents.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.contents.items.is_empty()
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.attributes.as_ref()?.name.as_deref()
+    }
+
+    pub fn attributes(&self) -> Option<&ListAttributes> {
+        self.attributes.as_ref()
+    }
+
+    pub fn owner_username(&self) -> Option<&str> {
+        self.owner_username.as_deref()
+    }
+
+    pub fn timestamp(&self) -> Option<SystemTime> {
+        self.timestamp
+    }
+
+    pub fn new_request(&self) -> PlaylistRequest {
+        let mut request = PlaylistRequest::new(&self.revision);
+        request.set_clock(self.generators.clock.clone());
+
+        request
+    }
+
+    /// Applies `changes` locally, the same way the server applies them to the playlist.
+    /// The revision is left untouched
+    pub fn apply_changes(&mut self, changes: &Changes) -> Result<()> {
+        self.contents
+            .apply_all(changes.ops(), &mut self.attributes, &mut self.length)
+    }
+}
+
+impl PagedList for Playlist {
+    #[cfg(feature = "protobuf")]
+    fn from_protobuf(bytes: &[u8]) -> Result<Self> {
+        let parts = crate::api::protobuf::decode_list(bytes)?;
+
+        Ok(Self {
+            revision: parts.revision,
+            length: parts.length,
+            attributes: parts.attributes,
+            timestamp: parts.timestamp,
+            owner_username: parts.owner_username,
+            contents: parts.contents,
+            generators: Generators::default(),
+        })
+    }
+
+    fn revision(&self) -> &str {
+        &self.revision
+    }
+
+    fn contents(&self) -> &ListContent {
+        &self.contents
+    }
+
+    fn contents_mut(&mut self) -> &mut ListContent {
+        &mut self.contents
+    }
+}
+
+/// A track or episode of a playlist together with its position
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PlaylistItem<'a> {
+    pub index: usize,
+    pub uri: &'a str,
+    pub attributes: &'a ItemAttributes,
+}
+
+impl<'a> PlaylistItem<'a> {
+    /// The parsed uri, `None` if it isn't a valid spotify uri
+    pub fn spotify_uri(&self) -> Option<SpotifyUri> {
+        self.uri.parse().ok()
+    }
+
+    /// Username of whoever added the item, `None` when unknown
+    pub fn added_by(&self) -> Option<&'a str> {
+        self.attributes
+            .added_by
+            .as_deref()
+            .filter(|added_by| !added_by.is_empty())
+    }
+
+    /// When the item was added
+    pub fn added_at(&self) -> Option<SystemTime> {
+        self.attributes.timestamp
+    }
+}
+
+/// Build a changes request for the tracks and episodes of a playlist, send it with
+/// [`Session::send_playlist_changes`](crate::Session::send_playlist_changes)
+pub struct PlaylistRequest {
+    revision: String,
+    ops: Vec<Operation>,
+    clock: Arc<dyn Clock>,
+}
+
+impl PlaylistRequest {
+    pub fn new(revision: &str) -> Self {
+        Self {
+            revision: revision.to_string(),
+            ops: vec![],
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// Clock for the timestamps of added items, the system time by default
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) -> &mut Self {
+        self.clock = clock;
+
+        self
+    }
+
+    pub fn build(&self) -> Changes {
+        Changes::new(&self.revision, self.ops.clone())
+    }
+
+    /// Adds the tracks or episodes `uris` at `index`, in order
+    ///
+    /// Fails if one of them isn't a track, an episode or a local file.
+    pub fn add(&mut self, uris: &[SpotifyUri], index: u32) -> Result<&mut Self> {
+        if let Some(uri) = uris.iter().find(|uri| {
+            !matches!(
+                uri,
+                SpotifyUri::Track(_) | SpotifyUri::Episode(_) | SpotifyUri::Local { .. }
+            )
+        }) {
+            return Err(format!("{} can't be added to a playlist", uri).into());
+        }
+
+        let now = self.clock.now();
+        self.ops.push(Operation::add(
+            index,
+            uris.iter()
+                .map(|uri| OperationItem::from_uri(uri, now))
+                .collect(),
+        ));
+
+        Ok(self)
+    }
+
+    pub fn remove(&mut self, start_index: u32, length: u32) -> &mut Self {
+        self.ops.push(Operation::remove(start_index, length));
+
+        self
+    }
+
+    /// Removes every occurrence of `uris`, wherever they are in the playlist
+    pub fn remove_uris(&mut self, uris: &[SpotifyUri]) -> &mut Self {
+        self.ops.push(Operation::remove_uris(uris));
+
+        self
+    }
+
+    /// Moves `length` items starting at `from_index` in front of the item currently at `to_index`
+    pub fn mov(&mut self, from_index: u32, to_index: u32, length: u32) -> &mut Self {
+        self.ops.push(Operation::mov(from_index, to_index, length));
+
+        self
+    }
+
+    pub fn update_item_attributes(
+        &mut self,
+        index: u32,
+        update: ItemAttributesUpdate,
+    ) -> &mut Self {
+        self.ops
+            .push(Operation::update_item_attributes(index, update));
+
+        self
+    }
+
+    pub fn update_list_attributes(&mut self, update: ListAttributesUpdate) -> &mut Self {
+        self.ops.push(Operation::update_list_attributes(update));
+
+        self
+    }
+
+    pub fn rename(&mut self, name: &str) -> &mut Self {
+        self.update_list_attributes(ListAttributesUpdate {
+            values: ListAttributes {
+                name: Some(name.to_string()),
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+    }
+
+    /// Sets the description, an empty one clears it
+    pub fn set_description(&mut self, description: &str) -> &mut Self {
+        if description.is_empty() {
+            return self.update_list_attributes(ListAttributesUpdate {
+                no_value: vec![ListAttributeKind::Description],
+                ..Default::default()
+            });
+        }
+
+        self.update_list_attributes(ListAttributesUpdate {
+            values: ListAttributes {
+                description: Some(description.to_string()),
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+    }
+
+    pub fn set_collaborative(&mut self, collaborative: bool) -> &mut Self {
+        self.update_list_attributes(ListAttributesUpdate {
+            values: ListAttributes {
+                collaborative: Some(collaborative),
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+    }
+}
+
+/// Where [`Session::create_playlist_at`](crate::Session::create_playlist_at) puts the new
+/// playlist in the root list
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PlaylistPlacement {
+    /// At an index of the root list, as used by [`FolderRequest`](crate::FolderRequest)
+    Index(u32),
+    /// First inside the folder with this id
+    InFolder(FolderId),
+}
+
+impl PlaylistPlacement {
+    /// Root list index the placement stands for, fails if the folder doesn't exist
+    pub(crate) fn index(&self, root_list: &RootList) -> Result<u32> {
+        match self {
+            PlaylistPlacement::Index(index) => Ok(*index),
+            PlaylistPlacement::InFolder(id) => {
+                let folder = root_list
+                    .find_by_folder_id(id)
+                    .ok_or_else(|| format!("folder {} not found", id))?;
+
+                Ok(folder.index as u32 + 1)
+            }
+        }
+    }
+}
+
+/// A playlist created by [`Session::create_playlist`](crate::Session::create_playlist)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CreatedPlaylist {
+    pub uri: SpotifyUri,
+    /// Revision of the root list after the playlist was added to it
+    pub root_list_revision: String,
+}
+
+/// A playlist [`Session::create_playlist_at`](crate::Session::create_playlist_at) created
+/// but couldn't add to the root list, the playlist exists without being followed
+#[derive(Debug)]
+pub struct PlaylistPlacementError {
+    pub uri: SpotifyUri,
+    source: Box<dyn std::error::Error>,
+}
+
+impl PlaylistPlacementError {
+    pub(crate) fn new(uri: SpotifyUri, source: Box<dyn std::error::Error>) -> Self {
+        Self { uri, source }
+    }
+}
+
+impl fmt::Display for PlaylistPlacementError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "created playlist {} but couldn't add it to the root list: {}",
+            self.uri, self.source
+        )
+    }
+}
+
+impl std::error::Error for PlaylistPlacementError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(self.source.as_ref())
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub(crate) struct CreatePlaylistResponse {
+    pub(crate) uri: SpotifyUri,
+}
+
+/// Body of a create playlist request, the attributes are set by the first revision
+#[derive(Serialize, Debug)]
+pub(crate) struct CreatePlaylist {
+    ops: Vec<Operation>,
+}
+
+impl CreatePlaylist {
+    pub(crate) fn new(name: &str, description: &str, collaborative: bool) -> Self {
+        let mut values = ListAttributes {
+            name: Some(name.to_string()),
+            description: Some(description.to_string()),
+            collaborative: Some(collaborative),
+            format_attributes: Some(FormatAttributes::List(vec![])),
+            ..Default::default()
+        };
+        values
+            .extra
+            .insert("pictureSize".to_string(), Value::Array(vec![]));
+
+        Self {
+            ops: vec![Operation::update_list_attributes(ListAttributesUpdate {
+                values,
+                ..Default::default()
+            })],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use super::{Playlist, PlaylistRequest};
+    use crate::api::attributes::{ItemAttributeKind, ItemAttributes, ItemAttributesUpdate};
+    use crate::api::folders::PagedList;
+    use crate::generators::FixedClock;
+    use crate::uri::SpotifyUri;
+
+    const PLAYLIST: &str = r#"{"revision":"AAAAB0aN2r2Q8Vqr","length":3,"attributes":{"name":"Focus","description":"deep work","collaborative":false},"contents":{"pos":0,"truncated":true,"items":[{"uri":"spotify:track:5aNzxEEkRE9MgNkiuXmpOR","attributes":{"addedBy":"friend","timestamp":"1665495078416"}},{"uri":"spotify:episode:7N42dgm5tFLK9N8MT7fHC7","attributes":{"addedBy":"","timestamp":"1665495079000"}}]},"timestamp":"1665495079000","ownerUsername":"owner"}"#;
+    const PAGE: &str = r#"{"revision":"AAAAB0aN2r2Q8Vqr","contents":{"pos":2,"items":[{"uri":"spotify:local:artist:album:title:1"}]}}"#;
+
+    #[test]
+    fn test_playlist_des() {
+        let mut playlist: Playlist = serde_json::from_str(PLAYLIST).expect("Couldn't parse");
+        assert_eq!(playlist.next_page_index(), Some(2));
+
+        playlist
+            .merge_page(serde_json::from_str(PAGE).expect("Couldn't parse page"))
+            .expect("failed to merge page");
+
+        assert_eq!(playlist.next_page_index(), None);
+        assert_eq!(playlist.len(), 3);
+        assert_eq!(playlist.name(), Some("Focus"));
+        assert_eq!(playlist.owner_username(), Some("owner"));
+
+        let items: Vec<_> = playlist.items().collect();
+        assert_eq!(items[0].added_by(), Some("friend"));
+        assert_eq!(
+            items[0].added_at(),
+            Some(UNIX_EPOCH + Duration::from_millis(1665495078416))
+        );
+        assert_eq!(
+            items[1].spotify_uri(),
+            Some(SpotifyUri::Episode(
+                "7N42dgm5tFLK9N8MT7fHC7".parse().unwrap()
+            ))
+        );
+        assert_eq!(items[1].added_by(), None);
+        assert_eq!(items[2].index, 2);
+        assert_eq!(items[2].added_at(), None);
+    }
+
+    #[test]
+    fn test_playlist_request_ser() {
+        let changes = PlaylistRequest::new("AAAAB0aN2r2Q8Vqr")
+            .set_clock(Arc::new(FixedClock::from_millis(1665495078416)))
+            .add(
+                &["spotify:track:5aNzxEEkRE9MgNkiuXmpOR".parse().unwrap()],
+                1,
+            )
+            .unwrap()
+            .remove_uris(&["spotify:episode:7N42dgm5tFLK9N8MT7fHC7".parse().unwrap()])
+            .build();
+
+        assert_eq!(
+            serde_json::to_string(&changes).expect("Couldn't serialize"),
+            r#"{"baseRevision":"AAAAB0aN2r2Q8Vqr","deltas":[{"ops":[{"kind":"ADD","add":{"fromIndex":1,"items":[{"uri":"spotify:track:5aNzxEEkRE9MgNkiuXmpOR","attributes":{"addedBy":"","timestamp":"1665495078416","seenAt":"0","public":false,"formatAttributes":[]}}],"addLast":false,"addFirst":false}},{"kind":"REM","rem":{"fromIndex":0,"length":1,"items":[{"uri":"spotify:episode:7N42dgm5tFLK9N8MT7fHC7"}],"itemsAsKey":true}}],"info":{"user":"","timestamp":"0","admin":false,"undo":false,"redo":false,"merge":false,"compressed":false,"migration":false,"splitId":0,"source":{"client":"WEBPLAYER","app":"","source":"","version":""}}}],"wantResultingRevisions":false,"wantSyncResult":false,"nonces":[]}"#
+        );
+    }
+
+    #[test]
+    fn test_playlist_apply_changes() {
+        let mut playlist: Playlist = serde_json::from_str(PLAYLIST).expect("Couldn't parse");
+
+        let changes = playlist
+            .new_request()
+            .mov(1, 0, 1)
+            .add(
+                &["spotify:track:3FKTkhbClLGgKdPpbx3aHy".parse().unwrap()],
+                2,
+            )
+            .unwrap()
+            .remove_uris(&["spotify:track:5aNzxEEkRE9MgNkiuXmpOR".parse().unwrap()])
+            .build();
+        playlist.apply_changes(&changes).expect("Couldn't apply");
+
+        assert_eq!(
+            playlist.items().map(|item| item.uri).collect::<Vec<_>>(),
+            vec![
+                "spotify:episode:7N42dgm5tFLK9N8MT7fHC7",
+                "spotify:track:3FKTkhbClLGgKdPpbx3aHy"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_playlist_request_add_rejects() {
+        let mut request = PlaylistRequest::new("AAAAB0aN2r2Q8Vqr");
+
+        for uri in [
+            "spotify:playlist:5aNzxEEkRE9MgNkiuXmpOR",
+            "spotify:album:4aawyAB9vmqN3uQ7FjRGTy",
+            "spotify:start-group:123456789abcdefa:Focus",
+        ] {
+            assert!(request.add(&[uri.parse().unwrap()], 0).is_err(), "{}", uri);
+        }
+        request
+            .add(
+                &["spotify:local:Artist:Album:Title:215".parse().unwrap()],
+                0,
+            )
+            .expect("local files can be added");
+        assert_eq!(request.build().ops().count(), 1);
+    }
+
+    #[test]
+    fn test_update_list_attributes_ser() {
+        let changes = PlaylistRequest::new("AAAAB0aN2r2Q8Vqr")
+            .rename("Deep Focus")
+            .set_description("")
+            .build();
+
+        let expected = serde_json::from_str(r#"{"baseRevision":"AAAAB0aN2r2Q8Vqr","deltas":[{"ops":[{"kind":"UPDATE_LIST_ATTRIBUTES","updateListAttributes":{"newAttributes":{"values":{"name":"Deep Focus"},"noValue":[]}}},{"kind":"UPDATE_LIST_ATTRIBUTES","updateListAttributes":{"newAttributes":{"values":{},"noValue":["LIST_DESCRIPTION"]}}}],"info":{"user":"","timestamp":"0","admin":false,"undo":false,"redo":false,"merge":false,"compressed":false,"migration":false,"splitId":0,"source":{"client":"WEBPLAYER","app":"","source":"","version":""}}}],"wantResultingRevisions":false,"wantSyncResult":false,"nonces":[]}"#).expect("Coudln't parse expected json");
+
+        assert_eq!(changes, expected);
+    }
+
+    #[test]
+    fn test_update_item_attributes_ser() {
+        let changes = PlaylistRequest::new("AAAAB0aN2r2Q8Vqr")
+            .update_item_attributes(
+                3,
+                ItemAttributesUpdate {
+                    values: ItemAttributes {
+                        seen_at: Some(UNIX_EPOCH + Duration::from_millis(1665495078416)),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+            )
+            .build();
+
+        let expected = serde_json::from_str(r#"{"baseRevision":"AAAAB0aN2r2Q8Vqr","deltas":[{"ops":[{"kind":"UPDATE_ITEM_ATTRIBUTES","updateItemAttributes":{"index":3,"newAttributes":{"values":{"seenAt":"1665495078416"},"noValue":[]}}}],"info":{"user":"","timestamp":"0","admin":false,"undo":false,"redo":false,"merge":false,"compressed":false,"migration":false,"splitId":0,"source":{"client":"WEBPLAYER","app":"","source":"","version":""}}}],"wantResultingRevisions":false,"wantSyncResult":false,"nonces":[]}"#).expect("Coudln't parse expected json");
+
+        assert_eq!(changes, expected);
+    }
+
+    #[test]
+    fn test_apply_attribute_updates() {
+        let mut playlist: Playlist = serde_json::from_str(PLAYLIST).expect("Couldn't parse");
+
+        let changes = playlist
+            .new_request()
+            .rename("Deep Focus")
+            .set_collaborative(true)
+            .update_item_attributes(
+                1,
+                ItemAttributesUpdate {
+                    no_value: vec![ItemAttributeKind::AddedBy],
+                    ..Default::default()
+                },
+            )
+            .build();
+        playlist.apply_changes(&changes).expect("Couldn't apply");
+
+        assert_eq!(playlist.name(), Some("Deep Focus"));
+        assert_eq!(playlist.attributes().unwrap().collaborative, Some(true));
+        assert_eq!(
+            playlist.attributes().unwrap().description.as_deref(),
+            Some("deep work")
+        );
+        assert_eq!(playlist.contents.items[1].attributes.added_by, None);
+
+        let out_of_bounds = playlist
+            .new_request()
+            .update_item_attributes(5, ItemAttributesUpdate::default())
+            .build();
+        assert!(playlist.apply_changes(&out_of_bounds).is_err());
+    }
+}
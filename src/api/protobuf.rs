@@ -0,0 +1,550 @@
+//! Codec between the `application/x-protobuf` form of the playlist endpoints and the JSON
+//! based types of this crate
+//!
+//! The protobuf messages have no room for unknown fields, so unlike JSON the encoding is lossy:
+//! `extra` keys of item attributes are dropped, and of list attributes only `pl3Version`,
+//! `clientId` and `format` are kept.
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use base64::Engine;
+use prost::Message;
+use serde_json::{Map, Value};
+
+use crate::api::attributes::{
+    FormatAttribute, FormatAttributes, ItemAttributeKind, ItemAttributes, ItemAttributesUpdate,
+    ListAttributeKind, ListAttributes, ListAttributesUpdate,
+};
+use crate::api::folders::{
+    Changes, Delta, ListContent, ListItem, Operation, OperationItem, RootListMetaItem,
+};
+use crate::Result;
+
+#[allow(clippy::all)]
+pub(crate) mod proto {
+    include!(concat!(env!("OUT_DIR"), "/spotify.playlist4.proto.rs"));
+}
+
+pub(crate) const CONTENT_TYPE: &str = "application/x-protobuf";
+
+/// The fields of a `SelectedListContent` that the root list and playlists are made of
+pub(crate) struct ListParts {
+    pub(crate) revision: String,
+    pub(crate) length: Option<u32>,
+    pub(crate) attributes: Option<ListAttributes>,
+    pub(crate) timestamp: Option<SystemTime>,
+    pub(crate) owner_username: Option<String>,
+    pub(crate) contents: ListContent,
+}
+
+pub(crate) fn decode_list(bytes: &[u8]) -> Result<ListParts> {
+    let list = proto::SelectedListContent::decode(bytes)?;
+    let contents = list.contents.unwrap_or_default();
+
+    Ok(ListParts {
+        revision: encode_revision(&list.revision.ok_or("list has no revision")?),
+        length: list.length.map(|length| length as u32),
+        attributes: list.attributes.map(list_attributes),
+        timestamp: list.timestamp.map(time),
+        owner_username: list.owner_username,
+        contents: ListContent {
+            pos: contents.pos as u32,
+            truncated: contents.truncated,
+            items: contents
+                .items
+                .into_iter()
+                .map(|item| ListItem {
+                    uri: item.uri,
+                    attributes: item.attributes.map(item_attributes).unwrap_or_default(),
+                })
+                .collect(),
+            meta_items: contents
+                .meta_items
+                .into_iter()
+                .map(|meta| RootListMetaItem {
+                    revision: meta.revision.as_deref().map(encode_revision),
+                    attributes: meta.attributes.map(list_attributes),
+                    length: meta.length.map(|length| length as u32),
+                    timestamp: meta.timestamp.map(time),
+                    owner_username: meta.owner_username,
+                })
+                .collect(),
+        },
+    })
+}
+
+/// Revision of the list a changes request resulted in
+pub(crate) fn decode_revision(bytes: &[u8]) -> Result<String> {
+    let list = proto::SelectedListContent::decode(bytes)?;
+
+    Ok(encode_revision(
+        &list.revision.ok_or("changes response has no revision")?,
+    ))
+}
+
+pub(crate) fn encode_changes(changes: &Changes) -> Result<Vec<u8>> {
+    let message = proto::ListChanges {
+        base_revision: Some(decode_revision_str(&changes.base_revision)?),
+        deltas: changes
+            .deltas
+            .iter()
+            .map(delta)
+            .collect::<Result<Vec<_>>>()?,
+        want_resulting_revisions: Some(changes.want_resulting_revisions),
+        want_sync_result: Some(changes.want_sync_result),
+        nonces: changes
+            .nonces
+            .iter()
+            .map(|nonce| match nonce {
+                Value::Number(nonce) => nonce.as_i64(),
+                Value::String(nonce) => nonce.parse().ok(),
+                _ => None,
+            })
+            .map(|nonce| nonce.ok_or("nonces must be integers"))
+            .collect::<std::result::Result<Vec<_>, _>>()?,
+    };
+
+    Ok(message.encode_to_vec())
+}
+
+fn delta(delta: &Delta) -> Result<proto::Delta> {
+    let info = &delta.info;
+    let client = proto::source_info::Client::from_str_name(&info.source.client)
+        .ok_or_else(|| format!("unknown client {}", info.source.client))?;
+
+    Ok(proto::Delta {
+        base_version: None,
+        ops: delta.ops.iter().map(op).collect::<Result<Vec<_>>>()?,
+        info: Some(proto::ChangeInfo {
+            user: Some(info.user.clone()),
+            timestamp: Some(info.timestamp.parse()?),
+            admin: Some(info.admin),
+            undo: Some(info.undo),
+            redo: Some(info.redo),
+            merge: Some(info.merge),
+            compressed: Some(info.compressed),
+            migration: Some(info.migration),
+            split_id: Some(info.split_id),
+            source: Some(proto::SourceInfo {
+                client: Some(client.into()),
+                app: Some(info.source.app.clone()),
+                source: Some(info.source.source.clone()),
+                version: Some(info.source.version.clone()),
+            }),
+        }),
+    })
+}
+
+fn op(op: &Operation) -> Result<proto::Op> {
+    use proto::op::Kind;
+
+    let mut message = proto::Op::default();
+
+    match op {
+        Operation::Add(add) => {
+            message.set_kind(Kind::Add);
+            message.add = Some(proto::Add {
+                from_index: Some(int32(add.add.from_index)?),
+                items: add.add.items.iter().map(item).collect(),
+                add_last: Some(add.add.add_last),
+                add_first: Some(add.add.add_first),
+            });
+        }
+        Operation::Rem(rem) => {
+            message.set_kind(Kind::Rem);
+            message.rem = Some(proto::Rem {
+                from_index: Some(int32(rem.rem.from_index)?),
+                length: Some(int32(rem.rem.length)?),
+                items: rem
+                    .rem
+                    .items
+                    .iter()
+                    .map(|item| {
+                        let uri = item
+                            .get("uri")
+                            .and_then(Value::as_str)
+                            .ok_or("removed items need an uri")?;
+
+                        Ok(proto::Item {
+                            uri: uri.to_string(),
+                            attributes: None,
+                        })
+                    })
+                    .collect::<Result<Vec<_>>>()?,
+                items_as_key: Some(rem.rem.items_as_key),
+            });
+        }
+        Operation::Mov(mov) => {
+            message.set_kind(Kind::Mov);
+            message.mov = Some(proto::Mov {
+                from_index: int32(mov.mov.from_index)?,
+                length: int32(mov.mov.length)?,
+                to_index: int32(mov.mov.to_index)?,
+            });
+        }
+        Operation::UpdateItemAttributes(update) => {
+            let update = &update.update_item_attributes;
+
+            message.set_kind(Kind::UpdateItemAttributes);
+            message.update_item_attributes = Some(proto::UpdateItemAttributes {
+                index: int32(update.index)?,
+                new_attributes: item_attributes_update(&update.new_attributes)?,
+                old_attributes: update
+                    .old_attributes
+                    .as_ref()
+                    .map(item_attributes_update)
+                    .transpose()?,
+            });
+        }
+        Operation::UpdateListAttributes(update) => {
+            let update = &update.update_list_attributes;
+
+            message.set_kind(Kind::UpdateListAttributes);
+            message.update_list_attributes = Some(proto::UpdateListAttributes {
+                new_attributes: list_attributes_update(&update.new_attributes)?,
+                old_attributes: update
+                    .old_attributes
+                    .as_ref()
+                    .map(list_attributes_update)
+                    .transpose()?,
+            });
+        }
+        Operation::Unknown(op) => {
+            return Err(format!(
+                "can't encode unknown operation kind {}",
+                op.get("kind").unwrap_or(&Value::Null)
+            )
+            .into());
+        }
+    }
+
+    Ok(message)
+}
+
+fn item(item: &OperationItem) -> proto::Item {
+    proto::Item {
+        uri: item.uri.clone(),
+        attributes: Some(proto_item_attributes(&item.attributes)),
+    }
+}
+
+fn item_attributes_update(
+    update: &ItemAttributesUpdate,
+) -> Result<proto::ItemAttributesPartialState> {
+    Ok(proto::ItemAttributesPartialState {
+        values: proto_item_attributes(&update.values),
+        no_value: update
+            .no_value
+            .iter()
+            .map(|kind| {
+                let kind = match kind {
+                    ItemAttributeKind::AddedBy => proto::ItemAttributeKind::ItemAddedBy,
+                    ItemAttributeKind::Timestamp => proto::ItemAttributeKind::ItemTimestamp,
+                    ItemAttributeKind::SeenAt => proto::ItemAttributeKind::ItemSeenAt,
+                    ItemAttributeKind::Public => proto::ItemAttributeKind::ItemPublic,
+                    ItemAttributeKind::Other(kind) => proto::ItemAttributeKind::from_str_name(kind)
+                        .ok_or_else(|| format!("unknown item attribute {}", kind))?,
+                };
+
+                Ok(kind.into())
+            })
+            .collect::<Result<Vec<_>>>()?,
+    })
+}
+
+fn list_attributes_update(
+    update: &ListAttributesUpdate,
+) -> Result<proto::ListAttributesPartialState> {
+    Ok(proto::ListAttributesPartialState {
+        values: proto_list_attributes(&update.values)?,
+        no_value: update
+            .no_value
+            .iter()
+            .map(|kind| {
+                let kind = match kind {
+                    ListAttributeKind::Name => proto::ListAttributeKind::ListName,
+                    ListAttributeKind::Description => proto::ListAttributeKind::ListDescription,
+                    ListAttributeKind::Picture => proto::ListAttributeKind::ListPicture,
+                    ListAttributeKind::Collaborative => proto::ListAttributeKind::ListCollaborative,
+                    ListAttributeKind::DeletedByOwner => {
+                        proto::ListAttributeKind::ListDeletedByOwner
+                    }
+                    ListAttributeKind::Other(kind) => proto::ListAttributeKind::from_str_name(kind)
+                        .ok_or_else(|| format!("unknown list attribute {}", kind))?,
+                };
+
+                Ok(kind.into())
+            })
+            .collect::<Result<Vec<_>>>()?,
+    })
+}
+
+fn item_attributes(attributes: proto::ItemAttributes) -> ItemAttributes {
+    ItemAttributes {
+        added_by: attributes.added_by,
+        timestamp: attributes.timestamp.map(time),
+        seen_at: attributes.seen_at.map(time),
+        public: attributes.public,
+        format_attributes: format_attributes(attributes.format_attributes),
+        extra: Map::new(),
+    }
+}
+
+/// Drops the `extra` keys, see the module docs
+fn proto_item_attributes(attributes: &ItemAttributes) -> proto::ItemAttributes {
+    proto::ItemAttributes {
+        added_by: attributes.added_by.clone(),
+        timestamp: attributes.timestamp.map(millis),
+        seen_at: attributes.seen_at.map(millis),
+        public: attributes.public,
+        format_attributes: proto_format_attributes(&attributes.format_attributes),
+        item_id: None,
+    }
+}
+
+fn list_attributes(attributes: proto::ListAttributes) -> ListAttributes {
+    let mut extra = Map::new();
+    for (key, value) in [
+        ("pl3Version", attributes.pl3_version),
+        ("clientId", attributes.client_id),
+        ("format", attributes.format),
+    ] {
+        if let Some(value) = value {
+            extra.insert(key.to_string(), Value::String(value));
+        }
+    }
+
+    ListAttributes {
+        name: attributes.name,
+        description: attributes.description,
+        picture: attributes
+            .picture
+            .map(|picture| picture.iter().map(|b| format!("{:02x}", b)).collect()),
+        collaborative: attributes.collaborative,
+        deleted_by_owner: attributes.deleted_by_owner,
+        format_attributes: format_attributes(attributes.format_attributes),
+        extra,
+    }
+}
+
+/// Drops the `extra` keys that have no protobuf field, see the module docs
+fn proto_list_attributes(attributes: &ListAttributes) -> Result<proto::ListAttributes> {
+    let extra = |key: &str| {
+        attributes
+            .extra
+            .get(key)
+            .and_then(Value::as_str)
+            .map(str::to_string)
+    };
+
+    Ok(proto::ListAttributes {
+        name: attributes.name.clone(),
+        description: attributes.description.clone(),
+        picture: attributes.picture.as_deref().map(decode_hex).transpose()?,
+        collaborative: attributes.collaborative,
+        pl3_version: extra("pl3Version"),
+        deleted_by_owner: attributes.deleted_by_owner,
+        client_id: extra("clientId"),
+        format: extra("format"),
+        format_attributes: proto_format_attributes(&attributes.format_attributes),
+    })
+}
+
+fn format_attributes(attributes: Vec<proto::FormatListAttribute>) -> Option<FormatAttributes> {
+    (!attributes.is_empty()).then(|| {
+        FormatAttributes::List(
+            attributes
+                .into_iter()
+                .map(|attribute| FormatAttribute {
+                    key: attribute.key.unwrap_or_default(),
+                    value: attribute.value.unwrap_or_default(),
+                })
+                .collect(),
+        )
+    })
+}
+
+/// Format attributes of an unexpected shape have no protobuf form and are left out
+fn proto_format_attributes(
+    attributes: &Option<FormatAttributes>,
+) -> Vec<proto::FormatListAttribute> {
+    let Some(FormatAttributes::List(attributes)) = attributes else {
+        return vec![];
+    };
+
+    attributes
+        .iter()
+        .map(|attribute| proto::FormatListAttribute {
+            key: Some(attribute.key.clone()),
+            value: Some(attribute.value.clone()),
+        })
+        .collect()
+}
+
+/// Indices and lengths are `int32` on the wire
+fn int32(value: u32) -> Result<i32> {
+    i32::try_from(value).map_err(|_| format!("{} doesn't fit a protobuf int32", value).into())
+}
+
+/// Revisions are raw bytes in protobuf and base64 in JSON
+fn encode_revision(revision: &[u8]) -> String {
+    base64::engine::general_purpose::STANDARD.encode(revision)
+}
+
+fn decode_revision_str(revision: &str) -> Result<Vec<u8>> {
+    Ok(base64::engine::general_purpose::STANDARD.decode(revision)?)
+}
+
+fn decode_hex(hex: &str) -> Result<Vec<u8>> {
+    let invalid = || format!("{:?} is not a hex string", hex);
+    // `usize::is_multiple_of` needs Rust 1.87
+    #[allow(clippy::manual_is_multiple_of)]
+    if hex.len() % 2 != 0 {
+        return Err(invalid().into());
+    }
+
+    // By bytes, a non ASCII character is no hex digit and can't be sliced in half
+    let digit = |byte: u8| (byte as char).to_digit(16).ok_or_else(invalid);
+    hex.as_bytes()
+        .chunks(2)
+        .map(|pair| Ok((digit(pair[0])? * 16 + digit(pair[1])?) as u8))
+        .collect()
+}
+
+fn time(millis: i64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_millis(millis.max(0) as u64)
+}
+
+fn millis(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as i64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use prost::Message;
+
+    use super::{decode_hex, decode_list, encode_changes, proto};
+    use crate::api::folders::{Changes, FolderRequest, PagedList, RootList};
+
+    const REV: &str = "AAAAELqqrKuzaoeUKYP7gEzCzrx3h0rD";
+
+    #[test]
+    fn test_decode_root_list() {
+        let expected: RootList = serde_json::from_str(r#"{"revision":"AAAAELqqrKuzaoeUKYP7gEzCzrx3h0rD","length":2,"attributes":{},"contents":{"pos":0,"truncated":false,"items":[{"uri":"spotify:start-group:123456789abcdefa:Focus","attributes":{"timestamp":"1665495078416","public":false}},{"uri":"spotify:playlist:5aNzxEEkRE9MgNkiuXmpOR","attributes":{"timestamp":"1665486971754"}}],"metaItems":[{},{"revision":"AAAAAX9FIoTlMkv9e4zCryuZtD/yioLv","attributes":{"name":"My Playlist #2","picture":"ab67706c"},"length":3,"ownerUsername":"owner"}]},"timestamp":"1665495078416"}"#).expect("Couldn't parse rootlist");
+
+        let message = proto::SelectedListContent {
+            revision: Some(super::decode_revision_str(REV).unwrap()),
+            length: Some(2),
+            attributes: Some(proto::ListAttributes::default()),
+            contents: Some(proto::ListItems {
+                pos: 0,
+                truncated: false,
+                items: vec![
+                    proto::Item {
+                        uri: "spotify:start-group:123456789abcdefa:Focus".to_string(),
+                        attributes: Some(proto::ItemAttributes {
+                            timestamp: Some(1665495078416),
+                            public: Some(false),
+                            ..Default::default()
+                        }),
+                    },
+                    proto::Item {
+                        uri: "spotify:playlist:5aNzxEEkRE9MgNkiuXmpOR".to_string(),
+                        attributes: Some(proto::ItemAttributes {
+                            timestamp: Some(1665486971754),
+                            ..Default::default()
+                        }),
+                    },
+                ],
+                meta_items: vec![
+                    proto::MetaItem::default(),
+                    proto::MetaItem {
+                        revision: Some(
+                            super::decode_revision_str("AAAAAX9FIoTlMkv9e4zCryuZtD/yioLv").unwrap(),
+                        ),
+                        attributes: Some(proto::ListAttributes {
+                            name: Some("My Playlist #2".to_string()),
+                            picture: Some(vec![0xab, 0x67, 0x70, 0x6c]),
+                            ..Default::default()
+                        }),
+                        length: Some(3),
+                        owner_username: Some("owner".to_string()),
+                        ..Default::default()
+                    },
+                ],
+            }),
+            timestamp: Some(1665495078416),
+            ..Default::default()
+        };
+
+        let root_list =
+            RootList::from_protobuf(&message.encode_to_vec()).expect("Couldn't decode rootlist");
+        assert_eq!(root_list, expected);
+
+        assert!(decode_list(&[0xff]).is_err());
+    }
+
+    #[test]
+    fn test_encode_changes() {
+        let changes = FolderRequest::new(REV)
+            .add("Focus", &"123456789abcdefa".parse().unwrap(), 0, 2)
+            .unwrap()
+            .mov(3, 1, 1)
+            .build();
+
+        let message = proto::ListChanges::decode(
+            encode_changes(&changes)
+                .expect("Couldn't encode changes")
+                .as_slice(),
+        )
+        .expect("Couldn't decode changes");
+
+        assert_eq!(message.base_revision, super::decode_revision_str(REV).ok());
+        let ops = &message.deltas[0].ops;
+        assert_eq!(ops[0].kind(), proto::op::Kind::Add);
+        assert_eq!(
+            ops[0].add.as_ref().unwrap().items[0].uri,
+            "spotify:start-group:123456789abcdefa:Focus"
+        );
+        assert_eq!(ops[1].add.as_ref().unwrap().from_index, Some(2));
+        assert_eq!(
+            ops[2].mov,
+            Some(proto::Mov {
+                from_index: 3,
+                length: 1,
+                to_index: 1
+            })
+        );
+        assert_eq!(
+            message.deltas[0]
+                .info
+                .as_ref()
+                .unwrap()
+                .source
+                .as_ref()
+                .unwrap()
+                .client(),
+            proto::source_info::Client::Webplayer
+        );
+
+        let out_of_range = FolderRequest::new(REV).mov(u32::MAX, 1, 0).build();
+        assert!(encode_changes(&out_of_range).is_err());
+
+        let unknown: Changes = serde_json::from_str(
+            r#"{"baseRevision":"AAAAELqqrKuzaoeUKYP7gEzCzrx3h0rD","deltas":[{"ops":[{"kind":"SET_PICTURE"}]}]}"#,
+        )
+        .expect("Couldn't parse changes");
+        assert!(encode_changes(&unknown).is_err());
+    }
+
+    #[test]
+    fn test_decode_hex() {
+        assert_eq!(decode_hex("ab67706C").unwrap(), [0xab, 0x67, 0x70, 0x6c]);
+        assert!(decode_hex("abc").is_err());
+        assert!(decode_hex("+f").is_err());
+        // Even number of bytes, but "é" takes two of them
+        assert!(decode_hex("aé1").is_err());
+        assert!(decode_hex("éé").is_err());
+    }
+}
@@ -0,0 +1,145 @@
+/// Optional fields the server adds to a list and its meta items
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Decoration {
+    Revision,
+    Length,
+    Attributes,
+    Timestamp,
+    Owner,
+}
+
+impl Decoration {
+    pub const ALL: [Decoration; 5] = [
+        Decoration::Revision,
+        Decoration::Length,
+        Decoration::Attributes,
+        Decoration::Timestamp,
+        Decoration::Owner,
+    ];
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            Decoration::Revision => "revision",
+            Decoration::Length => "length",
+            Decoration::Attributes => "attributes",
+            Decoration::Timestamp => "timestamp",
+            Decoration::Owner => "owner",
+        }
+    }
+}
+
+/// Build the query of [`Session::get_root_list_with`](crate::Session::get_root_list_with)
+///
+/// `revision` is always requested, paging and changes depend on it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RootListQuery {
+    pub(crate) decorations: Vec<Decoration>,
+    pub(crate) from: u32,
+    pub(crate) length: Option<u32>,
+    pub(crate) meta_items: bool,
+}
+
+impl Default for RootListQuery {
+    fn default() -> Self {
+        Self {
+            decorations: Decoration::ALL.to_vec(),
+            from: 0,
+            length: None,
+            meta_items: true,
+        }
+    }
+}
+
+impl RootListQuery {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn decorations(&mut self, decorations: &[Decoration]) -> &mut Self {
+        self.decorations = decorations.to_vec();
+
+        self
+    }
+
+    /// Index of the first item to fetch
+    pub fn from(&mut self, from: u32) -> &mut Self {
+        self.from = from;
+
+        self
+    }
+
+    /// Maximum number of items to fetch, everything after `from` by default
+    pub fn length(&mut self, length: u32) -> &mut Self {
+        self.length = Some(length);
+
+        self
+    }
+
+    /// Whether to fetch the meta items (playlist names, owners, ...) of the items
+    ///
+    /// Without them only the revision is requested, the length, attributes, timestamp and
+    /// owner decorations are what the server fills the meta items from.
+    pub fn meta_items(&mut self, meta_items: bool) -> &mut Self {
+        self.meta_items = meta_items;
+
+        self
+    }
+
+    pub(crate) fn to_query_string(&self, from: u32, length: u32) -> String {
+        let mut decorations = vec![Decoration::Revision.as_str()];
+        if self.meta_items {
+            decorations.extend(
+                self.decorations
+                    .iter()
+                    .filter(|decoration| **decoration != Decoration::Revision)
+                    .map(Decoration::as_str),
+            );
+        }
+
+        format!(
+            "decorate={}&from={}&length={}",
+            decorations.join("%2C"),
+            from,
+            length
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Decoration, RootListQuery};
+
+    #[test]
+    fn test_default_query() {
+        assert_eq!(
+            RootListQuery::new().to_query_string(0, 500),
+            "decorate=revision%2Clength%2Cattributes%2Ctimestamp%2Cowner&from=0&length=500"
+        );
+    }
+
+    #[test]
+    fn test_revision_is_always_requested() {
+        let query = RootListQuery::new()
+            .decorations(&[Decoration::Owner, Decoration::Revision])
+            .clone();
+
+        assert_eq!(
+            query.to_query_string(10, 20),
+            "decorate=revision%2Cowner&from=10&length=20"
+        );
+        assert_eq!(
+            RootListQuery::new().decorations(&[]).to_query_string(0, 1),
+            "decorate=revision&from=0&length=1"
+        );
+    }
+
+    #[test]
+    fn test_without_meta_items() {
+        assert_eq!(
+            RootListQuery::new()
+                .meta_items(false)
+                .to_query_string(0, 500),
+            "decorate=revision&from=0&length=500"
+        );
+    }
+}
@@ -19,7 +19,7 @@ pub struct AccessTokenResponse {
 
     #[serde_as(as = "TimestampMilliSeconds<String, Flexible>")]
     #[serde(rename = "accessTokenExpirationTimestampMs")]
-    expiration_time: SystemTime,
+    pub(crate) expiration_time: SystemTime,
 
     #[serde(rename = "isAnonymous")]
     is_anonymous: bool,
@@ -29,11 +29,15 @@ const FAKE_USER_AGENT: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWe
 
 pub async fn get_access_token(
     http_client: &reqwest::Client,
+    base_url: &str,
     dc: &str,
     key: &str,
 ) -> Result<AccessTokenResponse> {
     let res = http_client
-        .get("https://open.spotify.com/get_access_token?reason=transport&productType=web_player")
+        .get(format!(
+            "{}/get_access_token?reason=transport&productType=web_player",
+            base_url
+        ))
         .header("user-agent", FAKE_USER_AGENT)
         .header("Cookie", format!("sp_dc={};sp_key={}", dc, key))
         .send()
@@ -80,7 +84,11 @@ struct ClientTokenResponseGranted {
     extra: HashMap<String, Value>,
 }
 
-pub async fn get_client_token(http_client: &reqwest::Client, client_id: &str) -> Result<String> {
+pub async fn get_client_token(
+    http_client: &reqwest::Client,
+    base_url: &str,
+    client_id: &str,
+) -> Result<String> {
     let fake_client_token_request = ClientTokenRequest {
         client_data: ClientTokenRequestData {
             client_id: client_id.to_string(),
@@ -97,7 +105,7 @@ pub async fn get_client_token(http_client: &reqwest::Client, client_id: &str) ->
     let json = serde_json::to_string(&fake_client_token_request)?;
 
     let res_body = http_client
-        .post("https://clienttoken.spotify.com/v1/clienttoken")
+        .post(format!("{}/v1/clienttoken", base_url))
         .header("Accept", "application/json")
         .header("Content-Type", "application/json")
         .body(json)
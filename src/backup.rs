@@ -0,0 +1,374 @@
+use std::collections::HashSet;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::api::attributes::ItemAttributes;
+use crate::api::folders::{FolderRequest, OperationItem, RootList};
+use crate::entries::Entry;
+use crate::lcs::longest_common_subsequence;
+use crate::Result;
+
+/// Version written by [`RootListBackup::to_json`], older versions are still readable
+pub const BACKUP_VERSION: u32 = 1;
+
+/// Versioned snapshot of a root list, including item attributes, meta items and folder ids
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct RootListBackup {
+    pub version: u32,
+
+    #[serde(rename = "rootList")]
+    pub root_list: RootList,
+}
+
+impl RootListBackup {
+    pub fn new(root_list: RootList) -> Self {
+        Self {
+            version: BACKUP_VERSION,
+            root_list,
+        }
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn from_json(json: &str) -> Result<Self> {
+        let value: Value = serde_json::from_str(json)?;
+
+        let version = value
+            .get("version")
+            .and_then(Value::as_u64)
+            .ok_or("backup has no version")?;
+        if version > BACKUP_VERSION as u64 {
+            return Err(format!("unsupported backup version {}", version).into());
+        }
+
+        Ok(serde_json::from_value(value)?)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        std::fs::write(path, self.to_json()?)?;
+
+        Ok(())
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        Self::from_json(&std::fs::read_to_string(path)?)
+    }
+
+    /// Builds the request that turns `current` into the backed up layout.
+    ///
+    /// `current` may belong to a different account. Folders that are not part of the backup
+    /// are ungrouped, playlists missing from `current` are followed again and playlists that
+    /// are not part of the backup are kept after the restored layout. Added items get their
+    /// backed up attributes, items that `current` already has keep their current ones.
+    pub fn restore(&self, current: &RootList) -> FolderRequest {
+        let items = &self.root_list.contents.items;
+        let target: Vec<&str> = items.iter().map(|item| item.uri.as_str()).collect();
+        let attributes: Vec<&ItemAttributes> = items.iter().map(|item| &item.attributes).collect();
+
+        rebuild(current, &target, &attributes)
+    }
+}
+
+impl RootList {
+    pub fn backup(&self) -> RootListBackup {
+        RootListBackup::new(self.clone())
+    }
+}
+
+/// Builds the request that reorders `current` into `target`.
+///
+/// Entries that keep their relative order (the longest common subsequence) stay in place,
+/// every other entry is moved right behind its predecessor in `target`, or added there when
+/// `current` doesn't have it. Added items take their attributes from `saved`, indexed like
+/// `target`, and are created as new items when `saved` has none for them.
+pub(crate) fn rebuild(
+    current: &RootList,
+    target: &[&str],
+    saved: &[&ItemAttributes],
+) -> FolderRequest {
+    let mut request = current.new_request();
+    let target_uris: HashSet<&str> = target.iter().copied().collect();
+
+    // Items are tracked by their index in `current`, added items get fresh ids
+    let mut uris: Vec<&str> = current
+        .contents
+        .items
+        .iter()
+        .map(|item| item.uri.as_str())
+        .collect();
+    let mut work: Vec<usize> = (0..uris.len()).collect();
+
+    for index in (0..work.len()).rev() {
+        let uri = uris[work[index]];
+        if is_group(uri) && !target_uris.contains(uri) {
+            request.remove(index as u32, 1);
+            work.remove(index);
+        }
+    }
+
+    let work_uris: Vec<&str> = work.iter().map(|&id| uris[id]).collect();
+    let anchors = longest_common_subsequence(&work_uris, target);
+
+    let mut assigned: Vec<Option<usize>> = vec![None; target.len()];
+    let mut used = vec![false; work.len()];
+    for &(work_index, target_index) in &anchors {
+        assigned[target_index] = Some(work[work_index]);
+        used[work_index] = true;
+    }
+    let is_anchor: HashSet<usize> = anchors.iter().map(|&(_, t)| t).collect();
+
+    for (target_index, uri) in target.iter().enumerate() {
+        if assigned[target_index].is_some() {
+            continue;
+        }
+
+        if let Some(work_index) = (0..work.len()).find(|&i| !used[i] && work_uris[i] == *uri) {
+            assigned[target_index] = Some(work[work_index]);
+            used[work_index] = true;
+        }
+    }
+
+    let extras: Vec<usize> = (0..work.len())
+        .filter(|&i| !used[i])
+        .map(|i| work[i])
+        .collect();
+    for id in extras {
+        let end = work.len();
+        move_before(&mut request, &mut work, id, end);
+    }
+
+    for (target_index, uri) in target.iter().enumerate() {
+        if is_anchor.contains(&target_index) {
+            continue;
+        }
+
+        let before = match target_index {
+            0 => 0,
+            _ => {
+                let previous = assigned[target_index - 1].expect("previous item is placed");
+                position(&work, previous) + 1
+            }
+        };
+
+        match assigned[target_index] {
+            Some(id) => move_before(&mut request, &mut work, id, before),
+            None => {
+                match (saved.get(target_index), Entry::parse(uri, None)) {
+                    (Some(&attributes), _) => {
+                        let mut item = OperationItem::new(uri, request.now());
+                        item.attributes = attributes.clone();
+                        request.add_item(item, before as u32)
+                    }
+                    (None, Entry::Playlist { id, .. }) => request.add_playlist(&id, before as u32),
+                    (None, _) => {
+                        request.add_item(OperationItem::new(uri, request.now()), before as u32)
+                    }
+                };
+
+                uris.push(uri);
+                work.insert(before, uris.len() - 1);
+                assigned[target_index] = Some(uris.len() - 1);
+            }
+        }
+    }
+
+    request
+}
+
+fn is_group(uri: &str) -> bool {
+    matches!(
+        Entry::parse(uri, None),
+        Entry::FolderStart { .. } | Entry::FolderEnd { .. }
+    )
+}
+
+fn position(work: &[usize], id: usize) -> usize {
+    work.iter()
+        .position(|&x| x == id)
+        .expect("item is part of the work list")
+}
+
+fn move_before(request: &mut FolderRequest, work: &mut Vec<usize>, id: usize, before: usize) {
+    let from = position(work, id);
+    if from == before || from + 1 == before {
+        return;
+    }
+
+    request.mov(from as u32, before as u32, 1);
+
+    work.remove(from);
+    let to = if before > from { before - 1 } else { before };
+    work.insert(to, id);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RootListBackup, BACKUP_VERSION};
+    use crate::api::folders::RootList;
+
+    fn root_list(revision: &str, uris: &[&str]) -> RootList {
+        let items: Vec<String> = uris
+            .iter()
+            .map(|uri| {
+                format!(
+                    r#"{{"uri":"{}","attributes":{{"timestamp":"1665495078416"}}}}"#,
+                    uri
+                )
+            })
+            .collect();
+        let meta_items: Vec<&str> = uris.iter().map(|_| "{}").collect();
+
+        serde_json::from_str(&format!(
+            r#"{{"revision":"{}","length":{},"attributes":{{}},"contents":{{"pos":0,"truncated":false,"items":[{}],"metaItems":[{}]}},"timestamp":"1665495078416"}}"#,
+            revision,
+            uris.len(),
+            items.join(","),
+            meta_items.join(",")
+        ))
+        .expect("Couldn't parse rootlist")
+    }
+
+    fn uris(root_list: &RootList) -> Vec<&str> {
+        root_list
+            .contents
+            .items
+            .iter()
+            .map(|item| item.uri.as_str())
+            .collect()
+    }
+
+    fn assert_restores(backup: &[&str], current: &[&str], expected: &[&str]) {
+        let backup = root_list("backup", backup).backup();
+        let mut current = root_list("current", current);
+
+        let changes = backup.restore(&current).build();
+        current
+            .apply_changes(&changes)
+            .expect("Couldn't apply changes");
+
+        assert_eq!(uris(&current), expected);
+    }
+
+    #[test]
+    fn test_backup_round_trip() {
+        let backup = root_list(
+            "AAAAELqqrKuzaoeUKYP7gEzCzrx3h0rD",
+            &[
+                "spotify:start-group:123456789abcdefa:Work",
+                "spotify:playlist:5aNzxEEkRE9MgNkiuXmpOR",
+                "spotify:end-group:123456789abcdefa",
+            ],
+        )
+        .backup();
+
+        let json = backup.to_json().expect("Couldn't serialize backup");
+        let restored = RootListBackup::from_json(&json).expect("Couldn't parse backup");
+
+        assert_eq!(restored, backup);
+        assert_eq!(restored.version, BACKUP_VERSION);
+    }
+
+    #[test]
+    fn test_backup_unsupported_version() {
+        let mut backup = root_list("rev", &[]).backup();
+        backup.version = BACKUP_VERSION + 1;
+
+        let json = serde_json::to_string(&backup).expect("Couldn't serialize backup");
+
+        assert!(RootListBackup::from_json(&json).is_err());
+    }
+
+    #[test]
+    fn test_restore_same_layout() {
+        let layout = [
+            "spotify:start-group:123456789abcdefa:Work",
+            "spotify:playlist:1",
+            "spotify:end-group:123456789abcdefa",
+        ];
+
+        let backup = root_list("backup", &layout).backup();
+        let changes = backup.restore(&root_list("current", &layout)).build();
+
+        assert_eq!(changes, root_list("current", &[]).new_request().build());
+    }
+
+    #[test]
+    fn test_restore_layout() {
+        assert_restores(
+            &[
+                "spotify:start-group:123456789abcdefa:Work",
+                "spotify:playlist:1",
+                "spotify:start-group:aaaaaaaaaaaaaaaa:Focus",
+                "spotify:playlist:2",
+                "spotify:end-group:aaaaaaaaaaaaaaaa",
+                "spotify:end-group:123456789abcdefa",
+                "spotify:playlist:3",
+                "spotify:playlist:4",
+            ],
+            &[
+                "spotify:playlist:4",
+                "spotify:start-group:bbbbbbbbbbbbbbbb:Old",
+                "spotify:playlist:2",
+                "spotify:playlist:5",
+                "spotify:end-group:bbbbbbbbbbbbbbbb",
+                "spotify:playlist:3",
+            ],
+            &[
+                "spotify:start-group:123456789abcdefa:Work",
+                "spotify:playlist:1",
+                "spotify:start-group:aaaaaaaaaaaaaaaa:Focus",
+                "spotify:playlist:2",
+                "spotify:end-group:aaaaaaaaaaaaaaaa",
+                "spotify:end-group:123456789abcdefa",
+                "spotify:playlist:3",
+                "spotify:playlist:4",
+                "spotify:playlist:5",
+            ],
+        );
+    }
+
+    #[test]
+    fn test_restore_refollows_playlists() {
+        let backup = root_list("backup", &["spotify:playlist:5aNzxEEkRE9MgNkiuXmpOR"]).backup();
+
+        let changes = backup.restore(&root_list("current", &[])).build();
+
+        let expected = serde_json::to_value(&changes).expect("Couldn't serialize changes");
+        assert_eq!(
+            expected["deltas"][0]["ops"][0]["add"]["items"][0]["uri"],
+            "spotify:playlist:5aNzxEEkRE9MgNkiuXmpOR"
+        );
+        assert_eq!(expected["baseRevision"], "current");
+    }
+
+    #[test]
+    fn test_restore_keeps_attributes() {
+        let mut backup = root_list(
+            "backup",
+            &[
+                "spotify:playlist:5aNzxEEkRE9MgNkiuXmpOR",
+                "spotify:playlist:3FKTkhbClLGgKdPpbx3aHy",
+            ],
+        );
+        backup.contents.items[0].attributes.public = Some(true);
+        let backup = backup.backup();
+        let mut current = root_list("current", &["spotify:playlist:3FKTkhbClLGgKdPpbx3aHy"]);
+        current.contents.items[0].attributes.timestamp = None;
+
+        let changes = backup.restore(&current).build();
+        current
+            .apply_changes(&changes)
+            .expect("Couldn't apply changes");
+
+        // The added playlist gets its saved timestamp and public flag, the kept one is untouched
+        assert_eq!(
+            current.contents.items[0].attributes,
+            backup.root_list.contents.items[0].attributes
+        );
+        assert_eq!(current.contents.items[1].attributes.timestamp, None);
+    }
+}
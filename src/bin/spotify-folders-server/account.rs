@@ -0,0 +1,251 @@
+use std::collections::VecDeque;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use spotify_private_api::{
+    is_revision_conflict, Changes, Plan, RootList, RootListEditor, Session, TreeNode,
+};
+
+use crate::config::AccountConfig;
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+/// The session is logged in again when its tokens are valid for less than this
+const TOKEN_MARGIN: Duration = Duration::from_secs(60);
+
+/// How often a write is tried again when the library changed in the meantime
+const ATTEMPTS: usize = 3;
+
+/// Number of writes [`Account::history`] keeps
+const HISTORY_LENGTH: usize = 100;
+
+/// A high level edit, addressed by paths like `Work/Focus`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "op", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Operation {
+    /// Creates an empty folder, its parent has to exist
+    CreateFolder { path: String },
+    /// Moves a folder or playlist into `folder`, "/" is the top level
+    Move { path: String, folder: String },
+    /// Renames a folder
+    Rename { path: String, name: String },
+    /// Removes a folder and keeps its contents in its place
+    Ungroup { path: String },
+}
+
+/// A write sent through the server
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct HistoryEntry {
+    /// Milliseconds since the unix epoch
+    pub time: u64,
+    pub operations: Vec<Operation>,
+    pub plan: Vec<String>,
+    /// Revision of the root list after the write
+    pub revision: String,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Tree {
+    pub revision: String,
+    pub tree: Vec<TreeNode>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Applied {
+    pub revision: String,
+    pub plan: Vec<String>,
+    /// `false` for dry runs and operations that change nothing
+    pub sent: bool,
+}
+
+/// Why a request failed, decides the response status
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Failure {
+    /// The operations don't apply to the library
+    Invalid(String),
+    /// The library kept changing while the write was retried
+    Conflict(String),
+    /// Spotify failed
+    Upstream(String),
+}
+
+/// A spotify account and the writes sent to it
+///
+/// Callers keep it behind a lock, so the writes of an account don't race each other.
+#[derive(Debug)]
+pub struct Account {
+    session: Session,
+    /// Used to log in again when the tokens expire, without them the session is kept as is
+    credentials: Option<AccountConfig>,
+    history: VecDeque<HistoryEntry>,
+}
+
+impl Account {
+    pub async fn login(config: &AccountConfig) -> Result<Self> {
+        let session = Session::new(&config.dc, &config.key, &config.user_id).await?;
+
+        Ok(Self {
+            credentials: Some(config.clone()),
+            ..Self::new(session)
+        })
+    }
+
+    pub fn new(session: Session) -> Self {
+        Self {
+            session,
+            credentials: None,
+            history: VecDeque::new(),
+        }
+    }
+
+    pub async fn tree(&mut self) -> std::result::Result<Tree, Failure> {
+        let root_list = self.root_list().await?;
+
+        Ok(Tree {
+            tree: root_list.tree(),
+            revision: root_list.revision,
+        })
+    }
+
+    /// Applies `operations` to the current library and sends them as a single changes request,
+    /// the library is fetched again and the operations replayed on a revision conflict
+    pub async fn apply(
+        &mut self,
+        operations: &[Operation],
+        dry_run: bool,
+    ) -> std::result::Result<Applied, Failure> {
+        for attempt in 1..=ATTEMPTS {
+            let root_list = self.root_list().await?;
+            let (changes, plan) =
+                edit(&root_list, operations).map_err(|e| Failure::Invalid(e.to_string()))?;
+            let plan: Vec<String> = plan.steps.iter().map(ToString::to_string).collect();
+
+            if dry_run || changes.ops().next().is_none() {
+                return Ok(Applied {
+                    revision: root_list.revision,
+                    plan,
+                    sent: false,
+                });
+            }
+
+            match self.session.send_changes(&changes).await {
+                Ok(()) => {}
+                Err(e) if is_revision_conflict(e.as_ref()) && attempt < ATTEMPTS => continue,
+                Err(e) => return Err(failure(e)),
+            }
+
+            let revision = self.root_list().await?.revision;
+            self.record(operations, &plan, &revision);
+
+            return Ok(Applied {
+                revision,
+                plan,
+                sent: true,
+            });
+        }
+
+        unreachable!("the last attempt returns")
+    }
+
+    /// The latest writes, oldest first
+    pub fn history(&self) -> Vec<HistoryEntry> {
+        self.history.iter().cloned().collect()
+    }
+
+    fn record(&mut self, operations: &[Operation], plan: &[String], revision: &str) {
+        if self.history.len() == HISTORY_LENGTH {
+            self.history.pop_front();
+        }
+
+        self.history.push_back(HistoryEntry {
+            time: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis() as u64,
+            operations: operations.to_vec(),
+            plan: plan.to_vec(),
+            revision: revision.to_string(),
+        });
+    }
+
+    async fn root_list(&mut self) -> std::result::Result<RootList, Failure> {
+        self.refresh().await?;
+
+        self.session.get_root_list().await.map_err(failure)
+    }
+
+    /// Logs in again when the tokens are about to expire
+    async fn refresh(&mut self) -> std::result::Result<(), Failure> {
+        let Some(credentials) = &self.credentials else {
+            return Ok(());
+        };
+        if self
+            .session
+            .tokens()
+            .is_valid_at(SystemTime::now() + TOKEN_MARGIN)
+        {
+            return Ok(());
+        }
+
+        self.session = Session::new(&credentials.dc, &credentials.key, &credentials.user_id)
+            .await
+            .map_err(failure)?;
+
+        Ok(())
+    }
+}
+
+fn edit(root_list: &RootList, operations: &[Operation]) -> Result<(Changes, Plan)> {
+    let mut editor = RootListEditor::new(root_list);
+    for operation in operations {
+        match operation {
+            Operation::CreateFolder { path } => editor.create_folder(path)?,
+            Operation::Move { path, folder } => editor.move_to(path, folder)?,
+            Operation::Rename { path, name } => editor.rename(path, name)?,
+            Operation::Ungroup { path } => editor.ungroup(path)?,
+        };
+    }
+
+    let changes = editor.build();
+    let plan = root_list.plan(&changes)?;
+
+    Ok((changes, plan))
+}
+
+fn failure(error: Box<dyn std::error::Error>) -> Failure {
+    match is_revision_conflict(error.as_ref()) {
+        true => Failure::Conflict(error.to_string()),
+        false => Failure::Upstream(error.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Operation;
+
+    #[test]
+    fn test_operations() {
+        let operations: Vec<Operation> = serde_json::from_str(
+            r#"[
+                {"op": "create_folder", "path": "Work/Focus"},
+                {"op": "move", "path": "Deep", "folder": "Work/Focus"},
+                {"op": "rename", "path": "Work", "name": "Job"},
+                {"op": "ungroup", "path": "Job"}
+            ]"#,
+        )
+        .expect("failed to parse operations");
+        assert_eq!(
+            operations[1],
+            Operation::Move {
+                path: "Deep".to_string(),
+                folder: "Work/Focus".to_string()
+            }
+        );
+
+        assert!(serde_json::from_str::<Operation>(r#"{"op": "delete", "path": "Work"}"#).is_err());
+        assert!(
+            serde_json::from_str::<Operation>(r#"{"op": "ungroup", "path": "A", "to": "B"}"#)
+                .is_err()
+        );
+    }
+}
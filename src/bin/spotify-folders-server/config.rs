@@ -0,0 +1,98 @@
+use std::net::SocketAddr;
+use std::path::Path;
+
+use serde::Deserialize;
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+/// The server's TOML configuration file
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    #[serde(default = "default_listen")]
+    pub listen: SocketAddr,
+    /// Bearer tokens clients authenticate with, each of them can use every account
+    pub tokens: Vec<String>,
+    pub accounts: Vec<AccountConfig>,
+}
+
+/// A spotify account, served under `/accounts/{name}`
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct AccountConfig {
+    pub name: String,
+    pub dc: String,
+    pub key: String,
+    pub user_id: String,
+}
+
+fn default_listen() -> SocketAddr {
+    SocketAddr::from(([127, 0, 0, 1], 7878))
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+
+        Self::parse(&contents)
+            .map_err(|e| format!("failed to parse {}: {}", path.display(), e).into())
+    }
+
+    pub fn parse(contents: &str) -> Result<Self> {
+        let config: Config = toml::from_str(contents)?;
+
+        if config.tokens.iter().any(String::is_empty) {
+            return Err("tokens can't be empty".into());
+        }
+        if config.tokens.is_empty() {
+            return Err("no tokens, every request would be rejected".into());
+        }
+        for (i, account) in config.accounts.iter().enumerate() {
+            if account.name.is_empty() || account.name.contains('/') {
+                return Err(format!("invalid account name {:?}", account.name).into());
+            }
+            if config.accounts[..i].iter().any(|a| a.name == account.name) {
+                return Err(format!("account {} is listed twice", account.name).into());
+            }
+        }
+
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Config;
+
+    #[test]
+    fn test_parse() {
+        let config = Config::parse(
+            r#"
+tokens = ["secret"]
+
+[[accounts]]
+name = "me"
+dc = "dc"
+key = "key"
+user_id = "user"
+"#,
+        )
+        .expect("failed to parse config");
+        assert_eq!(config.listen.to_string(), "127.0.0.1:7878");
+        assert_eq!(config.tokens, ["secret"]);
+        assert_eq!(config.accounts[0].user_id, "user");
+
+        assert!(Config::parse("tokens = []\naccounts = []").is_err());
+        assert!(Config::parse(
+            r#"
+tokens = ["secret"]
+accounts = [
+    { name = "me", dc = "dc", key = "key", user_id = "a" },
+    { name = "me", dc = "dc", key = "key", user_id = "b" },
+]
+"#
+        )
+        .is_err());
+    }
+}
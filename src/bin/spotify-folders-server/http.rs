@@ -0,0 +1,373 @@
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use hyper::body::{Bytes, HttpBody};
+use hyper::header::{AUTHORIZATION, CONTENT_LENGTH, CONTENT_TYPE, WWW_AUTHENTICATE};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, StatusCode};
+use serde::Serialize;
+use tokio::sync::Mutex;
+
+use crate::account::{Account, Failure, Operation};
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+/// Largest request body read, in bytes
+const MAX_BODY_SIZE: usize = 1024 * 1024;
+
+/// The accounts and the tokens that may use them
+#[derive(Debug)]
+pub struct Server {
+    tokens: Vec<String>,
+    accounts: BTreeMap<String, Mutex<Account>>,
+}
+
+impl Server {
+    pub fn new(tokens: Vec<String>, accounts: impl IntoIterator<Item = (String, Account)>) -> Self {
+        Self {
+            tokens,
+            accounts: accounts
+                .into_iter()
+                .map(|(name, account)| (name, Mutex::new(account)))
+                .collect(),
+        }
+    }
+
+    /// Serves requests on `addr` until ctrl-c is pressed
+    pub async fn serve(self: Arc<Self>, addr: SocketAddr) -> Result<()> {
+        let server = hyper::Server::try_bind(&addr)?.serve(make_service_fn(move |_| {
+            let server = self.clone();
+
+            async move { Ok::<_, Infallible>(service_fn(move |req| server.clone().handle(req))) }
+        }));
+        eprintln!("listening on http://{}", server.local_addr());
+
+        server
+            .with_graceful_shutdown(async {
+                let _ = tokio::signal::ctrl_c().await;
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn handle(
+        self: Arc<Self>,
+        req: Request<Body>,
+    ) -> std::result::Result<Response<Body>, Infallible> {
+        if !self.authorized(&req) {
+            let mut res = error(StatusCode::UNAUTHORIZED, "missing or invalid token");
+            res.headers_mut()
+                .insert(WWW_AUTHENTICATE, "Bearer".parse().unwrap());
+
+            return Ok(res);
+        }
+
+        let (parts, body) = req.into_parts();
+        let path: Vec<&str> = parts
+            .uri
+            .path()
+            .split('/')
+            .filter(|segment| !segment.is_empty())
+            .collect();
+
+        let (name, resource) = match path.as_slice() {
+            ["accounts"] => {
+                return Ok(match parts.method {
+                    Method::GET => json(StatusCode::OK, &self.accounts.keys().collect::<Vec<_>>()),
+                    _ => error(StatusCode::METHOD_NOT_ALLOWED, "method not allowed"),
+                });
+            }
+            ["accounts", name, resource] => (*name, *resource),
+            _ => return Ok(error(StatusCode::NOT_FOUND, "not found")),
+        };
+        let Some(account) = self.accounts.get(name) else {
+            return Ok(error(
+                StatusCode::NOT_FOUND,
+                &format!("no account named {}", name),
+            ));
+        };
+
+        let res = match (&parts.method, resource) {
+            (&Method::GET, "tree") => match account.lock().await.tree().await {
+                Ok(tree) => json(StatusCode::OK, &tree),
+                Err(failure) => failed(failure),
+            },
+            (&Method::GET, "history") => json(StatusCode::OK, &account.lock().await.history()),
+            (&Method::POST, "operations") => {
+                let too_large = parts
+                    .headers
+                    .get(CONTENT_LENGTH)
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(|value| value.parse::<u64>().ok())
+                    .is_some_and(|length| length > MAX_BODY_SIZE as u64);
+                if too_large {
+                    return Ok(body_too_large());
+                }
+                let body = match read_body(body).await {
+                    Ok(body) => body,
+                    Err(res) => return Ok(res),
+                };
+                let operations: Vec<Operation> = match serde_json::from_slice(&body) {
+                    Ok(operations) => operations,
+                    Err(e) => return Ok(error(StatusCode::BAD_REQUEST, &e.to_string())),
+                };
+                let dry_run = parts
+                    .uri
+                    .query()
+                    .unwrap_or_default()
+                    .split('&')
+                    .any(|param| param == "dry_run" || param == "dry_run=true");
+
+                match account.lock().await.apply(&operations, dry_run).await {
+                    Ok(applied) => json(StatusCode::OK, &applied),
+                    Err(failure) => failed(failure),
+                }
+            }
+            (_, "tree" | "history" | "operations") => {
+                error(StatusCode::METHOD_NOT_ALLOWED, "method not allowed")
+            }
+            _ => error(StatusCode::NOT_FOUND, "not found"),
+        };
+
+        Ok(res)
+    }
+
+    fn authorized(&self, req: &Request<Body>) -> bool {
+        let Some(token) = req
+            .headers()
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+        else {
+            return false;
+        };
+
+        self.tokens
+            .iter()
+            .any(|known| constant_time_eq(known.as_bytes(), token.as_bytes()))
+    }
+}
+
+/// Reads at most [`MAX_BODY_SIZE`] bytes, `Content-Length` may be missing or wrong
+async fn read_body(mut body: Body) -> std::result::Result<Bytes, Response<Body>> {
+    let mut bytes = Vec::new();
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk.map_err(|e| error(StatusCode::BAD_REQUEST, &e.to_string()))?;
+        if bytes.len() + chunk.len() > MAX_BODY_SIZE {
+            return Err(body_too_large());
+        }
+        bytes.extend_from_slice(&chunk);
+    }
+
+    Ok(bytes.into())
+}
+
+fn body_too_large() -> Response<Body> {
+    error(
+        StatusCode::PAYLOAD_TOO_LARGE,
+        &format!("request body is larger than {} bytes", MAX_BODY_SIZE),
+    )
+}
+
+/// Compares without returning early, so the time taken doesn't tell how much of a token matched
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+fn failed(failure: Failure) -> Response<Body> {
+    match failure {
+        Failure::Invalid(message) => error(StatusCode::BAD_REQUEST, &message),
+        Failure::Conflict(message) => error(StatusCode::CONFLICT, &message),
+        Failure::Upstream(message) => error(StatusCode::BAD_GATEWAY, &message),
+    }
+}
+
+fn error(status: StatusCode, message: &str) -> Response<Body> {
+    json(status, &serde_json::json!({ "error": message }))
+}
+
+fn json<T: Serialize>(status: StatusCode, value: &T) -> Response<Body> {
+    let (status, body) = match serde_json::to_vec(value) {
+        Ok(body) => (status, body),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            serde_json::json!({ "error": e.to_string() })
+                .to_string()
+                .into_bytes(),
+        ),
+    };
+
+    let mut res = Response::new(Body::from(body));
+    *res.status_mut() = status;
+    res.headers_mut()
+        .insert(CONTENT_TYPE, "application/json".parse().unwrap());
+
+    res
+}
+
+#[cfg(all(test, feature = "testing"))]
+mod tests {
+    use std::sync::Arc;
+
+    use hyper::{Body, Method, Request, StatusCode};
+    use serde_json::Value;
+    use spotify_private_api::testing::FakeServer;
+    use spotify_private_api::RootList;
+
+    use super::{Server, MAX_BODY_SIZE};
+    use crate::account::Account;
+
+    const TOKEN: &str = "secret";
+
+    async fn server(fake: &FakeServer) -> Arc<Server> {
+        let session = fake.session().await.expect("failed to create session");
+
+        Arc::new(Server::new(
+            vec![TOKEN.to_string()],
+            [("me".to_string(), Account::new(session))],
+        ))
+    }
+
+    async fn request(
+        server: &Arc<Server>,
+        method: Method,
+        uri: &str,
+        body: &str,
+    ) -> (StatusCode, Value) {
+        let req = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("authorization", format!("Bearer {}", TOKEN))
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let res = server.clone().handle(req).await.unwrap();
+        let status = res.status();
+        let body = hyper::body::to_bytes(res.into_body()).await.unwrap();
+
+        (status, serde_json::from_slice(&body).expect("invalid json"))
+    }
+
+    #[tokio::test]
+    async fn test_requests() {
+        let root_list: RootList = serde_json::from_str(r#"{"revision":"AAAA","contents":{"items":[{"uri":"spotify:playlist:5aNzxEEkRE9MgNkiuXmpOR"},{"uri":"spotify:playlist:3FKTkhbClLGgKdPpbx3aHy"}],"metaItems":[{"attributes":{"name":"Deep"}},{"attributes":{"name":"Chill"}}]}}"#).expect("Couldn't parse rootlist");
+        let fake = FakeServer::with_root_list(root_list)
+            .await
+            .expect("failed to start server");
+        let server = server(&fake).await;
+
+        let req = Request::get("/accounts").body(Body::empty()).unwrap();
+        let res = server.clone().handle(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+        let (status, accounts) = request(&server, Method::GET, "/accounts", "").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(accounts, serde_json::json!(["me"]));
+
+        let operations = r#"[{"op":"create_folder","path":"Focus"},{"op":"move","path":"Deep","folder":"Focus"}]"#;
+        let (status, applied) = request(
+            &server,
+            Method::POST,
+            "/accounts/me/operations?dry_run",
+            operations,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(applied["sent"], false);
+        assert!(fake.received_changes().is_empty());
+
+        let (status, applied) =
+            request(&server, Method::POST, "/accounts/me/operations", operations).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            applied["plan"],
+            serde_json::json!([r#"+ folder "Focus""#, r#"~ move "Deep" into "Focus""#])
+        );
+        assert_eq!(applied["revision"], fake.root_list().revision.as_str());
+
+        let (status, tree) = request(&server, Method::GET, "/accounts/me/tree", "").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(tree["tree"][1]["name"], "Focus");
+        assert_eq!(tree["tree"][1]["children"][0]["name"], "Deep");
+
+        let (status, history) = request(&server, Method::GET, "/accounts/me/history", "").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(history.as_array().unwrap().len(), 1);
+        assert_eq!(history[0]["operations"][1]["op"], "move");
+
+        let (status, _) = request(
+            &server,
+            Method::POST,
+            "/accounts/me/operations",
+            r#"[{"op":"ungroup","path":"Missing"}]"#,
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let (status, _) = request(&server, Method::GET, "/accounts/you/tree", "").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_body_too_large() {
+        let fake = FakeServer::start().await.expect("failed to start server");
+        let server = server(&fake).await;
+        let body = format!("[{}]", " ".repeat(MAX_BODY_SIZE));
+
+        let (status, _) = request(&server, Method::POST, "/accounts/me/operations", &body).await;
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+
+        // Rejected by its length before reading anything
+        let req = Request::post("/accounts/me/operations")
+            .header("authorization", format!("Bearer {}", TOKEN))
+            .header("content-length", MAX_BODY_SIZE + 1)
+            .body(Body::empty())
+            .unwrap();
+        let res = server.clone().handle(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+        // A chunked body has no length up front
+        let (mut sender, stream) = Body::channel();
+        tokio::spawn(async move {
+            for _ in 0..=MAX_BODY_SIZE / 1024 {
+                if sender.send_data(vec![b' '; 1024].into()).await.is_err() {
+                    break;
+                }
+            }
+        });
+        let req = Request::post("/accounts/me/operations")
+            .header("authorization", format!("Bearer {}", TOKEN))
+            .body(stream)
+            .unwrap();
+        let res = server.clone().handle(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        assert!(fake.received_changes().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_writes() {
+        let fake = FakeServer::start().await.expect("failed to start server");
+        let server = server(&fake).await;
+
+        let (first, second) = tokio::join!(
+            request(
+                &server,
+                Method::POST,
+                "/accounts/me/operations",
+                r#"[{"op":"create_folder","path":"A"}]"#,
+            ),
+            request(
+                &server,
+                Method::POST,
+                "/accounts/me/operations",
+                r#"[{"op":"create_folder","path":"B"}]"#,
+            ),
+        );
+        assert_eq!(first.0, StatusCode::OK);
+        assert_eq!(second.0, StatusCode::OK);
+        assert_eq!(fake.received_changes().len(), 2);
+        assert_eq!(fake.root_list().tree().len(), 2);
+    }
+}
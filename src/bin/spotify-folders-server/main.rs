@@ -0,0 +1,69 @@
+//! Serves the folders of one or more spotify accounts over HTTP and JSON, enabled by the
+//! `server` feature
+//!
+//! Every request needs one of the configured tokens as `Authorization: Bearer {token}`.
+//!
+//! - `GET /accounts` lists the account names
+//! - `GET /accounts/{name}/tree` returns the revision and the folder tree
+//! - `POST /accounts/{name}/operations` applies a JSON array of operations like
+//!   `{"op": "move", "path": "Deep", "folder": "Work"}` and returns the plan, with
+//!   `?dry_run` nothing is sent
+//! - `GET /accounts/{name}/history` returns the latest writes sent through the server
+//!
+//! Writes to an account are sent one at a time and retried on a revision conflict.
+
+mod account;
+mod config;
+mod http;
+
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::sync::Arc;
+
+use clap::Parser;
+
+use crate::account::Account;
+use crate::config::Config;
+use crate::http::Server;
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+#[derive(Parser, Debug)]
+#[command(name = "spotify-folders-server", version, about)]
+struct Cli {
+    /// TOML file with the tokens and the accounts to serve
+    #[arg(long, env = "SPOTIFY_FOLDERS_SERVER_CONFIG")]
+    config: PathBuf,
+
+    /// Address to listen on, instead of the one in the config
+    #[arg(long)]
+    listen: Option<SocketAddr>,
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    match run(Cli::parse()).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run(cli: Cli) -> Result<()> {
+    let config = Config::load(&cli.config)?;
+
+    let mut accounts = vec![];
+    for account in &config.accounts {
+        let logged_in = Account::login(account)
+            .await
+            .map_err(|e| format!("failed to log in as {}: {}", account.name, e))?;
+        accounts.push((account.name.clone(), logged_in));
+    }
+
+    Arc::new(Server::new(config.tokens, accounts))
+        .serve(cli.listen.unwrap_or(config.listen))
+        .await
+}
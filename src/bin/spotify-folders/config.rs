@@ -0,0 +1,161 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use spotify_private_api::{Endpoints, Session, SessionTokens};
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+const CREDENTIALS_FILE: &str = "credentials.json";
+const TOKENS_FILE: &str = "tokens.json";
+
+/// Cached tokens are only used while they are valid for at least this long
+const TOKEN_MARGIN: Duration = Duration::from_secs(60);
+
+/// The `sp_dc` and `sp_key` cookies and the user they belong to
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Credentials {
+    pub dc: String,
+    pub key: String,
+    #[serde(rename = "userId")]
+    pub user_id: String,
+}
+
+/// The directory the credentials and the token cache are kept in
+#[derive(Debug, Clone)]
+pub struct Config {
+    dir: PathBuf,
+}
+
+impl Config {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    /// `spotify-folders` in the user's config directory
+    pub fn default_dir() -> Result<PathBuf> {
+        Ok(dirs::config_dir()
+            .ok_or("no config directory, pass --config")?
+            .join("spotify-folders"))
+    }
+
+    /// Logs in with `credentials` and keeps them for the next commands
+    pub async fn login(&self, credentials: &Credentials) -> Result<Session> {
+        let session = Session::new(&credentials.dc, &credentials.key, &credentials.user_id).await?;
+
+        self.write(CREDENTIALS_FILE, credentials)?;
+        self.write(TOKENS_FILE, &session.tokens())?;
+
+        Ok(session)
+    }
+
+    /// A session for the stored credentials, reusing the cached tokens while they are valid
+    pub async fn session(&self) -> Result<Session> {
+        let credentials: Credentials = self
+            .read(CREDENTIALS_FILE)?
+            .ok_or("not logged in, run `spotify-folders login` first")?;
+
+        let tokens: Option<SessionTokens> = self.read(TOKENS_FILE).unwrap_or(None);
+        if let Some(tokens) = tokens.filter(|t| t.is_valid_at(SystemTime::now() + TOKEN_MARGIN)) {
+            return Ok(Session::from_tokens(
+                &credentials.user_id,
+                &tokens,
+                Endpoints::default(),
+            ));
+        }
+
+        let session = Session::new(&credentials.dc, &credentials.key, &credentials.user_id).await?;
+        self.write(TOKENS_FILE, &session.tokens())?;
+
+        Ok(session)
+    }
+
+    fn read<T: DeserializeOwned>(&self, file: &str) -> Result<Option<T>> {
+        let path = self.dir.join(file);
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let contents = fs::read_to_string(&path)?;
+        Ok(Some(serde_json::from_str(&contents).map_err(|e| {
+            format!("failed to parse {}: {}", path.display(), e)
+        })?))
+    }
+
+    fn write<T: Serialize>(&self, file: &str, value: &T) -> Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        write_private(&self.dir.join(file), &serde_json::to_vec_pretty(value)?)
+    }
+}
+
+/// Writes a file only the user can read, it holds secrets
+fn write_private(path: &Path, contents: &[u8]) -> Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+    std::io::Write::write_all(&mut options.open(path)?, contents)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    use spotify_private_api::SessionTokens;
+
+    use super::{Config, Credentials, CREDENTIALS_FILE, TOKENS_FILE};
+
+    fn config(name: &str) -> Config {
+        let dir =
+            std::env::temp_dir().join(format!("spotify-folders-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        Config::new(dir)
+    }
+
+    #[tokio::test]
+    async fn test_cached_session() {
+        let config = config("cached");
+        assert!(config.session().await.is_err());
+
+        let credentials = Credentials {
+            dc: "dc".to_string(),
+            key: "key".to_string(),
+            user_id: "user".to_string(),
+        };
+        let tokens = SessionTokens {
+            access_token: "access-token".to_string(),
+            client_token: "client-token".to_string(),
+            // Cached with millisecond precision
+            expires_at: UNIX_EPOCH
+                + Duration::from_millis(
+                    SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap()
+                        .as_millis() as u64
+                        + 3_600_000,
+                ),
+        };
+        config.write(CREDENTIALS_FILE, &credentials).unwrap();
+        config.write(TOKENS_FILE, &tokens).unwrap();
+
+        let session = config.session().await.expect("failed to reuse tokens");
+        assert_eq!(session.user_id(), "user");
+        assert_eq!(session.tokens(), tokens);
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            let metadata = std::fs::metadata(config.dir.join(CREDENTIALS_FILE)).unwrap();
+            assert_eq!(metadata.permissions().mode() & 0o777, 0o600);
+        }
+
+        std::fs::remove_dir_all(&config.dir).unwrap();
+    }
+}
@@ -0,0 +1,99 @@
+use std::path::Path;
+
+use clap::ValueEnum;
+use spotify_private_api::Layout;
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+/// Format of a layout file
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Yaml,
+    Toml,
+}
+
+impl Format {
+    /// TOML for `.toml` files, YAML for everything else
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("toml") => Format::Toml,
+            _ => Format::Yaml,
+        }
+    }
+}
+
+pub fn load(path: &Path) -> Result<Layout> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+
+    parse(&contents, Format::from_path(path))
+        .map_err(|e| format!("failed to parse {}: {}", path.display(), e).into())
+}
+
+pub fn parse(contents: &str, format: Format) -> Result<Layout> {
+    Ok(match format {
+        Format::Yaml => serde_yaml::from_str(contents)?,
+        Format::Toml => toml::from_str(contents)?,
+    })
+}
+
+pub fn to_string(layout: &Layout, format: Format) -> Result<String> {
+    Ok(match format {
+        Format::Yaml => serde_yaml::to_string(layout)?,
+        Format::Toml => toml::to_string(layout)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use spotify_private_api::RootList;
+
+    use super::{parse, to_string, Format};
+
+    #[test]
+    fn test_formats() {
+        let rl: RootList = serde_json::from_str(r#"{"revision":"AAAA","contents":{"items":[{"uri":"spotify:start-group:aaaaaaaaaaaaaaaa:Work"},{"uri":"spotify:start-group:bbbbbbbbbbbbbbbb:Empty"},{"uri":"spotify:end-group:bbbbbbbbbbbbbbbb"},{"uri":"spotify:playlist:5aNzxEEkRE9MgNkiuXmpOR"},{"uri":"spotify:end-group:aaaaaaaaaaaaaaaa"},{"uri":"spotify:playlist:3FKTkhbClLGgKdPpbx3aHy"}],"metaItems":[{},{},{},{"attributes":{"name":"Deep"}},{},{}]}}"#).expect("Couldn't parse rootlist");
+        let layout = rl.layout();
+
+        for format in [Format::Yaml, Format::Toml] {
+            let written = to_string(&layout, format).expect("failed to write layout");
+            assert_eq!(
+                parse(&written, format).expect("failed to parse layout"),
+                layout
+            );
+        }
+
+        assert_eq!(Format::from_path(Path::new("layout.TOML")), Format::Toml);
+        assert_eq!(Format::from_path(Path::new("layout.yml")), Format::Yaml);
+    }
+
+    #[test]
+    fn test_hand_written() {
+        let yaml = r#"
+items:
+  - folder: Work
+    items:
+      - playlist: 5aNzxEEkRE9MgNkiuXmpOR
+        name: Deep
+  - playlist: https://open.spotify.com/playlist/3FKTkhbClLGgKdPpbx3aHy
+"#;
+        let toml = r#"
+[[items]]
+folder = "Work"
+
+[[items.items]]
+playlist = "5aNzxEEkRE9MgNkiuXmpOR"
+name = "Deep"
+
+[[items]]
+playlist = "https://open.spotify.com/playlist/3FKTkhbClLGgKdPpbx3aHy"
+"#;
+
+        assert_eq!(
+            parse(yaml, Format::Yaml).expect("failed to parse yaml"),
+            parse(toml, Format::Toml).expect("failed to parse toml")
+        );
+    }
+}
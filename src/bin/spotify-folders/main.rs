@@ -0,0 +1,430 @@
+//! Organizes the spotify library into folders from the command line
+//!
+//! Folders are addressed by paths like `Work/Focus/Deep`, see
+//! [`RootListEditor`](spotify_private_api::RootListEditor) for how paths are resolved.
+
+mod config;
+mod layout_file;
+mod print;
+#[cfg(feature = "tui")]
+mod tui;
+
+use std::io::{BufRead, Write};
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use clap::{Parser, Subcommand};
+use serde::Serialize;
+use spotify_private_api::{
+    Changes, Layout, RootListEditor, Session, SpotifyId, SpotifyUri, TreeNode,
+};
+
+use crate::config::{Config, Credentials};
+use crate::layout_file::Format;
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+#[derive(Parser, Debug)]
+#[command(name = "spotify-folders", version, about)]
+struct Cli {
+    /// Directory of the stored credentials and tokens
+    #[arg(long, global = true, env = "SPOTIFY_FOLDERS_CONFIG")]
+    config: Option<PathBuf>,
+
+    /// Print the plan and the root list as it would be after the change instead of sending it
+    #[arg(long, global = true)]
+    dry_run: bool,
+
+    /// Print JSON, the tree for `ls` and the changes for everything else
+    #[arg(long, global = true)]
+    json: bool,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug, PartialEq, Eq)]
+enum Command {
+    /// Stores the sp_dc and sp_key cookies for the next commands
+    Login {
+        #[arg(long, env = "SPOTIFY_DC", hide_env_values = true)]
+        dc: String,
+        #[arg(long, env = "SPOTIFY_KEY", hide_env_values = true)]
+        key: String,
+        #[arg(long = "user", env = "SPOTIFY_USER_ID")]
+        user_id: String,
+    },
+    /// Shows the folders and playlists, of the whole library or inside a folder
+    Ls { path: Option<String> },
+    /// Creates an empty folder
+    Mkdir {
+        path: String,
+        /// Create the missing parent folders as well
+        #[arg(short, long)]
+        parents: bool,
+    },
+    /// Moves a folder or playlist into a folder, "/" is the top level
+    Mv { path: String, folder: String },
+    /// Renames a folder or a playlist
+    Rename { path: String, name: String },
+    /// Removes a folder and keeps its contents in its place
+    Rmdir {
+        path: String,
+        /// Unfollow everything inside the folder as well
+        #[arg(short, long)]
+        recursive: bool,
+    },
+    /// Follows a playlist, given as a uri, link or id, and puts it into a folder
+    Follow {
+        playlist: String,
+        #[arg(default_value = "/")]
+        folder: String,
+    },
+    /// Unfollows a playlist
+    Unfollow { path: String },
+    /// Changes the library to match a YAML or TOML layout file, after showing the plan
+    Apply {
+        file: PathBuf,
+        /// Don't ask for confirmation
+        #[arg(short, long)]
+        yes: bool,
+        /// Unfollow the playlists the layout doesn't list
+        #[arg(long)]
+        prune: bool,
+    },
+    /// Prints the library as a layout file for `apply`
+    Export {
+        #[arg(long, value_enum, default_value_t = Format::Yaml)]
+        format: Format,
+    },
+    /// Reorganizes the library interactively
+    #[cfg(feature = "tui")]
+    Tui,
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    match run(Cli::parse()).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run(cli: Cli) -> Result<()> {
+    let config = Config::new(match &cli.config {
+        Some(dir) => dir.clone(),
+        None => Config::default_dir()?,
+    });
+
+    if let Command::Login { dc, key, user_id } = &cli.command {
+        let credentials = Credentials {
+            dc: dc.clone(),
+            key: key.clone(),
+            user_id: user_id.clone(),
+        };
+        config.login(&credentials).await?;
+        println!("logged in as {}", user_id);
+
+        return Ok(());
+    }
+
+    let session = config.session().await?;
+    match &cli.command {
+        Command::Login { .. } => unreachable!("handled above"),
+        Command::Ls { path } => {
+            let root_list = session.get_root_list().await?;
+            let nodes = match path {
+                Some(path) => match RootListEditor::new(&root_list).find(path)? {
+                    TreeNode::Folder(folder) => folder.children,
+                    node => vec![node],
+                },
+                None => root_list.tree(),
+            };
+
+            if cli.json {
+                print_json(&nodes)?;
+            } else {
+                print!("{}", print::render(&nodes));
+            }
+        }
+        Command::Mkdir { path, parents } => {
+            edit(&cli, &session, |editor| {
+                if *parents {
+                    create_parents(editor, path)?;
+                }
+                editor.create_folder(path)?;
+
+                Ok(())
+            })
+            .await?
+        }
+        Command::Mv { path, folder } => {
+            edit(&cli, &session, |editor| {
+                editor.move_to(path, folder)?;
+
+                Ok(())
+            })
+            .await?
+        }
+        Command::Rename { path, name } => {
+            let root_list = session.get_root_list().await?;
+            match RootListEditor::new(&root_list).find(path)? {
+                TreeNode::Playlist(item) => {
+                    let id = item
+                        .uri
+                        .parse::<SpotifyUri>()?
+                        .id()
+                        .ok_or("not a playlist")?;
+                    rename_playlist(&cli, &session, &id, name).await?
+                }
+                _ => {
+                    edit(&cli, &session, |editor| {
+                        editor.rename(path, name)?;
+
+                        Ok(())
+                    })
+                    .await?
+                }
+            }
+        }
+        Command::Rmdir { path, recursive } => {
+            edit(&cli, &session, |editor| {
+                if *recursive {
+                    if !matches!(editor.find(path)?, TreeNode::Folder(_)) {
+                        return Err(format!("{} is not a folder", path).into());
+                    }
+                    editor.remove(path)?;
+                } else {
+                    editor.ungroup(path)?;
+                }
+
+                Ok(())
+            })
+            .await?
+        }
+        Command::Follow { playlist, folder } => {
+            let id = parse_playlist_id(playlist)?;
+            edit(&cli, &session, |editor| {
+                editor.follow(&id, folder)?;
+
+                Ok(())
+            })
+            .await?
+        }
+        Command::Unfollow { path } => {
+            edit(&cli, &session, |editor| {
+                if let TreeNode::Folder(_) = editor.find(path)? {
+                    return Err(format!("{} is a folder, use rmdir", path).into());
+                }
+                editor.remove(path)?;
+
+                Ok(())
+            })
+            .await?
+        }
+        Command::Apply { file, yes, prune } => {
+            apply(&cli, &session, &layout_file::load(file)?, *yes, *prune).await?
+        }
+        Command::Export { format } => {
+            let layout = session.get_root_list().await?.layout();
+            if cli.json {
+                print_json(&layout)?;
+            } else {
+                print!("{}", layout_file::to_string(&layout, *format)?);
+            }
+        }
+        #[cfg(feature = "tui")]
+        Command::Tui => tui::run(&session).await?,
+    }
+
+    Ok(())
+}
+
+/// Shows the plan that turns the library into `layout` and sends it once confirmed
+async fn apply(
+    cli: &Cli,
+    session: &Session,
+    layout: &Layout,
+    yes: bool,
+    prune: bool,
+) -> Result<()> {
+    let root_list = session.get_root_list().await?;
+    let changes = layout.plan(&root_list, prune)?.build();
+
+    let mut planned = root_list.clone();
+    planned.apply_changes(&changes)?;
+    if cli.json {
+        print_json(&changes)?;
+    } else {
+        print!("{}", root_list.diff(&planned));
+    }
+
+    if cli.dry_run || changes.ops().next().is_none() {
+        return Ok(());
+    }
+    if !yes && !confirm("Apply these changes?")? {
+        eprintln!("nothing was changed");
+        return Ok(());
+    }
+
+    session.send_changes(&changes).await
+}
+
+/// Asks a yes/no question on the terminal, no is the default
+fn confirm(question: &str) -> Result<bool> {
+    eprint!("{} [y/N] ", question);
+    std::io::stderr().flush()?;
+
+    let mut answer = String::new();
+    std::io::stdin().lock().read_line(&mut answer)?;
+
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+/// Applies `edits` to the current root list, then prints and sends the changes
+async fn edit(
+    cli: &Cli,
+    session: &Session,
+    edits: impl FnOnce(&mut RootListEditor) -> Result<()>,
+) -> Result<()> {
+    let root_list = session.get_root_list().await?;
+    let mut editor = RootListEditor::new(&root_list);
+    edits(&mut editor)?;
+
+    let changes = editor.build();
+    if cli.json {
+        print_json(&changes)?;
+    } else if cli.dry_run {
+        println!("{}", root_list.plan(&changes)?);
+        print!("{}", print::render(&editor.root_list().tree()));
+    }
+
+    if !cli.dry_run {
+        session.send_changes(&changes).await?;
+    }
+
+    Ok(())
+}
+
+async fn rename_playlist(cli: &Cli, session: &Session, id: &SpotifyId, name: &str) -> Result<()> {
+    let playlist = session.get_playlist(id).await?;
+    let changes: Changes = playlist.new_request().rename(name).build();
+
+    if cli.json {
+        print_json(&changes)?;
+    } else if cli.dry_run {
+        println!(
+            "rename {} to {}",
+            playlist.name().unwrap_or(&id.to_string()),
+            name
+        );
+    }
+
+    if !cli.dry_run {
+        session.send_playlist_changes(id, &changes).await?;
+    }
+
+    Ok(())
+}
+
+/// Creates the folders above `path` that don't exist yet
+fn create_parents(editor: &mut RootListEditor, path: &str) -> Result<()> {
+    let parts: Vec<&str> = path.trim_matches('/').split('/').collect();
+
+    for i in 1..parts.len() {
+        let parent = parts[..i].join("/");
+        if editor.find(&parent).is_err() {
+            editor.create_folder(&parent)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Parses a playlist given as a uri, an `open.spotify.com` link or a base62 id
+fn parse_playlist_id(playlist: &str) -> Result<SpotifyId> {
+    let uri = playlist
+        .parse::<SpotifyUri>()
+        .or_else(|_| SpotifyUri::from_url(playlist));
+
+    match uri {
+        Ok(SpotifyUri::Playlist(id)) => Ok(id),
+        Ok(uri) => Err(format!("{} is not a playlist", uri).into()),
+        Err(_) => Ok(playlist.parse()?),
+    }
+}
+
+fn print_json<T: Serialize>(value: &T) -> Result<()> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::{CommandFactory, Parser};
+    use spotify_private_api::RootList;
+
+    use super::{create_parents, parse_playlist_id, Cli, Command};
+    use spotify_private_api::RootListEditor;
+
+    #[test]
+    fn test_cli() {
+        Cli::command().debug_assert();
+
+        let cli =
+            Cli::try_parse_from(["spotify-folders", "rmdir", "-r", "Work/Focus", "--dry-run"])
+                .expect("failed to parse arguments");
+        assert!(cli.dry_run);
+        assert_eq!(
+            cli.command,
+            Command::Rmdir {
+                path: "Work/Focus".to_string(),
+                recursive: true
+            }
+        );
+
+        let cli = Cli::try_parse_from(["spotify-folders", "follow", "5aNzxEEkRE9MgNkiuXmpOR"])
+            .expect("failed to parse arguments");
+        assert_eq!(
+            cli.command,
+            Command::Follow {
+                playlist: "5aNzxEEkRE9MgNkiuXmpOR".to_string(),
+                folder: "/".to_string()
+            }
+        );
+
+        assert!(Cli::try_parse_from(["spotify-folders", "mv", "Chill"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_playlist_id() {
+        let id = "5aNzxEEkRE9MgNkiuXmpOR".parse().unwrap();
+
+        for playlist in [
+            "5aNzxEEkRE9MgNkiuXmpOR",
+            "spotify:playlist:5aNzxEEkRE9MgNkiuXmpOR",
+            "https://open.spotify.com/playlist/5aNzxEEkRE9MgNkiuXmpOR?si=1",
+        ] {
+            assert_eq!(parse_playlist_id(playlist).unwrap(), id);
+        }
+        assert!(parse_playlist_id("spotify:track:5aNzxEEkRE9MgNkiuXmpOR").is_err());
+    }
+
+    #[test]
+    fn test_create_parents() {
+        let rl: RootList = serde_json::from_str(r#"{"revision":"AAAA","contents":{"items":[{"uri":"spotify:start-group:aaaaaaaaaaaaaaaa:Work"},{"uri":"spotify:end-group:aaaaaaaaaaaaaaaa"}]}}"#).expect("Couldn't parse rootlist");
+        let mut editor = RootListEditor::new(&rl);
+
+        create_parents(&mut editor, "Work/Focus/Deep").expect("failed to create parents");
+        editor
+            .create_folder("Work/Focus/Deep")
+            .expect("failed to create folder");
+
+        assert!(editor.find("Work/Focus/Deep").is_ok());
+        assert_eq!(editor.root_list().len(), 6);
+    }
+}
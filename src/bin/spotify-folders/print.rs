@@ -0,0 +1,59 @@
+use spotify_private_api::TreeNode;
+
+/// Draws `nodes` the way `tree` does, folders end with a `/`
+pub fn render(nodes: &[TreeNode]) -> String {
+    let mut out = String::new();
+    for node in nodes {
+        out.push_str(&label(node));
+        out.push('\n');
+        render_children(node, "", &mut out);
+    }
+
+    out
+}
+
+fn render_children(node: &TreeNode, prefix: &str, out: &mut String) {
+    let TreeNode::Folder(folder) = node else {
+        return;
+    };
+
+    for (i, child) in folder.children.iter().enumerate() {
+        let (branch, indent) = if i + 1 == folder.children.len() {
+            ("└── ", "    ")
+        } else {
+            ("├── ", "│   ")
+        };
+
+        out.push_str(prefix);
+        out.push_str(branch);
+        out.push_str(&label(child));
+        out.push('\n');
+        render_children(child, &format!("{}{}", prefix, indent), out);
+    }
+}
+
+fn label(node: &TreeNode) -> String {
+    match node {
+        TreeNode::Folder(folder) => format!("{}/", folder.name),
+        TreeNode::Playlist(item) | TreeNode::Other(item) => {
+            item.name.clone().unwrap_or_else(|| item.uri.clone())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use spotify_private_api::RootList;
+
+    use super::render;
+
+    #[test]
+    fn test_render() {
+        let rl: RootList = serde_json::from_str(r#"{"revision":"AAAA","contents":{"items":[{"uri":"spotify:start-group:aaaaaaaaaaaaaaaa:Work"},{"uri":"spotify:start-group:bbbbbbbbbbbbbbbb:Focus"},{"uri":"spotify:playlist:5aNzxEEkRE9MgNkiuXmpOR"},{"uri":"spotify:end-group:bbbbbbbbbbbbbbbb"},{"uri":"spotify:playlist:37i9dQZF1DXcBWIGoYBM5M"},{"uri":"spotify:end-group:aaaaaaaaaaaaaaaa"},{"uri":"spotify:playlist:3FKTkhbClLGgKdPpbx3aHy"}],"metaItems":[{},{},{"attributes":{"name":"Deep"}},{},{},{},{"attributes":{"name":"Chill"}}]}}"#).expect("Couldn't parse rootlist");
+
+        assert_eq!(
+            render(&rl.tree()),
+            "Work/\n├── Focus/\n│   └── Deep\n└── spotify:playlist:37i9dQZF1DXcBWIGoYBM5M\nChill\n"
+        );
+    }
+}
@@ -0,0 +1,818 @@
+use std::collections::HashSet;
+use std::fmt;
+
+use ratatui::crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use spotify_private_api::{Changes, FolderId, FolderNode, RootList, RootListEditor, TreeNode};
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+/// Identifies an entry across edits, folders by id and everything else by uri
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Key {
+    Folder(FolderId),
+    Item(String),
+}
+
+/// A queued edit, replayed on the fetched root list for undo and after a conflict
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Edit {
+    CreateFolder {
+        parent: Option<FolderId>,
+        name: String,
+        id: FolderId,
+    },
+    MoveInto {
+        keys: Vec<Key>,
+        folder: Option<FolderId>,
+    },
+    MoveBefore {
+        key: Key,
+        before: Key,
+    },
+    MoveAfter {
+        key: Key,
+        after: Key,
+    },
+    Rename {
+        id: FolderId,
+        name: String,
+    },
+    Ungroup {
+        id: FolderId,
+    },
+}
+
+/// A visible line of the tree
+#[derive(Debug, Clone)]
+pub struct Row {
+    pub depth: usize,
+    pub node: TreeNode,
+    pub key: Key,
+    /// Folder id of the parent, `None` at the top level
+    pub parent: Option<FolderId>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Mode {
+    Normal,
+    NewFolder(String),
+    Rename(String),
+    ConfirmSave,
+}
+
+/// What the event loop has to do after a key press
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Continue,
+    Quit,
+    Save,
+}
+
+pub struct App {
+    /// The root list as fetched, edits are queued on top of it
+    base: RootList,
+    editor: RootListEditor,
+    log: Vec<Edit>,
+    pub rows: Vec<Row>,
+    pub cursor: usize,
+    pub collapsed: HashSet<FolderId>,
+    pub selected: HashSet<Key>,
+    pub cut: Vec<Key>,
+    pub mode: Mode,
+    pub status: String,
+    quit_armed: bool,
+}
+
+impl App {
+    pub fn new(root_list: RootList) -> Self {
+        let mut app = Self {
+            editor: RootListEditor::new(&root_list),
+            base: root_list,
+            log: vec![],
+            rows: vec![],
+            cursor: 0,
+            collapsed: HashSet::new(),
+            selected: HashSet::new(),
+            cut: vec![],
+            mode: Mode::Normal,
+            status: String::new(),
+            quit_armed: false,
+        };
+        app.refresh_rows();
+
+        app
+    }
+
+    pub fn edits(&self) -> &[Edit] {
+        &self.log
+    }
+
+    /// Everything queued, as a single request against the fetched revision
+    pub fn changes(&self) -> Changes {
+        self.editor.build()
+    }
+
+    pub fn revision(&self) -> &str {
+        &self.base.revision
+    }
+
+    /// Starts over from `root_list`, e.g. after the queued edits were sent
+    pub fn reset(&mut self, root_list: RootList) {
+        self.base = root_list;
+        self.log.clear();
+        self.editor = RootListEditor::new(&self.base);
+        self.cut.clear();
+        self.selected.clear();
+        self.refresh_rows();
+    }
+
+    /// Replays the queued edits on `root_list`, dropping the ones that don't apply anymore.
+    /// Returns how many were kept
+    pub fn rebase(&mut self, root_list: RootList) -> usize {
+        self.base = root_list;
+        let (editor, kept) = replay(&self.base, &self.log);
+        self.editor = editor;
+        self.log = kept;
+        self.cut.clear();
+        self.selected.clear();
+        self.refresh_rows();
+
+        self.log.len()
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> Action {
+        let quit_armed = std::mem::take(&mut self.quit_armed);
+
+        match self.mode.clone() {
+            Mode::Normal => return self.handle_normal_key(key, quit_armed),
+            Mode::NewFolder(text) | Mode::Rename(text) => self.handle_input_key(key, text),
+            Mode::ConfirmSave => {
+                self.mode = Mode::Normal;
+                if matches!(key.code, KeyCode::Char('y') | KeyCode::Char('Y')) {
+                    return Action::Save;
+                }
+                self.status = "not sent".to_string();
+            }
+        }
+
+        Action::Continue
+    }
+
+    fn handle_normal_key(&mut self, key: KeyEvent, quit_armed: bool) -> Action {
+        let shift = key.modifiers.contains(KeyModifiers::SHIFT);
+
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => {
+                if self.log.is_empty() || quit_armed {
+                    return Action::Quit;
+                }
+                self.quit_armed = true;
+                self.status = format!(
+                    "{} unsaved edits, press q again to discard them",
+                    self.log.len()
+                );
+            }
+            KeyCode::Up if shift => self.move_up(),
+            KeyCode::Down if shift => self.move_down(),
+            KeyCode::Char('K') => self.move_up(),
+            KeyCode::Char('J') => self.move_down(),
+            KeyCode::Up | KeyCode::Char('k') => self.cursor = self.cursor.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => {
+                self.cursor = (self.cursor + 1).min(self.rows.len().saturating_sub(1))
+            }
+            KeyCode::Home | KeyCode::Char('g') => self.cursor = 0,
+            KeyCode::End | KeyCode::Char('G') => self.cursor = self.rows.len().saturating_sub(1),
+            KeyCode::Left | KeyCode::Char('h') => self.collapse(),
+            KeyCode::Right | KeyCode::Char('l') => self.expand(),
+            KeyCode::Enter | KeyCode::Tab => self.toggle(),
+            KeyCode::Char(' ') => self.toggle_selection(),
+            KeyCode::Char('x') => self.cut(),
+            KeyCode::Char('p') => self.paste(false),
+            KeyCode::Char('P') => self.paste(true),
+            KeyCode::Char('n') => self.mode = Mode::NewFolder(String::new()),
+            KeyCode::Char('r') => match self.current().map(|row| &row.node) {
+                Some(TreeNode::Folder(folder)) => self.mode = Mode::Rename(folder.name.clone()),
+                _ => self.status = "only folders can be renamed".to_string(),
+            },
+            KeyCode::Char('u') => match self.current().map(|row| &row.node) {
+                Some(TreeNode::Folder(folder)) => {
+                    let id = folder.id.clone();
+                    self.apply(Edit::Ungroup { id });
+                }
+                _ => self.status = "only folders can be ungrouped".to_string(),
+            },
+            KeyCode::Char('z') => self.undo(),
+            KeyCode::Char('s') | KeyCode::Char('w') => {
+                if self.log.is_empty() {
+                    self.status = "nothing to send".to_string();
+                } else {
+                    self.mode = Mode::ConfirmSave;
+                }
+            }
+            _ => {}
+        }
+
+        Action::Continue
+    }
+
+    fn handle_input_key(&mut self, key: KeyEvent, mut text: String) {
+        match key.code {
+            KeyCode::Esc => self.mode = Mode::Normal,
+            KeyCode::Enter => {
+                let mode = std::mem::replace(&mut self.mode, Mode::Normal);
+                match mode {
+                    Mode::NewFolder(_) => self.create_folder(text.trim()),
+                    Mode::Rename(_) => self.rename(text.trim()),
+                    _ => {}
+                }
+            }
+            KeyCode::Backspace => {
+                text.pop();
+                self.set_input(text);
+            }
+            KeyCode::Char(c) => {
+                text.push(c);
+                self.set_input(text);
+            }
+            _ => {}
+        }
+    }
+
+    fn set_input(&mut self, text: String) {
+        self.mode = match self.mode {
+            Mode::NewFolder(_) => Mode::NewFolder(text),
+            Mode::Rename(_) => Mode::Rename(text),
+            ref mode => mode.clone(),
+        };
+    }
+
+    pub fn current(&self) -> Option<&Row> {
+        self.rows.get(self.cursor)
+    }
+
+    /// The folder the cursor is on, or the one around it
+    fn current_folder(&self) -> Option<FolderId> {
+        let row = self.current()?;
+        match &row.node {
+            TreeNode::Folder(folder) => Some(folder.id.clone()),
+            _ => row.parent.clone(),
+        }
+    }
+
+    fn move_up(&mut self) {
+        let Some(row) = self.current().cloned() else {
+            return;
+        };
+        let tree = self.editor.tree();
+        let siblings = children(&tree, row.parent.as_ref());
+        let position = siblings.iter().position(|node| key_of(node) == row.key);
+
+        let edit = match position {
+            Some(i) if i > 0 => Edit::MoveBefore {
+                key: row.key,
+                before: key_of(&siblings[i - 1]),
+            },
+            _ => match row.parent {
+                Some(parent) => Edit::MoveBefore {
+                    key: row.key,
+                    before: Key::Folder(parent),
+                },
+                None => return,
+            },
+        };
+        self.apply(edit);
+    }
+
+    fn move_down(&mut self) {
+        let Some(row) = self.current().cloned() else {
+            return;
+        };
+        let tree = self.editor.tree();
+        let siblings = children(&tree, row.parent.as_ref());
+        let position = siblings.iter().position(|node| key_of(node) == row.key);
+
+        let edit = match position {
+            Some(i) if i + 1 < siblings.len() => Edit::MoveAfter {
+                key: row.key,
+                after: key_of(&siblings[i + 1]),
+            },
+            _ => match row.parent {
+                Some(parent) => Edit::MoveAfter {
+                    key: row.key,
+                    after: Key::Folder(parent),
+                },
+                None => return,
+            },
+        };
+        self.apply(edit);
+    }
+
+    fn collapse(&mut self) {
+        let Some(row) = self.current().cloned() else {
+            return;
+        };
+
+        match &row.node {
+            TreeNode::Folder(folder) if !self.collapsed.contains(&folder.id) => {
+                self.collapsed.insert(folder.id.clone());
+                self.refresh_rows();
+            }
+            _ => {
+                // Jump to the parent folder
+                if let Some(parent) = row.parent {
+                    self.focus(&Key::Folder(parent));
+                }
+            }
+        }
+    }
+
+    fn expand(&mut self) {
+        if let Some(TreeNode::Folder(folder)) = self.current().map(|row| &row.node) {
+            let id = folder.id.clone();
+            self.collapsed.remove(&id);
+            self.refresh_rows();
+        }
+    }
+
+    fn toggle(&mut self) {
+        if let Some(TreeNode::Folder(folder)) = self.current().map(|row| &row.node) {
+            let id = folder.id.clone();
+            if !self.collapsed.remove(&id) {
+                self.collapsed.insert(id);
+            }
+            self.refresh_rows();
+        }
+    }
+
+    fn toggle_selection(&mut self) {
+        let Some(key) = self.current().map(|row| row.key.clone()) else {
+            return;
+        };
+
+        if !self.selected.remove(&key) {
+            self.selected.insert(key);
+        }
+        self.cursor = (self.cursor + 1).min(self.rows.len().saturating_sub(1));
+    }
+
+    /// The selected entries in tree order, or the one under the cursor
+    fn targets(&self) -> Vec<Key> {
+        if self.selected.is_empty() {
+            return self
+                .current()
+                .map(|row| row.key.clone())
+                .into_iter()
+                .collect();
+        }
+
+        outermost(&self.editor.tree(), &self.selected)
+    }
+
+    fn cut(&mut self) {
+        self.cut = self.targets();
+        self.selected.clear();
+        self.status = format!(
+            "cut {} entries, press p to put them into a folder, P for the top level",
+            self.cut.len()
+        );
+    }
+
+    fn paste(&mut self, top_level: bool) {
+        if self.cut.is_empty() {
+            self.status = "nothing was cut, press x first".to_string();
+            return;
+        }
+
+        let folder = match top_level {
+            true => None,
+            false => self.current_folder(),
+        };
+        let keys = std::mem::take(&mut self.cut);
+        let focus = keys[0].clone();
+        if self.apply(Edit::MoveInto { keys, folder }) {
+            self.focus(&focus);
+        }
+    }
+
+    fn create_folder(&mut self, name: &str) {
+        let parent = self.current_folder();
+        let id = match self.editor.root_list().generate_folder_id() {
+            Ok(id) => id,
+            Err(e) => {
+                self.status = e.to_string();
+                return;
+            }
+        };
+        let selected = match self.selected.is_empty() {
+            true => vec![],
+            false => self.targets(),
+        };
+
+        if !self.apply(Edit::CreateFolder {
+            parent: parent.clone(),
+            name: name.to_string(),
+            id: id.clone(),
+        }) {
+            return;
+        }
+        if let Some(parent) = parent {
+            self.collapsed.remove(&parent);
+        }
+        // Wrap the selection into the new folder
+        if !selected.is_empty() {
+            self.selected.clear();
+            self.apply(Edit::MoveInto {
+                keys: selected,
+                folder: Some(id.clone()),
+            });
+        }
+        self.focus(&Key::Folder(id));
+    }
+
+    fn rename(&mut self, name: &str) {
+        if let Some(TreeNode::Folder(folder)) = self.current().map(|row| &row.node) {
+            let id = folder.id.clone();
+            self.apply(Edit::Rename {
+                id,
+                name: name.to_string(),
+            });
+        }
+    }
+
+    fn undo(&mut self) {
+        let Some(edit) = self.log.pop() else {
+            self.status = "nothing to undo".to_string();
+            return;
+        };
+
+        let (editor, kept) = replay(&self.base, &self.log);
+        self.editor = editor;
+        self.log = kept;
+        self.refresh_rows();
+        self.status = format!("undid {}", edit);
+    }
+
+    /// Queues `edit`, returns whether it applied
+    fn apply(&mut self, edit: Edit) -> bool {
+        let focus = self.current().map(|row| row.key.clone());
+
+        match apply_edit(&mut self.editor, &edit) {
+            Ok(()) => {
+                self.status = edit.to_string();
+                self.log.push(edit);
+                self.refresh_rows();
+                if let Some(key) = focus {
+                    self.focus(&key);
+                }
+
+                true
+            }
+            Err(e) => {
+                // The edit may have been applied partly
+                let (editor, _) = replay(&self.base, &self.log);
+                self.editor = editor;
+                self.refresh_rows();
+                self.status = e.to_string();
+
+                false
+            }
+        }
+    }
+
+    fn focus(&mut self, key: &Key) {
+        if let Some(i) = self.rows.iter().position(|row| &row.key == key) {
+            self.cursor = i;
+        }
+    }
+
+    fn refresh_rows(&mut self) {
+        self.rows.clear();
+        let tree = self.editor.tree();
+        flatten(&tree, 0, None, &self.collapsed, &mut self.rows);
+        self.cursor = self.cursor.min(self.rows.len().saturating_sub(1));
+    }
+}
+
+/// Applies `log` to `base`, skipping the edits that fail
+fn replay(base: &RootList, log: &[Edit]) -> (RootListEditor, Vec<Edit>) {
+    let mut editor = RootListEditor::new(base);
+    let mut kept: Vec<Edit> = vec![];
+
+    for edit in log {
+        if apply_edit(&mut editor, edit).is_ok() {
+            kept.push(edit.clone());
+        } else {
+            // Start over without it, it may have been applied partly
+            editor = RootListEditor::new(base);
+            for edit in &kept {
+                apply_edit(&mut editor, edit).expect("edit applied before");
+            }
+        }
+    }
+
+    (editor, kept)
+}
+
+fn apply_edit(editor: &mut RootListEditor, edit: &Edit) -> Result<()> {
+    let node = |editor: &RootListEditor, key: &Key| {
+        find_node(&editor.tree(), key)
+            .cloned()
+            .ok_or_else(|| format!("{:?} doesn't exist anymore", key))
+    };
+    let folder = |editor: &RootListEditor, id: &Option<FolderId>| -> Result<Option<FolderNode>> {
+        match id {
+            Some(id) => match node(editor, &Key::Folder(id.clone()))? {
+                TreeNode::Folder(folder) => Ok(Some(folder)),
+                _ => Err("not a folder".into()),
+            },
+            None => Ok(None),
+        }
+    };
+    let folder_node = |editor: &RootListEditor, id: &FolderId| -> Result<FolderNode> {
+        folder(editor, &Some(id.clone()))?.ok_or_else(|| "not a folder".into())
+    };
+
+    match edit {
+        Edit::CreateFolder { parent, name, id } => {
+            let parent = folder(editor, parent)?;
+            editor.create_folder_in(parent.as_ref(), name, id)?;
+        }
+        Edit::MoveInto { keys, folder: id } => {
+            for key in keys {
+                let moved = node(editor, key)?;
+                let target = folder(editor, id)?;
+                editor.move_into(&moved, target.as_ref())?;
+            }
+        }
+        Edit::MoveBefore { key, before } => {
+            let (moved, before) = (node(editor, key)?, node(editor, before)?);
+            editor.move_before(&moved, &before)?;
+        }
+        Edit::MoveAfter { key, after } => {
+            let (moved, after) = (node(editor, key)?, node(editor, after)?);
+            editor.move_after(&moved, &after)?;
+        }
+        Edit::Rename { id, name } => {
+            let target = folder_node(editor, id)?;
+            editor.rename_folder(&target, name)?;
+        }
+        Edit::Ungroup { id } => {
+            let target = folder_node(editor, id)?;
+            editor.ungroup_folder(&target)?;
+        }
+    }
+
+    Ok(())
+}
+
+pub fn key_of(node: &TreeNode) -> Key {
+    match node {
+        TreeNode::Folder(folder) => Key::Folder(folder.id.clone()),
+        TreeNode::Playlist(item) | TreeNode::Other(item) => Key::Item(item.uri.clone()),
+    }
+}
+
+pub fn display_name(node: &TreeNode) -> String {
+    match node {
+        TreeNode::Folder(folder) => folder.name.clone(),
+        TreeNode::Playlist(item) | TreeNode::Other(item) => {
+            item.name.clone().unwrap_or_else(|| item.uri.clone())
+        }
+    }
+}
+
+fn find_node<'a>(tree: &'a [TreeNode], key: &Key) -> Option<&'a TreeNode> {
+    tree.iter().find_map(|node| {
+        if &key_of(node) == key {
+            return Some(node);
+        }
+
+        match node {
+            TreeNode::Folder(folder) => find_node(&folder.children, key),
+            _ => None,
+        }
+    })
+}
+
+fn children<'a>(tree: &'a [TreeNode], parent: Option<&FolderId>) -> &'a [TreeNode] {
+    match parent {
+        Some(id) => match find_node(tree, &Key::Folder(id.clone())) {
+            Some(TreeNode::Folder(folder)) => &folder.children,
+            _ => &[],
+        },
+        None => tree,
+    }
+}
+
+/// Keys of `selected` in tree order, without the ones inside a selected folder
+fn outermost(tree: &[TreeNode], selected: &HashSet<Key>) -> Vec<Key> {
+    let mut keys = vec![];
+    for node in tree {
+        let key = key_of(node);
+        if selected.contains(&key) {
+            keys.push(key);
+        } else if let TreeNode::Folder(folder) = node {
+            keys.extend(outermost(&folder.children, selected));
+        }
+    }
+
+    keys
+}
+
+fn flatten(
+    nodes: &[TreeNode],
+    depth: usize,
+    parent: Option<&FolderId>,
+    collapsed: &HashSet<FolderId>,
+    rows: &mut Vec<Row>,
+) {
+    for node in nodes {
+        rows.push(Row {
+            depth,
+            node: node.clone(),
+            key: key_of(node),
+            parent: parent.cloned(),
+        });
+
+        if let TreeNode::Folder(folder) = node {
+            if !collapsed.contains(&folder.id) {
+                flatten(
+                    &folder.children,
+                    depth + 1,
+                    Some(&folder.id),
+                    collapsed,
+                    rows,
+                );
+            }
+        }
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Key::Folder(id) => write!(f, "folder {}", id),
+            Key::Item(uri) => write!(f, "{}", uri),
+        }
+    }
+}
+
+impl fmt::Display for Edit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Edit::CreateFolder { name, .. } => write!(f, "create folder {:?}", name),
+            Edit::MoveInto { keys, folder: None } => {
+                write!(f, "move {} entries to the top level", keys.len())
+            }
+            Edit::MoveInto {
+                keys,
+                folder: Some(id),
+            } => write!(f, "move {} entries into folder {}", keys.len(), id),
+            Edit::MoveBefore { key, before } => write!(f, "move {} before {}", key, before),
+            Edit::MoveAfter { key, after } => write!(f, "move {} after {}", key, after),
+            Edit::Rename { id, name } => write!(f, "rename folder {} to {:?}", id, name),
+            Edit::Ungroup { id } => write!(f, "ungroup folder {}", id),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ratatui::crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+    use spotify_private_api::RootList;
+
+    use super::{Action, App, Key, Mode};
+
+    const ROOT_LIST: &str = r#"{"revision":"AAAA","contents":{"items":[{"uri":"spotify:start-group:aaaaaaaaaaaaaaaa:Work"},{"uri":"spotify:playlist:5aNzxEEkRE9MgNkiuXmpOR"},{"uri":"spotify:end-group:aaaaaaaaaaaaaaaa"},{"uri":"spotify:playlist:3FKTkhbClLGgKdPpbx3aHy"},{"uri":"spotify:playlist:37i9dQZF1DXcBWIGoYBM5M"}],"metaItems":[{},{"attributes":{"name":"Deep"}},{},{"attributes":{"name":"Chill"}},{"attributes":{"name":"Focus"}}]}}"#;
+
+    fn root_list() -> RootList {
+        serde_json::from_str(ROOT_LIST).expect("Couldn't parse rootlist")
+    }
+
+    fn press(app: &mut App, keys: &str) -> Action {
+        let mut action = Action::Continue;
+        for c in keys.chars() {
+            action = app.handle_key(KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE));
+        }
+
+        action
+    }
+
+    fn enter(app: &mut App) {
+        app.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+    }
+
+    fn uris(app: &App) -> Vec<String> {
+        let mut rl = root_list();
+        rl.apply_changes(&app.changes())
+            .expect("failed to apply changes");
+
+        rl.entries()
+            .map(|entry| entry.raw_uri().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_rows() {
+        let mut app = App::new(root_list());
+        assert_eq!(app.rows.len(), 4);
+        assert_eq!(app.rows[1].depth, 1);
+
+        // Collapse Work, then expand it again
+        press(&mut app, "h");
+        assert_eq!(app.rows.len(), 3);
+        press(&mut app, "l");
+        assert_eq!(app.rows.len(), 4);
+    }
+
+    #[test]
+    fn test_move_and_undo() {
+        let mut app = App::new(root_list());
+
+        // Chill up, out of nothing into the spot before Work
+        press(&mut app, "jjK");
+        assert_eq!(
+            app.current().unwrap().key,
+            Key::Item("spotify:playlist:3FKTkhbClLGgKdPpbx3aHy".to_string())
+        );
+        assert_eq!(uris(&app)[0], "spotify:playlist:3FKTkhbClLGgKdPpbx3aHy");
+
+        // Deep down, out of Work
+        press(&mut app, "jjJ");
+        assert_eq!(uris(&app)[3], "spotify:playlist:5aNzxEEkRE9MgNkiuXmpOR");
+        assert_eq!(app.edits().len(), 2);
+
+        press(&mut app, "zz");
+        assert!(app.edits().is_empty());
+        assert_eq!(app.changes().ops().count(), 0);
+    }
+
+    #[test]
+    fn test_select_into_new_folder() {
+        let mut app = App::new(root_list());
+
+        // Select Chill and Focus, wrap them into a new folder
+        press(&mut app, "jj  ");
+        press(&mut app, "nEvening");
+        enter(&mut app);
+        assert!(matches!(app.mode, Mode::Normal));
+
+        let uris = uris(&app);
+        assert_eq!(uris.len(), 7);
+        assert!(uris[3].ends_with(":Evening"));
+        assert_eq!(uris[4], "spotify:playlist:3FKTkhbClLGgKdPpbx3aHy");
+        assert_eq!(uris[5], "spotify:playlist:37i9dQZF1DXcBWIGoYBM5M");
+        assert_eq!(app.edits().len(), 2);
+    }
+
+    #[test]
+    fn test_cut_paste_and_rebase() {
+        let mut app = App::new(root_list());
+
+        // Cut Focus and put it into Work, the cursor follows it, then rename Work
+        press(&mut app, "Gxggp");
+        assert_eq!(app.current().unwrap().depth, 1);
+        press(&mut app, "gr");
+        assert_eq!(app.mode, Mode::Rename("Work".to_string()));
+        app.handle_key(KeyEvent::new(KeyCode::Backspace, KeyModifiers::NONE));
+        press(&mut app, "ld");
+        enter(&mut app);
+        assert_eq!(
+            uris(&app)[..4],
+            [
+                "spotify:start-group:aaaaaaaaaaaaaaaa:World",
+                "spotify:playlist:5aNzxEEkRE9MgNkiuXmpOR",
+                "spotify:playlist:37i9dQZF1DXcBWIGoYBM5M",
+                "spotify:end-group:aaaaaaaaaaaaaaaa",
+            ]
+        );
+
+        // Someone unfollowed Focus meanwhile, only the rename still applies
+        let mut changed = root_list();
+        let mut request = changed.new_request();
+        request.remove(4, 1);
+        changed
+            .apply_changes(&request.build())
+            .expect("failed to apply changes");
+        changed.revision = "BBBB".to_string();
+
+        assert_eq!(app.rebase(changed), 1);
+        assert_eq!(app.revision(), "BBBB");
+        assert_eq!(app.changes().base_revision, "BBBB");
+    }
+
+    #[test]
+    fn test_quit_and_save() {
+        let mut app = App::new(root_list());
+        assert_eq!(press(&mut app, "s"), Action::Continue);
+        assert_eq!(press(&mut app, "q"), Action::Quit);
+
+        press(&mut app, "u");
+        assert_eq!(press(&mut app, "q"), Action::Continue);
+        assert_eq!(press(&mut app, "q"), Action::Quit);
+
+        assert_eq!(press(&mut app, "s"), Action::Continue);
+        assert_eq!(app.mode, Mode::ConfirmSave);
+        assert_eq!(press(&mut app, "y"), Action::Save);
+    }
+}
@@ -0,0 +1,127 @@
+//! Interactive editor for the library, enabled by the `tui` feature
+//!
+//! Edits are queued and previewed on a local copy of the root list, nothing is sent before
+//! they are confirmed, and then all of them go out as a single changes request.
+
+mod app;
+mod ui;
+
+use ratatui::crossterm::event::{self, Event, KeyEventKind};
+use ratatui::DefaultTerminal;
+use spotify_private_api::{is_revision_conflict, Session};
+
+use self::app::{Action, App};
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+pub async fn run(session: &Session) -> Result<()> {
+    let mut app = App::new(session.get_root_list().await?);
+
+    let mut terminal = ratatui::try_init()?;
+    let result = event_loop(&mut terminal, &mut app, session).await;
+    ratatui::restore();
+
+    result
+}
+
+async fn event_loop(
+    terminal: &mut DefaultTerminal,
+    app: &mut App,
+    session: &Session,
+) -> Result<()> {
+    loop {
+        terminal.draw(|frame| ui::draw(frame, app))?;
+
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+
+        match app.handle_key(key) {
+            Action::Continue => {}
+            Action::Quit => return Ok(()),
+            Action::Save => {
+                app.status = "sending...".to_string();
+                terminal.draw(|frame| ui::draw(frame, app))?;
+                save(app, session).await?;
+            }
+        }
+    }
+}
+
+/// Sends the queued edits, on a revision conflict the library is fetched again and the
+/// edits that still apply are queued on top of it
+async fn save(app: &mut App, session: &Session) -> Result<()> {
+    let edits = app.edits().len();
+
+    match session.send_changes(&app.changes()).await {
+        Ok(()) => {
+            app.reset(session.get_root_list().await?);
+            app.status = format!("sent {} edits", edits);
+        }
+        Err(e) if is_revision_conflict(e.as_ref()) => {
+            let kept = app.rebase(session.get_root_list().await?);
+            app.status = format!(
+                "the library was changed somewhere else, reloaded it and kept {} of {} edits, \
+                 check them and send again",
+                kept, edits
+            );
+        }
+        Err(e) => app.status = format!("failed to send: {}", e),
+    }
+
+    Ok(())
+}
+
+#[cfg(all(test, feature = "testing"))]
+mod tests {
+    use ratatui::crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+    use spotify_private_api::testing::FakeServer;
+    use spotify_private_api::RootList;
+
+    use super::app::App;
+    use super::save;
+
+    #[tokio::test]
+    async fn test_save_after_conflict() {
+        let root_list: RootList = serde_json::from_str(r#"{"revision":"AAAA","contents":{"items":[{"uri":"spotify:playlist:5aNzxEEkRE9MgNkiuXmpOR"},{"uri":"spotify:playlist:3FKTkhbClLGgKdPpbx3aHy"}]}}"#).expect("Couldn't parse rootlist");
+        let server = FakeServer::with_root_list(root_list)
+            .await
+            .expect("failed to start server");
+        let session = server.session().await.expect("failed to create session");
+
+        let mut app = App::new(session.get_root_list().await.unwrap());
+        // Move the second playlist up
+        app.handle_key(KeyEvent::new(KeyCode::Char('j'), KeyModifiers::NONE));
+        app.handle_key(KeyEvent::new(KeyCode::Char('K'), KeyModifiers::NONE));
+
+        // Another device follows a playlist meanwhile
+        let current = session.get_root_list().await.unwrap();
+        let mut request = current.new_request();
+        request.add_playlist(&"37i9dQZF1DXcBWIGoYBM5M".parse().unwrap(), 0);
+        session.send_changes(&request.build()).await.unwrap();
+
+        save(&mut app, &session).await.unwrap();
+        assert!(app.status.contains("changed somewhere else"));
+        assert_eq!(app.edits().len(), 1);
+        assert_eq!(server.received_changes().len(), 1);
+
+        save(&mut app, &session).await.unwrap();
+        assert_eq!(app.status, "sent 1 edits");
+        assert!(app.edits().is_empty());
+        assert_eq!(
+            server
+                .root_list()
+                .entries()
+                .map(|entry| entry.raw_uri().to_string())
+                .collect::<Vec<_>>(),
+            [
+                "spotify:playlist:37i9dQZF1DXcBWIGoYBM5M",
+                "spotify:playlist:3FKTkhbClLGgKdPpbx3aHy",
+                "spotify:playlist:5aNzxEEkRE9MgNkiuXmpOR",
+            ]
+        );
+    }
+}
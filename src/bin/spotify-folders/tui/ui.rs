@@ -0,0 +1,125 @@
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Modifier, Style, Stylize};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, List, ListItem, ListState, Paragraph};
+use ratatui::Frame;
+use spotify_private_api::TreeNode;
+
+use super::app::{display_name, App, Mode};
+
+const HELP: &str = "↑↓ move cursor  ⇧↑↓/JK move entry  ←→ fold  space select  x cut  p/P put  \
+                    n new folder  r rename  u ungroup  z undo  s send  q quit";
+
+pub fn draw(frame: &mut Frame, app: &App) {
+    let [main, status, help] = Layout::vertical([
+        Constraint::Min(3),
+        Constraint::Length(1),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+    let [tree, queue] =
+        Layout::horizontal([Constraint::Percentage(65), Constraint::Percentage(35)]).areas(main);
+
+    let rows: Vec<ListItem> = app
+        .rows
+        .iter()
+        .map(|row| {
+            let marker = match &row.node {
+                TreeNode::Folder(folder) if app.collapsed.contains(&folder.id) => "▸ ",
+                TreeNode::Folder(_) => "▾ ",
+                _ => "  ",
+            };
+            let selected = if app.selected.contains(&row.key) {
+                "● "
+            } else {
+                "  "
+            };
+
+            let mut name = Span::raw(display_name(&row.node));
+            if let TreeNode::Folder(_) = row.node {
+                name = name.bold();
+            }
+            if app.cut.contains(&row.key) {
+                name = name.add_modifier(Modifier::DIM | Modifier::ITALIC);
+            }
+
+            ListItem::new(Line::from(vec![
+                Span::raw(selected),
+                Span::raw("  ".repeat(row.depth)),
+                Span::raw(marker),
+                name,
+            ]))
+        })
+        .collect();
+
+    let title = format!(" Library (revision {}) ", short(app.revision()));
+    let list = List::new(rows)
+        .block(Block::bordered().title(title))
+        .highlight_style(Style::new().reversed());
+    let mut state = ListState::default().with_selected(Some(app.cursor));
+    frame.render_stateful_widget(list, tree, &mut state);
+
+    let edits: Vec<ListItem> = app
+        .edits()
+        .iter()
+        .map(|edit| ListItem::new(edit.to_string()))
+        .collect();
+    let title = format!(" Queued: {} ops ", app.changes().ops().count());
+    frame.render_widget(
+        List::new(edits).block(Block::bordered().title(title)),
+        queue,
+    );
+
+    let status_line = match &app.mode {
+        Mode::Normal => Line::raw(app.status.as_str()),
+        Mode::NewFolder(text) => {
+            Line::from(vec!["New folder: ".bold(), Span::raw(text), "█".into()])
+        }
+        Mode::Rename(text) => Line::from(vec!["Rename to: ".bold(), Span::raw(text), "█".into()]),
+        Mode::ConfirmSave => Line::from(
+            format!(
+                "Send {} edits as {} ops? [y/N]",
+                app.edits().len(),
+                app.changes().ops().count()
+            )
+            .bold(),
+        ),
+    };
+    frame.render_widget(Paragraph::new(status_line), status);
+    frame.render_widget(Paragraph::new(HELP).dim(), help);
+}
+
+fn short(revision: &str) -> &str {
+    revision.get(..8).unwrap_or(revision)
+}
+
+#[cfg(test)]
+mod tests {
+    use ratatui::backend::TestBackend;
+    use ratatui::Terminal;
+    use spotify_private_api::RootList;
+
+    use super::draw;
+    use crate::tui::app::App;
+
+    #[test]
+    fn test_draw() {
+        let rl: RootList = serde_json::from_str(r#"{"revision":"AAAAELqqrKuzaoeUKYP7gEzCzrx3h0rD","contents":{"items":[{"uri":"spotify:start-group:aaaaaaaaaaaaaaaa:Work"},{"uri":"spotify:playlist:5aNzxEEkRE9MgNkiuXmpOR"},{"uri":"spotify:end-group:aaaaaaaaaaaaaaaa"}],"metaItems":[{},{"attributes":{"name":"Deep"}},{}]}}"#).expect("Couldn't parse rootlist");
+        let app = App::new(rl);
+
+        let mut terminal = Terminal::new(TestBackend::new(100, 10)).unwrap();
+        terminal.draw(|frame| draw(frame, &app)).unwrap();
+
+        let screen: String = terminal
+            .backend()
+            .buffer()
+            .content()
+            .iter()
+            .map(|cell| cell.symbol())
+            .collect();
+        assert!(screen.contains("revision AAAAELqq"));
+        assert!(screen.contains("▾ Work"));
+        assert!(screen.contains("Deep"));
+        assert!(screen.contains("Queued: 0 ops"));
+    }
+}
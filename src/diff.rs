@@ -0,0 +1,676 @@
+use std::collections::{HashMap, HashSet};
+use std::convert::Infallible;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::api::folders::RootList;
+use crate::entries::Entry;
+use crate::lcs::longest_common_subsequence;
+use crate::uri::{FolderId, SpotifyUri};
+
+/// A folder as seen by a [`Change`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FolderRef {
+    pub id: FolderId,
+    pub name: String,
+}
+
+/// Uri of a playlist or another item in a [`Change`], kept as the server sent it when it
+/// isn't a valid spotify uri
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ItemUri {
+    Uri(SpotifyUri),
+    Raw(String),
+}
+
+impl FromStr for ItemUri {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(s.parse()
+            .map_or_else(|_| ItemUri::Raw(s.to_string()), ItemUri::Uri))
+    }
+}
+
+impl fmt::Display for ItemUri {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ItemUri::Uri(uri) => write!(f, "{}", uri),
+            ItemUri::Raw(uri) => f.write_str(uri),
+        }
+    }
+}
+
+/// A single structural difference between two root lists
+///
+/// `folder`, `from`, `to` and `parent` are `None` when the entry sits at the top level.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    FolderAdded {
+        folder: FolderRef,
+        parent: Option<FolderRef>,
+    },
+    FolderRemoved {
+        folder: FolderRef,
+        parent: Option<FolderRef>,
+    },
+    FolderRenamed {
+        id: FolderId,
+        old_name: String,
+        new_name: String,
+    },
+    FolderMoved {
+        folder: FolderRef,
+        from: Option<FolderRef>,
+        to: Option<FolderRef>,
+    },
+    FolderReordered {
+        folder: FolderRef,
+        parent: Option<FolderRef>,
+        old_index: usize,
+        new_index: usize,
+    },
+    PlaylistFollowed {
+        uri: ItemUri,
+        name: Option<String>,
+        folder: Option<FolderRef>,
+    },
+    PlaylistUnfollowed {
+        uri: ItemUri,
+        name: Option<String>,
+        folder: Option<FolderRef>,
+    },
+    PlaylistMoved {
+        uri: ItemUri,
+        name: Option<String>,
+        from: Option<FolderRef>,
+        to: Option<FolderRef>,
+    },
+    PlaylistReordered {
+        uri: ItemUri,
+        name: Option<String>,
+        folder: Option<FolderRef>,
+        old_index: usize,
+        new_index: usize,
+    },
+}
+
+/// Structural diff between two root lists, see [`RootList::diff`]
+///
+/// Indices in [`Change::FolderReordered`] and [`Change::PlaylistReordered`] are positions
+/// among the entry's siblings, not raw root list indices. Items that aren't valid spotify
+/// uris are compared by their raw uri, see [`ItemUri::Raw`].
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct RootListDiff {
+    pub changes: Vec<Change>,
+}
+
+impl RootListDiff {
+    pub fn new(old: &RootList, new: &RootList) -> Self {
+        let old = Snapshot::new(old);
+        let new = Snapshot::new(new);
+        let mut changes = vec![];
+
+        for (id, folder) in &old.folders {
+            if !new.folders.contains_key(id) {
+                changes.push(Change::FolderRemoved {
+                    folder: old.folder_ref(id),
+                    parent: old.parent_ref(&folder.parent),
+                });
+            }
+        }
+
+        for (id, folder) in &new.folders {
+            match old.folders.get(id) {
+                None => changes.push(Change::FolderAdded {
+                    folder: new.folder_ref(id),
+                    parent: new.parent_ref(&folder.parent),
+                }),
+                Some(old_folder) => {
+                    if old_folder.name != folder.name {
+                        changes.push(Change::FolderRenamed {
+                            id: id.clone(),
+                            old_name: old_folder.name.clone(),
+                            new_name: folder.name.clone(),
+                        });
+                    }
+                    if old_folder.parent != folder.parent {
+                        changes.push(Change::FolderMoved {
+                            folder: new.folder_ref(id),
+                            from: old.parent_ref(&old_folder.parent),
+                            to: new.parent_ref(&folder.parent),
+                        });
+                    }
+                }
+            }
+        }
+
+        for (uri, playlist) in &old.playlists {
+            if !new.playlists.contains_key(uri) {
+                changes.push(Change::PlaylistUnfollowed {
+                    uri: uri.clone(),
+                    name: playlist.name.clone(),
+                    folder: old.parent_ref(&playlist.parent),
+                });
+            }
+        }
+
+        for (uri, playlist) in &new.playlists {
+            match old.playlists.get(uri) {
+                None => changes.push(Change::PlaylistFollowed {
+                    uri: uri.clone(),
+                    name: playlist.name.clone(),
+                    folder: new.parent_ref(&playlist.parent),
+                }),
+                Some(old_playlist) if old_playlist.parent != playlist.parent => {
+                    changes.push(Change::PlaylistMoved {
+                        uri: uri.clone(),
+                        name: playlist.name.clone().or_else(|| old_playlist.name.clone()),
+                        from: old.parent_ref(&old_playlist.parent),
+                        to: new.parent_ref(&playlist.parent),
+                    })
+                }
+                Some(_) => {}
+            }
+        }
+
+        changes.extend(reorders(&old, &new));
+        changes.sort_by_key(Change::sort_key);
+
+        Self { changes }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+impl RootList {
+    /// Compares this root list (the older one) against `newer`
+    pub fn diff(&self, newer: &RootList) -> RootListDiff {
+        RootListDiff::new(self, newer)
+    }
+}
+
+impl Change {
+    fn sort_key(&self) -> (u8, String, String) {
+        match self {
+            Change::FolderRemoved { folder, .. } => (0, folder.name.clone(), folder.id.to_string()),
+            Change::FolderAdded { folder, .. } => (1, folder.name.clone(), folder.id.to_string()),
+            Change::FolderRenamed { id, old_name, .. } => (2, old_name.clone(), id.to_string()),
+            Change::FolderMoved { folder, .. } => (3, folder.name.clone(), folder.id.to_string()),
+            Change::PlaylistUnfollowed { uri, name, .. } => {
+                (4, display_name(name, uri), uri.to_string())
+            }
+            Change::PlaylistFollowed { uri, name, .. } => {
+                (5, display_name(name, uri), uri.to_string())
+            }
+            Change::PlaylistMoved { uri, name, .. } => {
+                (6, display_name(name, uri), uri.to_string())
+            }
+            Change::FolderReordered { folder, .. } => {
+                (7, folder.name.clone(), folder.id.to_string())
+            }
+            Change::PlaylistReordered { uri, name, .. } => {
+                (8, display_name(name, uri), uri.to_string())
+            }
+        }
+    }
+}
+
+fn display_name(name: &Option<String>, uri: &ItemUri) -> String {
+    name.clone().unwrap_or_else(|| uri.to_string())
+}
+
+struct Location<'a> {
+    folder: &'a Option<FolderRef>,
+}
+
+impl fmt::Display for Location<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.folder {
+            Some(folder) => write!(f, "{:?}", folder.name),
+            None => write!(f, "(root)"),
+        }
+    }
+}
+
+fn loc(folder: &Option<FolderRef>) -> Location<'_> {
+    Location { folder }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Change::FolderAdded { folder, parent } => {
+                write!(f, "+ folder {:?} in {}", folder.name, loc(parent))
+            }
+            Change::FolderRemoved { folder, parent } => {
+                write!(f, "- folder {:?} from {}", folder.name, loc(parent))
+            }
+            Change::FolderRenamed {
+                old_name, new_name, ..
+            } => write!(f, "~ rename folder {:?} to {:?}", old_name, new_name),
+            Change::FolderMoved { folder, from, to } => write!(
+                f,
+                "~ move folder {:?} from {} to {}",
+                folder.name,
+                loc(from),
+                loc(to)
+            ),
+            Change::FolderReordered {
+                folder,
+                parent,
+                old_index,
+                new_index,
+            } => write!(
+                f,
+                "^ reorder folder {:?} in {} ({} -> {})",
+                folder.name,
+                loc(parent),
+                old_index,
+                new_index
+            ),
+            Change::PlaylistFollowed { uri, name, folder } => {
+                write!(
+                    f,
+                    "+ follow {:?} in {}",
+                    display_name(name, uri),
+                    loc(folder)
+                )
+            }
+            Change::PlaylistUnfollowed { uri, name, folder } => write!(
+                f,
+                "- unfollow {:?} from {}",
+                display_name(name, uri),
+                loc(folder)
+            ),
+            Change::PlaylistMoved {
+                uri,
+                name,
+                from,
+                to,
+            } => write!(
+                f,
+                "~ move {:?} from {} to {}",
+                display_name(name, uri),
+                loc(from),
+                loc(to)
+            ),
+            Change::PlaylistReordered {
+                uri,
+                name,
+                folder,
+                old_index,
+                new_index,
+            } => write!(
+                f,
+                "^ reorder {:?} in {} ({} -> {})",
+                display_name(name, uri),
+                loc(folder),
+                old_index,
+                new_index
+            ),
+        }
+    }
+}
+
+impl fmt::Display for RootListDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.changes.is_empty() {
+            return writeln!(f, "no changes");
+        }
+
+        for change in &self.changes {
+            writeln!(f, "{}", change)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Key {
+    Folder(FolderId),
+    Playlist(ItemUri),
+}
+
+struct FolderInfo {
+    name: String,
+    parent: Option<FolderId>,
+}
+
+struct PlaylistInfo {
+    name: Option<String>,
+    parent: Option<FolderId>,
+}
+
+/// Flattened view of a root list keyed by folder id and playlist uri
+struct Snapshot {
+    folders: HashMap<FolderId, FolderInfo>,
+    playlists: HashMap<ItemUri, PlaylistInfo>,
+    children: HashMap<Option<FolderId>, Vec<Key>>,
+}
+
+impl Snapshot {
+    fn new(root_list: &RootList) -> Self {
+        let mut snapshot = Self {
+            folders: HashMap::new(),
+            playlists: HashMap::new(),
+            children: HashMap::new(),
+        };
+        let mut stack: Vec<FolderId> = vec![];
+
+        for item in root_list.entries() {
+            let parent = stack.last().cloned();
+
+            match item.entry {
+                Entry::FolderStart { id, name } => {
+                    let id = FolderId::from_checked(id);
+                    if snapshot.folders.contains_key(&id) {
+                        continue;
+                    }
+
+                    snapshot.folders.insert(
+                        id.clone(),
+                        FolderInfo {
+                            name: name.to_string(),
+                            parent: parent.clone(),
+                        },
+                    );
+                    snapshot
+                        .children
+                        .entry(parent)
+                        .or_default()
+                        .push(Key::Folder(id.clone()));
+                    stack.push(id);
+                }
+                Entry::FolderEnd { id } => {
+                    if let Some(position) = stack.iter().rposition(|open| open.as_str() == id) {
+                        stack.truncate(position);
+                    }
+                }
+                Entry::Playlist { .. } | Entry::Unknown(_) => {
+                    let uri = match &item.uri {
+                        Some(uri) => ItemUri::Uri(uri.clone()),
+                        None => ItemUri::Raw(item.raw_uri().to_string()),
+                    };
+                    if snapshot.playlists.contains_key(&uri) {
+                        continue;
+                    }
+
+                    snapshot.playlists.insert(
+                        uri.clone(),
+                        PlaylistInfo {
+                            name: item.entry.name().map(str::to_string),
+                            parent: parent.clone(),
+                        },
+                    );
+                    snapshot
+                        .children
+                        .entry(parent)
+                        .or_default()
+                        .push(Key::Playlist(uri));
+                }
+            }
+        }
+
+        snapshot
+    }
+
+    fn folder_ref(&self, id: &FolderId) -> FolderRef {
+        FolderRef {
+            id: id.clone(),
+            name: self
+                .folders
+                .get(id)
+                .map(|folder| folder.name.clone())
+                .unwrap_or_default(),
+        }
+    }
+
+    fn parent_ref(&self, parent: &Option<FolderId>) -> Option<FolderRef> {
+        parent.as_ref().map(|id| self.folder_ref(id))
+    }
+
+    fn parent_of(&self, key: &Key) -> Option<&Option<FolderId>> {
+        match key {
+            Key::Folder(id) => self.folders.get(id).map(|folder| &folder.parent),
+            Key::Playlist(uri) => self.playlists.get(uri).map(|playlist| &playlist.parent),
+        }
+    }
+}
+
+/// Entries that stayed in the same folder but changed their relative order.
+///
+/// The longest common subsequence of siblings is considered untouched, everything else
+/// in the folder was reordered.
+fn reorders(old: &Snapshot, new: &Snapshot) -> Vec<Change> {
+    let mut changes = vec![];
+
+    for (parent, new_children) in &new.children {
+        let Some(old_children) = old.children.get(parent) else {
+            continue;
+        };
+
+        let stayed = |key: &&Key| old.parent_of(key) == new.parent_of(key);
+        let old_common: Vec<&Key> = old_children
+            .iter()
+            .filter(|key| new.parent_of(key).is_some())
+            .filter(stayed)
+            .collect();
+        let new_common: Vec<&Key> = new_children
+            .iter()
+            .filter(|key| old.parent_of(key).is_some())
+            .filter(stayed)
+            .collect();
+
+        let untouched: HashSet<&Key> = longest_common_subsequence(&old_common, &new_common)
+            .into_iter()
+            .map(|(old_index, _)| old_common[old_index])
+            .collect();
+
+        for (new_index, key) in new_common.iter().enumerate() {
+            if untouched.contains(key) {
+                continue;
+            }
+
+            let old_index = old_common
+                .iter()
+                .position(|old_key| old_key == key)
+                .unwrap_or_default();
+
+            changes.push(match key {
+                Key::Folder(id) => Change::FolderReordered {
+                    folder: new.folder_ref(id),
+                    parent: new.parent_ref(parent),
+                    old_index,
+                    new_index,
+                },
+                Key::Playlist(uri) => Change::PlaylistReordered {
+                    uri: uri.clone(),
+                    name: new.playlists.get(uri).and_then(|p| p.name.clone()),
+                    folder: new.parent_ref(parent),
+                    old_index,
+                    new_index,
+                },
+            });
+        }
+    }
+
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Change, FolderRef, ItemUri};
+    use crate::api::folders::RootList;
+
+    fn root_list(items: &[(&str, Option<&str>)]) -> RootList {
+        let items_json: Vec<String> = items
+            .iter()
+            .map(|(uri, _)| format!(r#"{{"uri":"{}","attributes":{{}}}}"#, uri))
+            .collect();
+        let meta_json: Vec<String> = items
+            .iter()
+            .map(|(_, name)| match name {
+                Some(name) => format!(r#"{{"attributes":{{"name":"{}"}}}}"#, name),
+                None => "{}".to_string(),
+            })
+            .collect();
+
+        serde_json::from_str(&format!(
+            r#"{{"revision":"rev","length":{},"attributes":{{}},"contents":{{"pos":0,"truncated":false,"items":[{}],"metaItems":[{}]}},"timestamp":"0"}}"#,
+            items.len(),
+            items_json.join(","),
+            meta_json.join(",")
+        ))
+        .expect("Couldn't parse rootlist")
+    }
+
+    fn folder(id: &str, name: &str) -> FolderRef {
+        FolderRef {
+            id: id.parse().unwrap(),
+            name: name.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_diff_identical() {
+        let items = [
+            ("spotify:start-group:aaaa:Work", None),
+            ("spotify:playlist:0000000000000000000001", Some("One")),
+            ("spotify:end-group:aaaa", None),
+            ("spotify:playlist:0000000000000000000002", Some("Two")),
+        ];
+
+        let diff = root_list(&items).diff(&root_list(&items));
+
+        assert!(diff.is_empty());
+        assert_eq!(diff.to_string(), "no changes\n");
+    }
+
+    #[test]
+    fn test_diff_folders() {
+        let old = root_list(&[
+            ("spotify:start-group:aaaa:Work", None),
+            ("spotify:playlist:0000000000000000000001", Some("One")),
+            ("spotify:end-group:aaaa", None),
+            ("spotify:start-group:bbbb:Old", None),
+            ("spotify:end-group:bbbb", None),
+        ]);
+        let new = root_list(&[
+            ("spotify:start-group:aaaa:Focus", None),
+            ("spotify:start-group:cccc:Deep", None),
+            ("spotify:playlist:0000000000000000000001", Some("One")),
+            ("spotify:end-group:cccc", None),
+            ("spotify:end-group:aaaa", None),
+        ]);
+
+        let diff = old.diff(&new);
+
+        assert_eq!(
+            diff.changes,
+            vec![
+                Change::FolderRemoved {
+                    folder: folder("bbbb", "Old"),
+                    parent: None,
+                },
+                Change::FolderAdded {
+                    folder: folder("cccc", "Deep"),
+                    parent: Some(folder("aaaa", "Focus")),
+                },
+                Change::FolderRenamed {
+                    id: "aaaa".parse().unwrap(),
+                    old_name: "Work".to_string(),
+                    new_name: "Focus".to_string(),
+                },
+                Change::PlaylistMoved {
+                    uri: "spotify:playlist:0000000000000000000001".parse().unwrap(),
+                    name: Some("One".to_string()),
+                    from: Some(folder("aaaa", "Work")),
+                    to: Some(folder("cccc", "Deep")),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_diff_playlists() {
+        let old = root_list(&[
+            ("spotify:playlist:0000000000000000000001", Some("One")),
+            ("spotify:playlist:0000000000000000000002", Some("Two")),
+            ("spotify:playlist:0000000000000000000003", Some("Three")),
+            ("spotify:playlist:0000000000000000000004", Some("Four")),
+        ]);
+        let new = root_list(&[
+            ("spotify:playlist:0000000000000000000002", Some("Two")),
+            ("spotify:playlist:0000000000000000000003", Some("Three")),
+            ("spotify:playlist:0000000000000000000001", Some("One")),
+            ("spotify:playlist:0000000000000000000005", Some("Five")),
+        ]);
+
+        let diff = old.diff(&new);
+
+        assert_eq!(
+            diff.changes,
+            vec![
+                Change::PlaylistUnfollowed {
+                    uri: "spotify:playlist:0000000000000000000004".parse().unwrap(),
+                    name: Some("Four".to_string()),
+                    folder: None,
+                },
+                Change::PlaylistFollowed {
+                    uri: "spotify:playlist:0000000000000000000005".parse().unwrap(),
+                    name: Some("Five".to_string()),
+                    folder: None,
+                },
+                Change::PlaylistReordered {
+                    uri: "spotify:playlist:0000000000000000000001".parse().unwrap(),
+                    name: Some("One".to_string()),
+                    folder: None,
+                    old_index: 0,
+                    new_index: 2,
+                },
+            ]
+        );
+        assert_eq!(
+            diff.to_string(),
+            "- unfollow \"Four\" from (root)\n+ follow \"Five\" in (root)\n^ reorder \"One\" in (root) (0 -> 2)\n"
+        );
+    }
+
+    #[test]
+    fn test_diff_invalid_uris() {
+        let old = root_list(&[
+            ("spotify:start-group:aaaa:Work", None),
+            ("spotify:bogus:1", None),
+            ("spotify:end-group:aaaa", None),
+            ("spotify:bogus:2", None),
+        ]);
+        let new = root_list(&[
+            ("spotify:start-group:aaaa:Work", None),
+            ("spotify:end-group:aaaa", None),
+            ("spotify:bogus:1", None),
+        ]);
+
+        let diff = old.diff(&new);
+
+        assert_eq!(
+            diff.changes,
+            vec![
+                Change::PlaylistUnfollowed {
+                    uri: ItemUri::Raw("spotify:bogus:2".to_string()),
+                    name: None,
+                    folder: None,
+                },
+                Change::PlaylistMoved {
+                    uri: ItemUri::Raw("spotify:bogus:1".to_string()),
+                    name: None,
+                    from: Some(folder("aaaa", "Work")),
+                    to: None,
+                },
+            ]
+        );
+    }
+}
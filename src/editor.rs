@@ -0,0 +1,487 @@
+use crate::api::folders::{Changes, FolderRequest, OperationItem, RootList};
+use crate::tree::{FolderNode, TreeNode};
+use crate::uri::{FolderId, SpotifyId, SpotifyUri};
+use crate::Result;
+
+/// Edits a root list by folder paths such as `Work/Focus/Deep`, the way a user thinks of it
+///
+/// Every edit is turned into index based ops right away and simulated on a copy of the root
+/// list, so later edits see the result of earlier ones and [`RootListEditor::root_list`]
+/// previews what the server will end up with. [`RootListEditor::build`] makes a single
+/// request out of all of them.
+///
+/// Paths are folder names separated by `/`, an empty path or `/` is the top level. The last
+/// part of a path can also be a playlist's name or base62 id, and a playlist can be given
+/// as its uri or `open.spotify.com` link instead of a path.
+pub struct RootListEditor {
+    root_list: RootList,
+    request: FolderRequest,
+}
+
+impl RootListEditor {
+    pub fn new(root_list: &RootList) -> Self {
+        Self {
+            root_list: root_list.clone(),
+            request: root_list.new_request(),
+        }
+    }
+
+    /// The root list with every edit so far applied
+    pub fn root_list(&self) -> &RootList {
+        &self.root_list
+    }
+
+    pub fn build(&self) -> Changes {
+        self.request.build()
+    }
+
+    /// Creates an empty folder at the end of its parent folder, which has to exist
+    pub fn create_folder(&mut self, path: &str) -> Result<&mut Self> {
+        let (parent, name) = split_path(path);
+        let tree = self.root_list.tree();
+        let parent = find_folder(&tree, parent)?;
+        let id = self.root_list.generate_folder_id()?;
+
+        self.create_folder_in(parent, name, &id)
+    }
+
+    /// Moves a folder, with everything inside it, or a playlist to the end of `folder`
+    pub fn move_to(&mut self, path: &str, folder: &str) -> Result<&mut Self> {
+        let tree = self.root_list.tree();
+        let node = find(&tree, path)?;
+        let target = find_folder(&tree, folder)?;
+
+        self.move_into(node, target)
+    }
+
+    /// Renames a folder, its id stays the same
+    pub fn rename(&mut self, path: &str, name: &str) -> Result<&mut Self> {
+        let tree = self.root_list.tree();
+        let folder = find_folder(&tree, path)?.ok_or("can't rename the top level")?;
+
+        self.rename_folder(folder, name)
+    }
+
+    /// Removes a folder but keeps everything inside it where it is
+    pub fn ungroup(&mut self, path: &str) -> Result<&mut Self> {
+        let tree = self.root_list.tree();
+        let folder = find_folder(&tree, path)?.ok_or("can't ungroup the top level")?;
+
+        self.ungroup_folder(folder)
+    }
+
+    /// Removes a folder with everything inside it, or unfollows a playlist
+    pub fn remove(&mut self, path: &str) -> Result<&mut Self> {
+        let tree = self.root_list.tree();
+        let node = find(&tree, path)?;
+
+        self.remove_node(node)
+    }
+
+    /// Follows the playlist `id` and places it at the end of `folder`
+    pub fn follow(&mut self, id: &SpotifyId, folder: &str) -> Result<&mut Self> {
+        if self
+            .root_list
+            .find_by_uri(&SpotifyUri::Playlist(*id))
+            .is_some()
+        {
+            return Err(format!("playlist {} is already followed", id).into());
+        }
+
+        let tree = self.root_list.tree();
+        let index = end_of(find_folder(&tree, folder)?, &self.root_list);
+        let mut request = self.root_list.new_request();
+        request.add_playlist(id, index as u32);
+
+        self.apply(request)
+    }
+
+    /// Finds the folder or playlist at `path` in the edited root list
+    pub fn find(&self, path: &str) -> Result<TreeNode> {
+        find(&self.root_list.tree(), path).cloned()
+    }
+
+    /// The edited root list as a tree, its nodes can be passed to the methods below that
+    /// take nodes instead of paths until the next edit
+    pub fn tree(&self) -> Vec<TreeNode> {
+        self.root_list.tree()
+    }
+
+    /// Creates an empty folder with the id `id` at the end of `parent`
+    pub fn create_folder_in(
+        &mut self,
+        parent: Option<&FolderNode>,
+        name: &str,
+        id: &FolderId,
+    ) -> Result<&mut Self> {
+        if name.is_empty() {
+            return Err("folder name is empty".into());
+        }
+        if let Some(parent) = parent {
+            self.check(&TreeNode::Folder(parent.clone()))?;
+        }
+
+        let tree = self.root_list.tree();
+        if children(&tree, parent)
+            .iter()
+            .any(|node| matches!(node, TreeNode::Folder(folder) if folder.name == name))
+        {
+            return Err(format!("folder {} already exists", name).into());
+        }
+
+        let index = end_of(parent, &self.root_list) as u32;
+        let mut request = self.root_list.new_request();
+        request.add(name, id, index, index + 1)?;
+
+        self.apply(request)
+    }
+
+    /// Moves `node` to the end of `folder`, the top level if `None`
+    pub fn move_into(&mut self, node: &TreeNode, folder: Option<&FolderNode>) -> Result<&mut Self> {
+        let to = end_of(folder, &self.root_list);
+
+        self.move_node(node, to)
+    }
+
+    /// Moves `node` right in front of `before`
+    pub fn move_before(&mut self, node: &TreeNode, before: &TreeNode) -> Result<&mut Self> {
+        self.check(before)?;
+
+        self.move_node(node, before.index())
+    }
+
+    /// Moves `node` right behind `after`, folders are passed as a whole
+    pub fn move_after(&mut self, node: &TreeNode, after: &TreeNode) -> Result<&mut Self> {
+        self.check(after)?;
+
+        self.move_node(node, after.index() + after.span())
+    }
+
+    pub fn rename_folder(&mut self, folder: &FolderNode, name: &str) -> Result<&mut Self> {
+        if name.is_empty() {
+            return Err("folder name is empty".into());
+        }
+        self.check(&TreeNode::Folder(folder.clone()))?;
+
+        let item = &self.root_list.contents.items[folder.index];
+        let renamed = OperationItem {
+            uri: SpotifyUri::StartGroup {
+                id: folder.id.clone(),
+                name: name.to_string(),
+            }
+            .to_string(),
+            attributes: item.attributes.clone(),
+            extra: Default::default(),
+        };
+        let mut request = self.root_list.new_request();
+        request
+            .remove(folder.index as u32, 1)
+            .add_item(renamed, folder.index as u32);
+
+        self.apply(request)
+    }
+
+    pub fn ungroup_folder(&mut self, folder: &FolderNode) -> Result<&mut Self> {
+        self.check(&TreeNode::Folder(folder.clone()))?;
+
+        let mut request = self.root_list.new_request();
+        if let Some(end_index) = folder.end_index {
+            request.remove(end_index as u32, 1);
+        }
+        request.remove(folder.index as u32, 1);
+
+        self.apply(request)
+    }
+
+    pub fn remove_node(&mut self, node: &TreeNode) -> Result<&mut Self> {
+        self.check(node)?;
+
+        let mut request = self.root_list.new_request();
+        request.remove(node.index() as u32, node.span() as u32);
+
+        self.apply(request)
+    }
+
+    fn move_node(&mut self, node: &TreeNode, to: usize) -> Result<&mut Self> {
+        self.check(node)?;
+
+        let (from, len) = (node.index(), node.span());
+        if to > from && to < from + len {
+            return Err(format!("can't move {} into itself", display(node)).into());
+        }
+
+        let mut request = self.root_list.new_request();
+        request.mov(from as u32, to as u32, len as u32);
+
+        self.apply(request)
+    }
+
+    /// Fails if `node` doesn't come from the current tree
+    fn check(&self, node: &TreeNode) -> Result<()> {
+        let uri = self
+            .root_list
+            .contents
+            .items
+            .get(node.index())
+            .map(|item| item.uri.as_str());
+        let current = match node {
+            TreeNode::Folder(folder) => uri.is_some_and(|uri| {
+                matches!(
+                    uri.parse(),
+                    Ok(SpotifyUri::StartGroup { id, .. }) if id == folder.id
+                )
+            }),
+            TreeNode::Playlist(item) | TreeNode::Other(item) => uri == Some(item.uri.as_str()),
+        };
+
+        match current {
+            true => Ok(()),
+            false => Err(format!("{} moved since the tree was read", display(node)).into()),
+        }
+    }
+
+    fn apply(&mut self, request: FolderRequest) -> Result<&mut Self> {
+        self.root_list.apply_changes(&request.build())?;
+        self.request.extend(&request);
+
+        Ok(self)
+    }
+}
+
+fn display(node: &TreeNode) -> String {
+    match node {
+        TreeNode::Folder(folder) => folder.name.clone(),
+        TreeNode::Playlist(item) | TreeNode::Other(item) => {
+            item.name.clone().unwrap_or_else(|| item.uri.clone())
+        }
+    }
+}
+
+/// Splits `path` into its parent folder and its last part
+fn split_path(path: &str) -> (&str, &str) {
+    let path = path.trim_matches('/');
+
+    path.rsplit_once('/').unwrap_or(("", path))
+}
+
+/// Parses a playlist given as a uri or link
+fn parse_playlist(path: &str) -> Option<SpotifyId> {
+    match path
+        .parse::<SpotifyUri>()
+        .or_else(|_| SpotifyUri::from_url(path))
+    {
+        Ok(SpotifyUri::Playlist(id)) => Some(id),
+        _ => None,
+    }
+}
+
+fn children<'a>(tree: &'a [TreeNode], folder: Option<&'a FolderNode>) -> &'a [TreeNode] {
+    folder.map_or(tree, |folder| &folder.children)
+}
+
+/// The folder at `path`, `None` for the top level
+fn find_folder<'a>(tree: &'a [TreeNode], path: &str) -> Result<Option<&'a FolderNode>> {
+    if path.trim_matches('/').is_empty() {
+        return Ok(None);
+    }
+
+    match find(tree, path)? {
+        TreeNode::Folder(folder) => Ok(Some(folder)),
+        _ => Err(format!("{} is not a folder", path).into()),
+    }
+}
+
+fn find<'a>(tree: &'a [TreeNode], path: &str) -> Result<&'a TreeNode> {
+    if let Some(id) = parse_playlist(path) {
+        return find_playlist(tree, &id).ok_or_else(|| format!("{} is not followed", path).into());
+    }
+
+    let (parent, name) = split_path(path);
+    if name.is_empty() {
+        return Err("path is empty".into());
+    }
+    let parent = find_folder(tree, parent)?;
+
+    let matches: Vec<&TreeNode> = children(tree, parent)
+        .iter()
+        .filter(|node| match node {
+            TreeNode::Folder(folder) => folder.name == name,
+            TreeNode::Playlist(item) => {
+                item.name.as_deref() == Some(name)
+                    || item.uri.strip_prefix("spotify:playlist:") == Some(name)
+            }
+            TreeNode::Other(item) => item.uri == name,
+        })
+        .collect();
+
+    match matches[..] {
+        [node] => Ok(node),
+        [] => Err(format!("{} not found", path).into()),
+        _ => Err(format!("{} is ambiguous, {} entries match", path, matches.len()).into()),
+    }
+}
+
+fn find_playlist<'a>(tree: &'a [TreeNode], id: &SpotifyId) -> Option<&'a TreeNode> {
+    let uri = SpotifyUri::Playlist(*id).to_string();
+
+    tree.iter().find_map(|node| match node {
+        TreeNode::Playlist(item) if item.uri == uri => Some(node),
+        TreeNode::Folder(folder) => find_playlist(&folder.children, id),
+        _ => None,
+    })
+}
+
+/// Index that appends to `folder`, or to the top level
+fn end_of(folder: Option<&FolderNode>, root_list: &RootList) -> usize {
+    match folder {
+        Some(folder) => folder.end_index.unwrap_or_else(|| {
+            folder.index + 1 + folder.children.iter().map(TreeNode::span).sum::<usize>()
+        }),
+        None => root_list.len(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::RootListEditor;
+    use crate::api::folders::RootList;
+    use crate::generators::SeededIds;
+    use crate::tree::TreeNode;
+
+    const ROOT_LIST: &str = r#"{"revision":"AAAAELqqrKuzaoeUKYP7gEzCzrx3h0rD","length":6,"contents":{"pos":0,"truncated":false,"items":[{"uri":"spotify:start-group:aaaaaaaaaaaaaaaa:Work"},{"uri":"spotify:start-group:bbbbbbbbbbbbbbbb:Focus"},{"uri":"spotify:playlist:5aNzxEEkRE9MgNkiuXmpOR"},{"uri":"spotify:end-group:bbbbbbbbbbbbbbbb"},{"uri":"spotify:end-group:aaaaaaaaaaaaaaaa"},{"uri":"spotify:playlist:3FKTkhbClLGgKdPpbx3aHy"}],"metaItems":[{},{},{"attributes":{"name":"Deep"}},{},{},{"attributes":{"name":"Chill"}}]}}"#;
+
+    fn root_list() -> RootList {
+        let mut rl: RootList = serde_json::from_str(ROOT_LIST).expect("Couldn't parse rootlist");
+        rl.set_id_generator(Arc::new(SeededIds::new(1)));
+
+        rl
+    }
+
+    fn uris(root_list: &RootList) -> Vec<&str> {
+        root_list.entries().map(|entry| entry.raw_uri()).collect()
+    }
+
+    #[test]
+    fn test_edits() {
+        let original = root_list();
+        let mut editor = RootListEditor::new(&original);
+
+        editor
+            .create_folder("Work/Evening")
+            .expect("failed to create folder");
+        let evening = match editor.find("Work/Evening").expect("folder not found") {
+            crate::tree::TreeNode::Folder(folder) => folder.id,
+            node => panic!("expected a folder, got {:?}", node),
+        };
+        editor
+            .move_to("Chill", "Work/Evening")
+            .expect("failed to move playlist")
+            .rename("Work/Focus", "Deep Work")
+            .expect("failed to rename folder")
+            .ungroup("Work")
+            .expect("failed to ungroup folder");
+
+        let expected = vec![
+            "spotify:start-group:bbbbbbbbbbbbbbbb:Deep Work".to_string(),
+            "spotify:playlist:5aNzxEEkRE9MgNkiuXmpOR".to_string(),
+            "spotify:end-group:bbbbbbbbbbbbbbbb".to_string(),
+            format!("spotify:start-group:{}:Evening", evening),
+            "spotify:playlist:3FKTkhbClLGgKdPpbx3aHy".to_string(),
+            format!("spotify:end-group:{}", evening),
+        ];
+        assert_eq!(uris(editor.root_list()), expected);
+
+        // The single request does the same to the original
+        let mut sent = original.clone();
+        sent.apply_changes(&editor.build())
+            .expect("failed to apply changes");
+        assert_eq!(uris(&sent), expected);
+    }
+
+    #[test]
+    fn test_paths() {
+        let mut editor = RootListEditor::new(&root_list());
+
+        assert!(editor.find("Work/Focus/Deep").is_ok());
+        assert!(editor.find("Work/Focus/5aNzxEEkRE9MgNkiuXmpOR").is_ok());
+        assert!(editor
+            .find("https://open.spotify.com/playlist/5aNzxEEkRE9MgNkiuXmpOR?si=1")
+            .is_ok());
+        assert!(editor.find("Work/Deep").is_err());
+        assert!(editor.find("Chill/Deep").is_err());
+
+        assert!(editor.create_folder("Work/Focus").is_err());
+        assert!(editor.create_folder("Missing/New").is_err());
+        assert!(editor.move_to("Work", "Work/Focus").is_err());
+        assert!(editor
+            .follow(&"5aNzxEEkRE9MgNkiuXmpOR".parse().unwrap(), "")
+            .is_err());
+        assert_eq!(editor.build().ops().count(), 0);
+    }
+
+    #[test]
+    fn test_node_edits() {
+        let mut editor = RootListEditor::new(&root_list());
+
+        let tree = editor.tree();
+        let (work, chill) = (&tree[0], &tree[1]);
+        editor
+            .move_before(chill, work)
+            .expect("failed to move playlist up");
+        // Nodes are stale after an edit
+        assert!(editor.move_after(work, chill).is_err());
+
+        let tree = editor.tree();
+        let TreeNode::Folder(work) = &tree[1] else {
+            panic!("expected a folder");
+        };
+        let focus = work.children[0].clone();
+        editor
+            .move_after(&tree[0], &focus)
+            .expect("failed to move playlist into the folder")
+            .create_folder_in(None, "New", &"cccccccccccccccc".parse().unwrap())
+            .expect("failed to create folder");
+        assert_eq!(
+            uris(editor.root_list()),
+            [
+                "spotify:start-group:aaaaaaaaaaaaaaaa:Work",
+                "spotify:start-group:bbbbbbbbbbbbbbbb:Focus",
+                "spotify:playlist:5aNzxEEkRE9MgNkiuXmpOR",
+                "spotify:end-group:bbbbbbbbbbbbbbbb",
+                "spotify:playlist:3FKTkhbClLGgKdPpbx3aHy",
+                "spotify:end-group:aaaaaaaaaaaaaaaa",
+                "spotify:start-group:cccccccccccccccc:New",
+                "spotify:end-group:cccccccccccccccc",
+            ]
+        );
+
+        let tree = editor.tree();
+        let TreeNode::Folder(work) = &tree[0] else {
+            panic!("expected a folder");
+        };
+        assert!(editor.move_after(&tree[0], &work.children[0]).is_err());
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut editor = RootListEditor::new(&root_list());
+
+        editor
+            .follow(&"37i9dQZF1DXcBWIGoYBM5M".parse().unwrap(), "Work/Focus")
+            .expect("failed to follow playlist")
+            .remove("Chill")
+            .expect("failed to unfollow playlist");
+        assert_eq!(
+            uris(editor.root_list())[2..4],
+            [
+                "spotify:playlist:5aNzxEEkRE9MgNkiuXmpOR",
+                "spotify:playlist:37i9dQZF1DXcBWIGoYBM5M"
+            ]
+        );
+
+        editor.remove("Work").expect("failed to remove folder");
+        assert!(editor.root_list().is_empty());
+    }
+}
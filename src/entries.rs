@@ -0,0 +1,302 @@
+use std::time::SystemTime;
+
+use crate::api::attributes::{ItemAttributes, ListAttributes};
+use crate::api::folders::{RootList, RootListMetaItem};
+use crate::uri::{FolderId, GroupUri, SpotifyId, SpotifyUri};
+
+/// What a root list item stands for
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Entry<'a> {
+    FolderStart {
+        id: &'a str,
+        name: &'a str,
+    },
+    FolderEnd {
+        id: &'a str,
+    },
+    Playlist {
+        id: SpotifyId,
+        meta: Option<&'a RootListMetaItem>,
+    },
+    Unknown(&'a str),
+}
+
+impl<'a> Entry<'a> {
+    /// Playlists and folders with an invalid uri are [`Entry::Unknown`]
+    pub(crate) fn parse(uri: &'a str, meta: Option<&'a RootListMetaItem>) -> Self {
+        Self::parse_with_uri(uri, meta).0
+    }
+
+    /// The entry together with the parsed uri, `None` if `uri` isn't a valid spotify uri
+    fn parse_with_uri(
+        uri: &'a str,
+        meta: Option<&'a RootListMetaItem>,
+    ) -> (Self, Option<SpotifyUri>) {
+        match GroupUri::parse(uri) {
+            Ok(group @ GroupUri::Start { id, name }) => {
+                (Entry::FolderStart { id, name }, Some(group.to_uri()))
+            }
+            Ok(group @ GroupUri::End { id }) => (Entry::FolderEnd { id }, Some(group.to_uri())),
+            Err(_) => match uri.parse() {
+                Ok(SpotifyUri::Playlist(id)) => {
+                    (Entry::Playlist { id, meta }, Some(SpotifyUri::Playlist(id)))
+                }
+                parsed => (Entry::Unknown(uri), parsed.ok()),
+            },
+        }
+    }
+
+    /// Folder name or playlist name
+    pub fn name(&self) -> Option<&'a str> {
+        match self {
+            Entry::FolderStart { name, .. } => Some(name),
+            Entry::Playlist { meta, .. } => meta.and_then(RootListMetaItem::name),
+            _ => None,
+        }
+    }
+}
+
+/// A root list item together with its position
+#[derive(Debug, Clone, PartialEq)]
+pub struct RootListEntry<'a> {
+    /// Index in the root list, as used by [`FolderRequest`](crate::FolderRequest)
+    pub index: usize,
+    /// Number of folders around the entry, a folder's start and end are outside of it.
+    /// Ends are paired with starts by folder id, an end without a matching start doesn't
+    /// close anything
+    pub depth: usize,
+    /// `None` if the item isn't a valid spotify uri, see [`RootListEntry::raw_uri`]
+    pub uri: Option<SpotifyUri>,
+    pub attributes: &'a ItemAttributes,
+    pub entry: Entry<'a>,
+    raw_uri: &'a str,
+}
+
+impl<'a> RootListEntry<'a> {
+    /// The uri as the server sent it
+    pub fn raw_uri(&self) -> &'a str {
+        self.raw_uri
+    }
+}
+
+/// Iterator over the entries of a root list, see [`RootList::entries`]
+#[derive(Debug, Clone)]
+pub struct Entries<'a> {
+    root_list: &'a RootList,
+    index: usize,
+    /// Ids of the open folders, innermost last
+    open: Vec<&'a str>,
+}
+
+impl<'a> Iterator for Entries<'a> {
+    type Item = RootListEntry<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let item = self.root_list.contents.items.get(self.index)?;
+        let meta = self.root_list.contents.meta_items.get(self.index);
+        let (entry, uri) = Entry::parse_with_uri(&item.uri, meta);
+
+        let depth = match entry {
+            Entry::FolderStart { id, .. } => {
+                self.open.push(id);
+                self.open.len() - 1
+            }
+            Entry::FolderEnd { id } => {
+                if let Some(position) = self.open.iter().rposition(|open| *open == id) {
+                    self.open.truncate(position);
+                }
+                self.open.len()
+            }
+            _ => self.open.len(),
+        };
+
+        let root_list_entry = RootListEntry {
+            index: self.index,
+            depth,
+            uri,
+            attributes: &item.attributes,
+            entry,
+            raw_uri: &item.uri,
+        };
+        self.index += 1;
+
+        Some(root_list_entry)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.root_list.contents.items.len() - self.index;
+        (remaining, Some(remaining))
+    }
+}
+
+impl RootList {
+    pub fn entries(&self) -> Entries<'_> {
+        Entries {
+            root_list: self,
+            index: 0,
+            open: vec![],
+        }
+    }
+
+    /// Number of fetched items, folder starts and ends included
+    pub fn len(&self) -> usize {
+        self.contents.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.contents.items.is_empty()
+    }
+
+    pub fn timestamp(&self) -> Option<SystemTime> {
+        self.timestamp
+    }
+
+    pub fn attributes(&self) -> Option<&ListAttributes> {
+        self.attributes.as_ref()
+    }
+
+    pub fn find_by_uri(&self, uri: &SpotifyUri) -> Option<RootListEntry<'_>> {
+        self.entries().find(|entry| entry.uri.as_ref() == Some(uri))
+    }
+
+    /// Finds the start of the folder `id`
+    pub fn find_by_folder_id(&self, id: &FolderId) -> Option<RootListEntry<'_>> {
+        self.entries().find(|entry| match entry.entry {
+            Entry::FolderStart { id: folder_id, .. } => folder_id == id.as_str(),
+            _ => false,
+        })
+    }
+
+    pub fn find_by_playlist_id(&self, id: &SpotifyId) -> Option<RootListEntry<'_>> {
+        self.entries().find(|entry| match entry.entry {
+            Entry::Playlist {
+                id: playlist_id, ..
+            } => playlist_id == *id,
+            _ => false,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use super::Entry;
+    use crate::api::folders::RootList;
+
+    const ROOT_LIST: &str = r#"{"revision":"AAAAELqqrKuzaoeUKYP7gEzCzrx3h0rD","length":6,"attributes":{},"contents":{"pos":0,"truncated":false,"items":[{"uri":"spotify:start-group:123456789abcdefa:Work","attributes":{"timestamp":"1665495078416"}},{"uri":"spotify:start-group:fedcba9876543210:Focus"},{"uri":"spotify:playlist:5aNzxEEkRE9MgNkiuXmpOR"},{"uri":"spotify:end-group:fedcba9876543210"},{"uri":"spotify:end-group:123456789abcdefa"},{"uri":"spotify:local:artist:album:title:1"}],"metaItems":[{},{},{"attributes":{"name":"My Playlist #2"},"ownerUsername":"31h5mfzvglpwfevvaens2flw7smu"},{},{},{}]},"timestamp":"1665495078416"}"#;
+
+    fn root_list() -> RootList {
+        serde_json::from_str(ROOT_LIST).expect("Couldn't parse rootlist")
+    }
+
+    #[test]
+    fn test_entries() {
+        let rl = root_list();
+
+        let entries: Vec<(usize, usize, Entry)> = rl
+            .entries()
+            .map(|entry| (entry.index, entry.depth, entry.entry))
+            .collect();
+
+        assert_eq!(
+            entries,
+            vec![
+                (
+                    0,
+                    0,
+                    Entry::FolderStart {
+                        id: "123456789abcdefa",
+                        name: "Work"
+                    }
+                ),
+                (
+                    1,
+                    1,
+                    Entry::FolderStart {
+                        id: "fedcba9876543210",
+                        name: "Focus"
+                    }
+                ),
+                (
+                    2,
+                    2,
+                    Entry::Playlist {
+                        id: "5aNzxEEkRE9MgNkiuXmpOR".parse().unwrap(),
+                        meta: rl.contents.meta_items.get(2)
+                    }
+                ),
+                (
+                    3,
+                    1,
+                    Entry::FolderEnd {
+                        id: "fedcba9876543210"
+                    }
+                ),
+                (
+                    4,
+                    0,
+                    Entry::FolderEnd {
+                        id: "123456789abcdefa"
+                    }
+                ),
+                (5, 0, Entry::Unknown("spotify:local:artist:album:title:1")),
+            ]
+        );
+        assert_eq!(rl.len(), 6);
+        assert_eq!(
+            rl.timestamp(),
+            Some(UNIX_EPOCH + Duration::from_millis(1665495078416))
+        );
+    }
+
+    #[test]
+    fn test_find() {
+        let rl = root_list();
+
+        let playlist = rl
+            .find_by_playlist_id(&"5aNzxEEkRE9MgNkiuXmpOR".parse().unwrap())
+            .expect("playlist not found");
+        assert_eq!(playlist.index, 2);
+        assert_eq!(playlist.entry.name(), Some("My Playlist #2"));
+        assert_eq!(
+            playlist.uri,
+            Some("spotify:playlist:5aNzxEEkRE9MgNkiuXmpOR".parse().unwrap())
+        );
+        assert_eq!(
+            playlist.raw_uri(),
+            "spotify:playlist:5aNzxEEkRE9MgNkiuXmpOR"
+        );
+
+        let folder = rl
+            .find_by_folder_id(&"fedcba9876543210".parse().unwrap())
+            .expect("folder not found");
+        assert_eq!(folder.index, 1);
+        assert_eq!(folder.entry.name(), Some("Focus"));
+
+        let end = rl
+            .find_by_uri(&"spotify:end-group:123456789abcdefa".parse().unwrap())
+            .expect("folder end not found");
+        assert_eq!(end.index, 4);
+
+        assert_eq!(rl.find_by_folder_id(&"missing".parse().unwrap()), None);
+        assert_eq!(
+            rl.find_by_playlist_id(&"3FKTkhbClLGgKdPpbx3aHy".parse().unwrap()),
+            None
+        );
+    }
+
+    #[test]
+    fn test_unmatched_folder_end() {
+        // The start is missing its name, so it isn't a folder and its end closes nothing
+        let rl: RootList = serde_json::from_str(r#"{"revision":"AAAA","contents":{"items":[{"uri":"spotify:start-group:123456789abcdefa"},{"uri":"spotify:playlist:5aNzxEEkRE9MgNkiuXmpOR"},{"uri":"spotify:end-group:123456789abcdefa"},{"uri":"spotify:start-group:fedcba9876543210:Focus"},{"uri":"spotify:end-group:123456789abcdefa"},{"uri":"spotify:playlist:5aNzxEEkRE9MgNkiuXmpOR"}]}}"#)
+            .expect("Couldn't parse rootlist");
+
+        let depths: Vec<usize> = rl.entries().map(|entry| entry.depth).collect();
+        assert_eq!(depths, vec![0, 0, 0, 0, 1, 1]);
+        assert!(matches!(
+            rl.entries().next().unwrap().entry,
+            Entry::Unknown(_)
+        ));
+    }
+}
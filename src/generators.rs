@@ -0,0 +1,165 @@
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const FOLDER_ID_LENGTH: usize = 16;
+const FOLDER_ID_CHARSET: &str = "abcdef1234567890";
+
+/// Source of the timestamps of added items
+pub trait Clock: Debug + Send + Sync {
+    fn now(&self) -> SystemTime;
+}
+
+/// Source of new folder ids, 16 lowercase hex digits
+pub trait IdGenerator: Debug + Send + Sync {
+    fn folder_id(&self) -> String;
+}
+
+/// Whether `id` has the format [`IdGenerator::folder_id`] promises
+pub(crate) fn is_folder_id(id: &str) -> bool {
+    id.len() == FOLDER_ID_LENGTH
+        && id
+            .bytes()
+            .all(|c| FOLDER_ID_CHARSET.as_bytes().contains(&c))
+}
+
+/// The real time, the default
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/// Starts at a fixed time and moves `step` forward on every call, the step is zero by default
+#[derive(Debug)]
+pub struct FixedClock {
+    next: Mutex<SystemTime>,
+    step: Duration,
+}
+
+impl FixedClock {
+    pub fn new(start: SystemTime) -> Self {
+        Self {
+            next: Mutex::new(start),
+            step: Duration::ZERO,
+        }
+    }
+
+    /// Starts at `millis` milliseconds since the unix epoch
+    pub fn from_millis(millis: u64) -> Self {
+        Self::new(UNIX_EPOCH + Duration::from_millis(millis))
+    }
+
+    pub fn with_step(mut self, step: Duration) -> Self {
+        self.step = step;
+
+        self
+    }
+}
+
+impl Clock for FixedClock {
+    fn now(&self) -> SystemTime {
+        let mut next = self.next.lock().unwrap();
+        let now = *next;
+        *next += self.step;
+
+        now
+    }
+}
+
+/// Random ids, the default
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RandomIds;
+
+impl IdGenerator for RandomIds {
+    fn folder_id(&self) -> String {
+        random_string::generate(FOLDER_ID_LENGTH, FOLDER_ID_CHARSET)
+    }
+}
+
+/// The same sequence of ids for the same seed
+#[derive(Debug)]
+pub struct SeededIds {
+    state: Mutex<u64>,
+}
+
+impl SeededIds {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            state: Mutex::new(seed),
+        }
+    }
+}
+
+impl IdGenerator for SeededIds {
+    fn folder_id(&self) -> String {
+        // splitmix64
+        let mut state = self.state.lock().unwrap();
+        *state = state.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = *state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+
+        format!("{:016x}", z ^ (z >> 31))
+    }
+}
+
+/// The clock and id generator a list hands to the requests it creates
+///
+/// They aren't part of the list, so every two are equal.
+#[derive(Debug, Clone)]
+pub(crate) struct Generators {
+    pub(crate) clock: Arc<dyn Clock>,
+    pub(crate) ids: Arc<dyn IdGenerator>,
+}
+
+impl Default for Generators {
+    fn default() -> Self {
+        Self {
+            clock: Arc::new(SystemClock),
+            ids: Arc::new(RandomIds),
+        }
+    }
+}
+
+impl PartialEq for Generators {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use super::{Clock, FixedClock, IdGenerator, RandomIds, SeededIds};
+
+    #[test]
+    fn test_fixed_clock() {
+        let clock = FixedClock::from_millis(1000).with_step(Duration::from_millis(5));
+
+        assert_eq!(clock.now(), UNIX_EPOCH + Duration::from_millis(1000));
+        assert_eq!(clock.now(), UNIX_EPOCH + Duration::from_millis(1005));
+    }
+
+    #[test]
+    fn test_seeded_ids() {
+        let ids: Vec<String> = (0..3).map(|_| SeededIds::new(7).folder_id()).collect();
+        assert_eq!(ids[0], ids[1]);
+
+        let seeded = SeededIds::new(7);
+        let (first, second) = (seeded.folder_id(), seeded.folder_id());
+        assert_eq!(first, ids[0]);
+        assert_ne!(first, second);
+
+        for id in [first, second, RandomIds.folder_id()] {
+            assert_eq!(id.len(), 16);
+            assert!(id
+                .chars()
+                .all(|c| c.is_ascii_hexdigit() && !c.is_uppercase()));
+        }
+    }
+}
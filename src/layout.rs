@@ -0,0 +1,369 @@
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::api::folders::{FolderRequest, RootList};
+use crate::backup::rebuild;
+use crate::entries::Entry;
+use crate::tree::{FolderNode, TreeNode};
+use crate::uri::{FolderId, SpotifyId, SpotifyUri};
+use crate::Result;
+
+/// Declarative folder layout of a root list, meant to be written by hand or exported with
+/// [`RootList::layout`] and shared between accounts
+///
+/// Folders are matched to the existing ones by `id` first and by their path second, so
+/// exported layouts rename folders when only the name is edited. Playlists are given as a
+/// uri, an `open.spotify.com` link or a base62 id, `name` is only there for the reader.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+pub struct Layout {
+    #[serde(default)]
+    pub items: Vec<LayoutItem>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum LayoutItem {
+    Folder(LayoutFolder),
+    Playlist(LayoutPlaylist),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct LayoutFolder {
+    pub folder: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<FolderId>,
+    #[serde(default)]
+    pub items: Vec<LayoutItem>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct LayoutPlaylist {
+    pub playlist: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+}
+
+impl Layout {
+    /// Builds the request that turns `current` into this layout.
+    ///
+    /// Folders that aren't part of the layout are ungrouped and playlists missing from
+    /// `current` are followed. Playlists that aren't part of the layout are kept after it,
+    /// unless `prune` is set, then they are unfollowed.
+    pub fn plan(&self, current: &RootList, prune: bool) -> Result<FolderRequest> {
+        let mut target = vec![];
+        let mut resolver = Resolver::new(current);
+        resolver.reserve_ids(&self.items);
+        resolver.resolve(&self.items, &[], &mut target)?;
+
+        let mut request = current.new_request();
+        let mut pruned = current.clone();
+        if prune {
+            let listed: HashSet<&str> = target.iter().map(String::as_str).collect();
+            for entry in current.entries().collect::<Vec<_>>().into_iter().rev() {
+                if matches!(entry.entry, Entry::Playlist { .. })
+                    && !listed.contains(entry.raw_uri())
+                {
+                    request.remove(entry.index as u32, 1);
+                }
+            }
+            pruned.apply_changes(&request.build())?;
+        }
+
+        let target: Vec<&str> = target.iter().map(String::as_str).collect();
+        request.extend(&rebuild(&pruned, &target, &[]));
+
+        Ok(request)
+    }
+}
+
+impl RootList {
+    /// The folders and playlists of the root list as a [`Layout`]
+    ///
+    /// Entries that aren't playlists, e.g. local files, are left out.
+    pub fn layout(&self) -> Layout {
+        Layout {
+            items: layout_items(&self.tree()),
+        }
+    }
+}
+
+fn layout_items(nodes: &[TreeNode]) -> Vec<LayoutItem> {
+    nodes
+        .iter()
+        .filter_map(|node| match node {
+            TreeNode::Folder(folder) => Some(LayoutItem::Folder(LayoutFolder {
+                folder: folder.name.clone(),
+                id: Some(folder.id.clone()),
+                items: layout_items(&folder.children),
+            })),
+            TreeNode::Playlist(item) => Some(LayoutItem::Playlist(LayoutPlaylist {
+                playlist: item.uri.clone(),
+                name: item.name.clone(),
+            })),
+            TreeNode::Other(_) => None,
+        })
+        .collect()
+}
+
+/// Turns layout items into root list uris, reusing the ids of existing folders
+struct Resolver<'a> {
+    current: &'a RootList,
+    /// Existing folder ids by their path
+    paths: HashMap<Vec<String>, Vec<FolderId>>,
+    ids: HashSet<FolderId>,
+    /// Existing ids the layout names explicitly, they aren't matched by path
+    reserved_ids: HashSet<FolderId>,
+    used_ids: HashSet<FolderId>,
+    playlists: HashSet<SpotifyId>,
+}
+
+impl<'a> Resolver<'a> {
+    fn new(current: &'a RootList) -> Self {
+        let mut resolver = Self {
+            current,
+            paths: HashMap::new(),
+            ids: HashSet::new(),
+            reserved_ids: HashSet::new(),
+            used_ids: HashSet::new(),
+            playlists: HashSet::new(),
+        };
+        resolver.collect_folders(&current.tree(), &[]);
+
+        resolver
+    }
+
+    fn collect_folders(&mut self, nodes: &[TreeNode], parent: &[String]) {
+        for node in nodes {
+            if let TreeNode::Folder(FolderNode {
+                id, name, children, ..
+            }) = node
+            {
+                let path = [parent, std::slice::from_ref(name)].concat();
+                self.paths.entry(path.clone()).or_default().push(id.clone());
+                self.ids.insert(id.clone());
+                self.collect_folders(children, &path);
+            }
+        }
+    }
+
+    fn reserve_ids(&mut self, items: &[LayoutItem]) {
+        for item in items {
+            if let LayoutItem::Folder(folder) = item {
+                if let Some(id) = folder.id.as_ref().filter(|id| self.ids.contains(*id)) {
+                    self.reserved_ids.insert(id.clone());
+                }
+                self.reserve_ids(&folder.items);
+            }
+        }
+    }
+
+    fn resolve(
+        &mut self,
+        items: &[LayoutItem],
+        parent: &[String],
+        target: &mut Vec<String>,
+    ) -> Result<()> {
+        for item in items {
+            match item {
+                LayoutItem::Folder(folder) => {
+                    if folder.folder.is_empty() || folder.folder.contains('/') {
+                        return Err(format!("invalid folder name {:?}", folder.folder).into());
+                    }
+
+                    let path = [parent, std::slice::from_ref(&folder.folder)].concat();
+                    let id = self.folder_id(folder.id.as_ref(), &path)?;
+                    target.push(
+                        SpotifyUri::StartGroup {
+                            id: id.clone(),
+                            name: folder.folder.clone(),
+                        }
+                        .to_string(),
+                    );
+                    self.resolve(&folder.items, &path, target)?;
+                    target.push(SpotifyUri::EndGroup { id }.to_string());
+                }
+                LayoutItem::Playlist(playlist) => {
+                    let id = parse_playlist(&playlist.playlist)?;
+                    if !self.playlists.insert(id) {
+                        return Err(format!("playlist {} is listed twice", id).into());
+                    }
+
+                    target.push(SpotifyUri::Playlist(id).to_string());
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn folder_id(&mut self, id: Option<&FolderId>, path: &[String]) -> Result<FolderId> {
+        if let Some(id) = id.filter(|id| self.ids.contains(*id)) {
+            if !self.used_ids.insert(id.clone()) {
+                return Err(format!("folder id {} is listed twice", id).into());
+            }
+
+            return Ok(id.clone());
+        }
+
+        let existing = self
+            .paths
+            .get(path)
+            .and_then(|ids| {
+                ids.iter()
+                    .find(|id| !self.used_ids.contains(*id) && !self.reserved_ids.contains(*id))
+            })
+            .cloned();
+        let id = match existing {
+            Some(id) => id,
+            None => self.current.generate_folder_id()?,
+        };
+        self.used_ids.insert(id.clone());
+
+        Ok(id)
+    }
+}
+
+/// Parses a playlist given as a uri, an `open.spotify.com` link or a base62 id
+fn parse_playlist(playlist: &str) -> Result<SpotifyId> {
+    let uri = playlist
+        .parse::<SpotifyUri>()
+        .or_else(|_| SpotifyUri::from_url(playlist));
+
+    match uri {
+        Ok(SpotifyUri::Playlist(id)) => Ok(id),
+        Ok(uri) => Err(format!("{} is not a playlist", uri).into()),
+        Err(_) => Ok(SpotifyId::from_base62(playlist)?),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::{Layout, LayoutFolder, LayoutItem, LayoutPlaylist};
+    use crate::api::folders::RootList;
+    use crate::generators::SeededIds;
+
+    const ROOT_LIST: &str = r#"{"revision":"AAAAELqqrKuzaoeUKYP7gEzCzrx3h0rD","contents":{"items":[{"uri":"spotify:start-group:aaaaaaaaaaaaaaaa:Work"},{"uri":"spotify:playlist:5aNzxEEkRE9MgNkiuXmpOR"},{"uri":"spotify:end-group:aaaaaaaaaaaaaaaa"},{"uri":"spotify:playlist:3FKTkhbClLGgKdPpbx3aHy"},{"uri":"spotify:local:artist:album:title:1"}],"metaItems":[{},{"attributes":{"name":"Deep"}},{},{"attributes":{"name":"Chill"}},{}]}}"#;
+
+    fn root_list() -> RootList {
+        let mut rl: RootList = serde_json::from_str(ROOT_LIST).expect("Couldn't parse rootlist");
+        rl.set_id_generator(Arc::new(SeededIds::new(1)));
+
+        rl
+    }
+
+    fn uris(root_list: &RootList) -> Vec<&str> {
+        root_list.entries().map(|entry| entry.raw_uri()).collect()
+    }
+
+    fn apply(layout: &Layout, prune: bool) -> RootList {
+        let mut rl = root_list();
+        let changes = layout.plan(&rl, prune).expect("failed to plan").build();
+        rl.apply_changes(&changes).expect("failed to apply changes");
+
+        rl
+    }
+
+    #[test]
+    fn test_export() {
+        let layout = root_list().layout();
+
+        assert_eq!(
+            layout.items,
+            vec![
+                LayoutItem::Folder(LayoutFolder {
+                    folder: "Work".to_string(),
+                    id: Some("aaaaaaaaaaaaaaaa".parse().unwrap()),
+                    items: vec![LayoutItem::Playlist(LayoutPlaylist {
+                        playlist: "spotify:playlist:5aNzxEEkRE9MgNkiuXmpOR".to_string(),
+                        name: Some("Deep".to_string()),
+                    })],
+                }),
+                LayoutItem::Playlist(LayoutPlaylist {
+                    playlist: "spotify:playlist:3FKTkhbClLGgKdPpbx3aHy".to_string(),
+                    name: Some("Chill".to_string()),
+                }),
+            ]
+        );
+
+        // Exporting and applying changes nothing
+        let rl = root_list();
+        assert_eq!(layout.plan(&rl, true).unwrap().build().ops().count(), 0);
+    }
+
+    #[test]
+    fn test_plan() {
+        let layout: Layout = serde_json::from_str(
+            r#"{"items":[
+                {"folder":"Work","items":[
+                    {"folder":"Evening","items":[{"playlist":"3FKTkhbClLGgKdPpbx3aHy"}]}
+                ]},
+                {"playlist":"https://open.spotify.com/playlist/37i9dQZF1DXcBWIGoYBM5M"}
+            ]}"#,
+        )
+        .expect("failed to parse layout");
+
+        let kept = apply(&layout, false);
+        let evening = uris(&kept)[1]
+            .strip_prefix("spotify:start-group:")
+            .and_then(|rest| rest.strip_suffix(":Evening"))
+            .expect("Evening is created")
+            .to_string();
+        assert_eq!(
+            uris(&kept),
+            vec![
+                "spotify:start-group:aaaaaaaaaaaaaaaa:Work".to_string(),
+                format!("spotify:start-group:{}:Evening", evening),
+                "spotify:playlist:3FKTkhbClLGgKdPpbx3aHy".to_string(),
+                format!("spotify:end-group:{}", evening),
+                "spotify:end-group:aaaaaaaaaaaaaaaa".to_string(),
+                "spotify:playlist:37i9dQZF1DXcBWIGoYBM5M".to_string(),
+                "spotify:playlist:5aNzxEEkRE9MgNkiuXmpOR".to_string(),
+                "spotify:local:artist:album:title:1".to_string(),
+            ]
+        );
+
+        let pruned = apply(&layout, true);
+        assert_eq!(pruned.len(), 7);
+        assert!(!uris(&pruned).contains(&"spotify:playlist:5aNzxEEkRE9MgNkiuXmpOR"));
+    }
+
+    #[test]
+    fn test_plan_rename() {
+        let mut layout = root_list().layout();
+        let LayoutItem::Folder(work) = &mut layout.items[0] else {
+            panic!("expected a folder");
+        };
+        work.folder = "Work Stuff".to_string();
+
+        let rl = apply(&layout, false);
+        assert_eq!(
+            uris(&rl)[..3],
+            [
+                "spotify:start-group:aaaaaaaaaaaaaaaa:Work Stuff",
+                "spotify:playlist:5aNzxEEkRE9MgNkiuXmpOR",
+                "spotify:end-group:aaaaaaaaaaaaaaaa"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_invalid_layout() {
+        let rl = root_list();
+
+        let twice: Layout = serde_json::from_str(
+            r#"{"items":[{"playlist":"3FKTkhbClLGgKdPpbx3aHy"},{"folder":"A","items":[{"playlist":"spotify:playlist:3FKTkhbClLGgKdPpbx3aHy"}]}]}"#,
+        )
+        .unwrap();
+        assert!(twice.plan(&rl, false).is_err());
+
+        let slash: Layout = serde_json::from_str(r#"{"items":[{"folder":"A/B"}]}"#).unwrap();
+        assert!(slash.plan(&rl, false).is_err());
+
+        assert!(serde_json::from_str::<Layout>(r#"{"items":[{"folder":"A","typo":1}]}"#).is_err());
+    }
+}
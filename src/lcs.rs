@@ -0,0 +1,148 @@
+//! Longest common subsequence of two lists, what diffs and restores keep in place
+//!
+//! The common prefix and suffix are matched directly, what is left in between is split with
+//! Hirschberg's algorithm, which needs memory linear in the length of the lists instead of a
+//! table of every pair of items.
+
+/// Matching `(left, right)` index pairs of a longest common subsequence, in order
+pub(crate) fn longest_common_subsequence<T: PartialEq>(
+    left: &[T],
+    right: &[T],
+) -> Vec<(usize, usize)> {
+    let prefix = left
+        .iter()
+        .zip(right)
+        .take_while(|(left, right)| left == right)
+        .count();
+    let suffix = left[prefix..]
+        .iter()
+        .rev()
+        .zip(right[prefix..].iter().rev())
+        .take_while(|(left, right)| left == right)
+        .count();
+
+    let mut pairs: Vec<(usize, usize)> = (0..prefix).map(|i| (i, i)).collect();
+    split(
+        &left[prefix..left.len() - suffix],
+        &right[prefix..right.len() - suffix],
+        (prefix, prefix),
+        &mut pairs,
+    );
+    pairs.extend((0..suffix).map(|i| (left.len() - suffix + i, right.len() - suffix + i)));
+
+    pairs
+}
+
+/// Pushes the pairs of `left` and `right`, which start at `offset` of the whole lists
+fn split<T: PartialEq>(
+    left: &[T],
+    right: &[T],
+    offset: (usize, usize),
+    pairs: &mut Vec<(usize, usize)>,
+) {
+    if left.is_empty() || right.is_empty() {
+        return;
+    }
+    if let [item] = left {
+        if let Some(j) = right.iter().position(|other| other == item) {
+            pairs.push((offset.0, offset.1 + j));
+        }
+        return;
+    }
+
+    // The first half of `left` is matched against `right[..at]` and the second half against
+    // `right[at..]`, where `at` keeps the most items in common
+    let middle = left.len() / 2;
+    let forward = lengths(left[..middle].iter(), right.iter());
+    let backward = lengths(left[middle..].iter().rev(), right.iter().rev());
+    let at = (0..=right.len())
+        .max_by_key(|&j| (forward[j] + backward[right.len() - j], std::cmp::Reverse(j)))
+        .unwrap_or_default();
+
+    split(&left[..middle], &right[..at], offset, pairs);
+    split(
+        &left[middle..],
+        &right[at..],
+        (offset.0 + middle, offset.1 + at),
+        pairs,
+    );
+}
+
+/// Length of the longest common subsequence of `left` and every prefix of `right`
+fn lengths<'a, T: PartialEq + 'a>(
+    left: impl Iterator<Item = &'a T>,
+    right: impl Iterator<Item = &'a T> + Clone,
+) -> Vec<usize> {
+    let mut row = vec![0; right.clone().count() + 1];
+
+    for item in left {
+        let mut diagonal = 0;
+        for (j, other) in right.clone().enumerate() {
+            let above = row[j + 1];
+            row[j + 1] = if item == other {
+                diagonal + 1
+            } else {
+                above.max(row[j])
+            };
+            diagonal = above;
+        }
+    }
+
+    row
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::collection::vec;
+    use proptest::prelude::*;
+
+    use super::longest_common_subsequence;
+
+    /// Length from the full table, what the linear space version has to match
+    fn table_length(left: &[u8], right: &[u8]) -> usize {
+        let mut lengths = vec![vec![0usize; right.len() + 1]; left.len() + 1];
+        for i in (0..left.len()).rev() {
+            for j in (0..right.len()).rev() {
+                lengths[i][j] = if left[i] == right[j] {
+                    lengths[i + 1][j + 1] + 1
+                } else {
+                    lengths[i + 1][j].max(lengths[i][j + 1])
+                };
+            }
+        }
+
+        lengths[0][0]
+    }
+
+    #[test]
+    fn test_longest_common_subsequence() {
+        assert_eq!(
+            longest_common_subsequence(&["a", "b", "c", "d"], &["a", "c", "b", "d"]),
+            vec![(0, 0), (2, 1), (3, 3)]
+        );
+        assert_eq!(
+            longest_common_subsequence(&["a", "b"], &["a", "b"]),
+            vec![(0, 0), (1, 1)]
+        );
+        assert_eq!(
+            longest_common_subsequence(&["x", "a", "y"], &["a"]),
+            vec![(1, 0)]
+        );
+        assert_eq!(longest_common_subsequence::<&str>(&[], &["a"]), vec![]);
+    }
+
+    proptest! {
+        #[test]
+        fn test_longest(left in vec(0u8..4, 0..24), right in vec(0u8..4, 0..24)) {
+            let pairs = longest_common_subsequence(&left, &right);
+
+            prop_assert_eq!(pairs.len(), table_length(&left, &right));
+            for window in pairs.windows(2) {
+                prop_assert!(window[0].0 < window[1].0 && window[0].1 < window[1].1);
+            }
+            for &(i, j) in &pairs {
+                prop_assert_eq!(left[i], right[j]);
+            }
+        }
+    }
+}
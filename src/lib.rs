@@ -22,7 +22,7 @@
 //!
 //!     let changes = root_list
 //!         .new_request()
-//!         .add("New Folder", &root_list.generate_folder_uri(), 0, 2)
+//!         .add("New Folder", &root_list.generate_folder_id().unwrap(), 0, 2).unwrap()
 //!         .build();
 //!
 //!     s.send_changes(&changes)
@@ -46,9 +46,90 @@
 //! - The last part of the link is your user id, e.g: `https://open.spotify.com/user/{user_id}`
 
 mod api;
+mod backup;
+mod diff;
+mod editor;
+mod entries;
+mod generators;
+mod layout;
+mod lcs;
+mod plan;
+#[cfg(test)]
+mod proptests;
 mod session;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+mod tree;
+mod uri;
 
 use std::error;
 
 pub type Session = session::Session;
+pub type Endpoints = session::Endpoints;
+pub type SessionTokens = session::SessionTokens;
+pub use session::is_revision_conflict;
+pub type WireFormat = session::WireFormat;
+pub type SpotifyUri = uri::SpotifyUri;
+pub type SpotifyId = uri::SpotifyId;
+pub type FolderId = uri::FolderId;
+pub type ParseUriError = uri::ParseUriError;
+pub type RootList = api::folders::RootList;
+pub type RootListMetaItem = api::folders::RootListMetaItem;
+pub type RootListEntry<'a> = entries::RootListEntry<'a>;
+pub type Entry<'a> = entries::Entry<'a>;
+pub type Entries<'a> = entries::Entries<'a>;
+pub type FolderRequest = api::folders::FolderRequest;
+pub type Changes = api::folders::Changes;
+pub type Delta = api::folders::Delta;
+pub type DeltaInfo = api::folders::DeltaInfo;
+pub type DeltaInfoSource = api::folders::DeltaInfoSource;
+pub type Operation = api::folders::Operation;
+pub type OperationItem = api::folders::OperationItem;
+pub type AddOperation = api::folders::AddOperation;
+pub type AddOperationParams = api::folders::AddOperationParams;
+pub type RemoveOperation = api::folders::RemoveOperation;
+pub type RemoveOperationParams = api::folders::RemoveOperationParams;
+pub type MoveOperation = api::folders::MoveOperation;
+pub type MoveOperationParams = api::folders::MoveOperationParams;
+pub type UpdateItemAttributesOperation = api::folders::UpdateItemAttributesOperation;
+pub type UpdateItemAttributesParams = api::folders::UpdateItemAttributesParams;
+pub type UpdateListAttributesOperation = api::folders::UpdateListAttributesOperation;
+pub type UpdateListAttributesParams = api::folders::UpdateListAttributesParams;
+pub type Playlist = api::playlist::Playlist;
+pub type PlaylistItem<'a> = api::playlist::PlaylistItem<'a>;
+pub type PlaylistRequest = api::playlist::PlaylistRequest;
+pub type PlaylistPlacement = api::playlist::PlaylistPlacement;
+pub type CreatedPlaylist = api::playlist::CreatedPlaylist;
+pub type PlaylistPlacementError = api::playlist::PlaylistPlacementError;
+pub type RootListSync = api::folders::RootListSync;
+pub type RootListEvent = api::dealer::RootListEvent;
+pub type RootListSubscription = api::dealer::RootListSubscription;
+pub use api::dealer::AccessTokenSource;
+pub type ItemAttributes = api::attributes::ItemAttributes;
+pub type ListAttributes = api::attributes::ListAttributes;
+pub type FormatAttribute = api::attributes::FormatAttribute;
+pub type FormatAttributes = api::attributes::FormatAttributes;
+pub type ListAttributesUpdate = api::attributes::ListAttributesUpdate;
+pub type ItemAttributesUpdate = api::attributes::ItemAttributesUpdate;
+pub type ListAttributeKind = api::attributes::ListAttributeKind;
+pub type ItemAttributeKind = api::attributes::ItemAttributeKind;
+pub type RootListQuery = api::query::RootListQuery;
+pub type Decoration = api::query::Decoration;
+pub type RootListBackup = backup::RootListBackup;
+pub use backup::BACKUP_VERSION;
+pub use generators::{Clock, FixedClock, IdGenerator, RandomIds, SeededIds, SystemClock};
+pub type RootListDiff = diff::RootListDiff;
+pub type Change = diff::Change;
+pub type FolderRef = diff::FolderRef;
+pub type ItemUri = diff::ItemUri;
+pub type TreeNode = tree::TreeNode;
+pub type FolderNode = tree::FolderNode;
+pub type ItemNode = tree::ItemNode;
+pub type RootListEditor = editor::RootListEditor;
+pub type Layout = layout::Layout;
+pub type LayoutItem = layout::LayoutItem;
+pub type LayoutFolder = layout::LayoutFolder;
+pub type LayoutPlaylist = layout::LayoutPlaylist;
+pub type Plan = plan::Plan;
+pub type PlanStep = plan::PlanStep;
 type Result<T> = std::result::Result<T, Box<dyn error::Error>>;
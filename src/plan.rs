@@ -0,0 +1,649 @@
+use std::fmt;
+
+use crate::api::folders::{
+    AddOperation, Changes, MoveOperation, Operation, RemoveOperation, RootList,
+    UpdateItemAttributesOperation,
+};
+use crate::entries::Entry;
+use crate::tree::TreeNode;
+use crate::uri::FolderId;
+use crate::Result;
+
+/// A single line of a [`Plan`]
+///
+/// `parent`, `to` and `folder` are the name of the folder the entry ends up in, `None` at
+/// the top level. Playlists without a name in the meta items are named by their uri.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PlanStep {
+    /// A folder was created around `entries` existing entries, `playlists` of them are
+    /// playlists
+    AddFolder {
+        name: String,
+        entries: usize,
+        playlists: usize,
+        parent: Option<String>,
+    },
+    Follow {
+        name: String,
+        parent: Option<String>,
+    },
+    /// An item that isn't a playlist or a folder was added
+    Add {
+        uri: String,
+        parent: Option<String>,
+    },
+    RenameFolder {
+        old_name: String,
+        new_name: String,
+    },
+    /// `entries` top level entries starting with `name` were moved into another folder
+    Move {
+        name: String,
+        entries: usize,
+        to: Option<String>,
+    },
+    /// `entries` top level entries starting with `name` were moved within `folder`
+    Reorder {
+        name: String,
+        entries: usize,
+        folder: Option<String>,
+    },
+    /// A folder was removed but its contents were kept
+    Ungroup {
+        name: String,
+    },
+    /// A folder was removed together with everything in it
+    RemoveFolder {
+        name: String,
+        playlists: usize,
+    },
+    Remove {
+        name: String,
+    },
+    UpdateItem {
+        name: String,
+    },
+    UpdateList,
+}
+
+/// Human readable description of what a [`Changes`] does to a root list, see
+/// [`RootList::plan`]
+///
+/// Unlike [`RootListDiff`](crate::RootListDiff) it follows the operations one by one, so
+/// every step names the entries as they are at that point.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Plan {
+    pub steps: Vec<PlanStep>,
+}
+
+impl Plan {
+    /// Fails if an operation doesn't apply to `root_list`
+    pub fn new(root_list: &RootList, changes: &Changes) -> Result<Self> {
+        let mut planner = Planner {
+            list: root_list.clone(),
+            steps: vec![],
+            added_starts: vec![],
+            removed_starts: vec![],
+            removed_ends: vec![],
+        };
+
+        for op in changes.ops() {
+            planner.step(op)?;
+        }
+
+        Ok(planner.finish())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+}
+
+impl RootList {
+    /// Describes what sending `changes` would do to this root list
+    pub fn plan(&self, changes: &Changes) -> Result<Plan> {
+        Plan::new(self, changes)
+    }
+}
+
+struct Planner {
+    /// The root list with the operations seen so far applied
+    list: RootList,
+    steps: Vec<PlanStep>,
+    /// Ids of folders whose start was added but not their end yet
+    added_starts: Vec<String>,
+    /// Folder starts removed while their end is still there, with the folder name.
+    /// A start added again with the same id renames the folder
+    removed_starts: Vec<(String, String)>,
+    /// Folder ends removed while their start is still there, with the folder name
+    removed_ends: Vec<(String, String)>,
+}
+
+impl Planner {
+    fn step(&mut self, op: &Operation) -> Result<()> {
+        match op {
+            Operation::Add(AddOperation { add, .. }) => {
+                let index = if add.add_first {
+                    0
+                } else if add.add_last {
+                    self.list.len()
+                } else {
+                    add.from_index as usize
+                };
+                self.apply(op)?;
+
+                for index in index..index + add.items.len() {
+                    self.added(index);
+                }
+            }
+            Operation::Rem(RemoveOperation { rem, .. }) if rem.items_as_key => {
+                let uris: Vec<&str> = rem
+                    .items
+                    .iter()
+                    .filter_map(|item| item.get("uri")?.as_str())
+                    .collect();
+                let mut removed: Vec<usize> = self
+                    .list
+                    .entries()
+                    .filter(|entry| uris.contains(&entry.raw_uri()))
+                    .map(|entry| entry.index)
+                    .collect();
+
+                // Back to front, so the indices of the ones left don't change
+                while let Some(index) = removed.pop() {
+                    self.removed(index, index + 1)?;
+                }
+            }
+            Operation::Rem(RemoveOperation { rem, .. }) => {
+                let from = rem.from_index as usize;
+                self.removed(from, from + rem.length as usize)?;
+            }
+            Operation::Mov(MoveOperation { mov, .. }) => {
+                let (from, len) = (mov.from_index as usize, mov.length as usize);
+                let to = mov.to_index as usize;
+                let (name, entries) = self.subject(from, len);
+                let old_parent = self.parent_of(from);
+                self.apply(op)?;
+
+                if (from..=from + len).contains(&to) {
+                    return Ok(());
+                }
+                let index = if to > from + len { to - len } else { to };
+                let parent = self.parent_of(index);
+
+                self.steps.push(match parent == old_parent {
+                    true => PlanStep::Reorder {
+                        name,
+                        entries,
+                        folder: parent,
+                    },
+                    false => PlanStep::Move {
+                        name,
+                        entries,
+                        to: parent,
+                    },
+                });
+            }
+            Operation::UpdateItemAttributes(UpdateItemAttributesOperation {
+                update_item_attributes,
+                ..
+            }) => {
+                let name = self.name_at(update_item_attributes.index as usize);
+                self.apply(op)?;
+
+                self.steps.push(PlanStep::UpdateItem { name });
+            }
+            Operation::UpdateListAttributes(_) => {
+                self.apply(op)?;
+
+                self.steps.push(PlanStep::UpdateList);
+            }
+            Operation::Unknown(_) => self.apply(op)?,
+        }
+
+        Ok(())
+    }
+
+    fn apply(&mut self, op: &Operation) -> Result<()> {
+        self.list.apply_ops(std::iter::once(op))
+    }
+
+    /// Describes the item just added at `index`
+    fn added(&mut self, index: usize) {
+        let Some(entry) = self.list.entries().nth(index) else {
+            return;
+        };
+
+        match entry.entry {
+            Entry::FolderStart { id, name } => {
+                if let Some(position) = self.removed_starts.iter().position(|(i, _)| i == id) {
+                    let (_, old_name) = self.removed_starts.remove(position);
+                    if old_name != name {
+                        self.steps.push(PlanStep::RenameFolder {
+                            old_name,
+                            new_name: name.to_string(),
+                        });
+                    }
+                } else {
+                    self.added_starts.push(id.to_string());
+                }
+            }
+            Entry::FolderEnd { id } => {
+                if let Some(position) = self.added_starts.iter().position(|i| i == id) {
+                    let id = self.added_starts.remove(position);
+                    self.push_folder(&id);
+                }
+            }
+            Entry::Playlist { .. } => self.steps.push(PlanStep::Follow {
+                name: self.name_at(index),
+                parent: self.parent_of(index),
+            }),
+            Entry::Unknown(uri) => self.steps.push(PlanStep::Add {
+                uri: uri.to_string(),
+                parent: self.parent_of(index),
+            }),
+        }
+    }
+
+    /// Describes and removes the items `from..end`
+    fn removed(&mut self, from: usize, end: usize) -> Result<()> {
+        let entries: Vec<(usize, String, Entry)> = self
+            .list
+            .entries()
+            .skip(from)
+            .take(end.saturating_sub(from))
+            .map(|entry| (entry.index, self.name_at(entry.index), entry.entry))
+            .collect();
+        let mut steps = vec![];
+        // Index of the end of the folder removed as a whole the entries are in
+        let mut inside: Option<usize> = None;
+
+        for (index, name, entry) in &entries {
+            if inside.is_some_and(|end| *index <= end) {
+                continue;
+            }
+
+            match entry {
+                Entry::FolderStart { id, .. } => match self.end_of(id, *index) {
+                    Some(folder_end) if folder_end < end => {
+                        let playlists = entries
+                            .iter()
+                            .filter(|(i, _, entry)| {
+                                (*index..folder_end).contains(i)
+                                    && matches!(entry, Entry::Playlist { .. })
+                            })
+                            .count();
+                        steps.push(PlanStep::RemoveFolder {
+                            name: name.clone(),
+                            playlists,
+                        });
+                        inside = Some(folder_end);
+                    }
+                    _ => match take(&mut self.removed_ends, id) {
+                        Some(name) => steps.push(PlanStep::Ungroup { name }),
+                        None => self.removed_starts.push((id.to_string(), name.clone())),
+                    },
+                },
+                Entry::FolderEnd { id } => match take(&mut self.removed_starts, id) {
+                    Some(name) => steps.push(PlanStep::Ungroup { name }),
+                    None => {
+                        let name = self.start_of(id, *index).unwrap_or(id).to_string();
+                        self.removed_ends.push((id.to_string(), name));
+                    }
+                },
+                Entry::Playlist { .. } | Entry::Unknown(_) => {
+                    steps.push(PlanStep::Remove { name: name.clone() })
+                }
+            }
+        }
+
+        self.list.apply_ops(std::iter::once(&Operation::remove(
+            from as u32,
+            (end - from) as u32,
+        )))?;
+        self.steps.extend(steps);
+
+        Ok(())
+    }
+
+    /// Folders left half added or half removed
+    fn finish(mut self) -> Plan {
+        for id in std::mem::take(&mut self.added_starts) {
+            self.push_folder(&id);
+        }
+        for (_, name) in self
+            .removed_starts
+            .drain(..)
+            .chain(self.removed_ends.drain(..))
+        {
+            self.steps.push(PlanStep::Ungroup { name });
+        }
+
+        Plan { steps: self.steps }
+    }
+
+    fn push_folder(&mut self, id: &str) {
+        let tree = self.list.tree();
+        let Some(folder) = find_folder(&tree, id) else {
+            return;
+        };
+        let playlists = folder
+            .iter()
+            .filter(|node| matches!(node, TreeNode::Playlist(_)))
+            .count();
+
+        let start = self
+            .list
+            .find_by_folder_id(&FolderId::from_checked(id))
+            .map(|entry| entry.index);
+        self.steps.push(PlanStep::AddFolder {
+            name: start.map(|index| self.name_at(index)).unwrap_or_default(),
+            entries: folder.len(),
+            playlists,
+            parent: start.and_then(|index| self.parent_of(index)),
+        });
+    }
+
+    /// Name of the item at `index`, the uri for items without one
+    fn name_at(&self, index: usize) -> String {
+        match self.list.entries().nth(index) {
+            Some(entry) => entry.entry.name().unwrap_or(entry.raw_uri()).to_string(),
+            None => format!("#{}", index),
+        }
+    }
+
+    /// Name of the folder the item at `index` is in
+    fn parent_of(&self, index: usize) -> Option<String> {
+        let mut open: Vec<(&str, &str)> = vec![];
+
+        for entry in self.list.entries().take(index) {
+            match entry.entry {
+                Entry::FolderStart { id, name } => open.push((id, name)),
+                Entry::FolderEnd { id } => {
+                    if let Some(position) = open.iter().rposition(|(i, _)| *i == id) {
+                        open.truncate(position);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        open.last().map(|(_, name)| name.to_string())
+    }
+
+    /// First item of `from..from + len` and the number of top level entries in it
+    fn subject(&self, from: usize, len: usize) -> (String, usize) {
+        let entries: Vec<_> = self.list.entries().skip(from).take(len).collect();
+        let depth = entries.first().map_or(0, |entry| entry.depth);
+        let count = entries
+            .iter()
+            .filter(|entry| entry.depth == depth && !matches!(entry.entry, Entry::FolderEnd { .. }))
+            .count();
+
+        (self.name_at(from), count.max(1))
+    }
+
+    fn end_of(&self, id: &str, start: usize) -> Option<usize> {
+        self.list
+            .entries()
+            .skip(start + 1)
+            .find(|entry| matches!(entry.entry, Entry::FolderEnd { id: end } if end == id))
+            .map(|entry| entry.index)
+    }
+
+    fn start_of(&self, id: &str, end: usize) -> Option<&str> {
+        self.list
+            .entries()
+            .take(end)
+            .filter_map(|entry| match entry.entry {
+                Entry::FolderStart { id: start, name } if start == id => Some(name),
+                _ => None,
+            })
+            .last()
+    }
+}
+
+/// Removes the folder `id` from `folders` and returns its name
+fn take(folders: &mut Vec<(String, String)>, id: &str) -> Option<String> {
+    let position = folders.iter().position(|(i, _)| i == id)?;
+
+    Some(folders.remove(position).1)
+}
+
+/// Children of the folder `id`
+fn find_folder<'a>(nodes: &'a [TreeNode], id: &str) -> Option<&'a [TreeNode]> {
+    nodes.iter().find_map(|node| match node {
+        TreeNode::Folder(folder) if folder.id.as_str() == id => Some(folder.children.as_slice()),
+        TreeNode::Folder(folder) => find_folder(&folder.children, id),
+        _ => None,
+    })
+}
+
+struct EntryCount {
+    count: usize,
+    playlists: bool,
+}
+
+impl fmt::Display for EntryCount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let noun = match self.playlists {
+            true => "playlist",
+            false => "entry",
+        };
+        match (self.count, self.playlists) {
+            (1, _) => write!(f, "1 {}", noun),
+            (count, true) => write!(f, "{} playlists", count),
+            (count, false) => write!(f, "{} entries", count),
+        }
+    }
+}
+
+struct Subject<'a> {
+    name: &'a str,
+    entries: usize,
+}
+
+impl fmt::Display for Subject<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self.name)?;
+        match self.entries {
+            0 | 1 => Ok(()),
+            2 => write!(f, " and 1 more"),
+            entries => write!(f, " and {} more", entries - 1),
+        }
+    }
+}
+
+impl fmt::Display for PlanStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PlanStep::AddFolder {
+                name,
+                entries,
+                playlists,
+                parent,
+            } => {
+                write!(f, "+ folder {:?}", name)?;
+                if *entries > 0 {
+                    let entries = EntryCount {
+                        count: *entries,
+                        playlists: playlists == entries,
+                    };
+                    write!(f, " around {}", entries)?;
+                }
+                match parent {
+                    Some(parent) => write!(f, " in {:?}", parent),
+                    None => Ok(()),
+                }
+            }
+            PlanStep::Follow { name, parent } => {
+                write!(f, "+ follow {:?}", name)?;
+                match parent {
+                    Some(parent) => write!(f, " in {:?}", parent),
+                    None => Ok(()),
+                }
+            }
+            PlanStep::Add { uri, parent } => {
+                write!(f, "+ add {:?}", uri)?;
+                match parent {
+                    Some(parent) => write!(f, " in {:?}", parent),
+                    None => Ok(()),
+                }
+            }
+            PlanStep::RenameFolder { old_name, new_name } => {
+                write!(f, "~ rename folder {:?} to {:?}", old_name, new_name)
+            }
+            PlanStep::Move { name, entries, to } => {
+                let subject = Subject {
+                    name,
+                    entries: *entries,
+                };
+                match to {
+                    Some(to) => write!(f, "~ move {} into {:?}", subject, to),
+                    None => write!(f, "~ move {} to the top level", subject),
+                }
+            }
+            PlanStep::Reorder {
+                name,
+                entries,
+                folder,
+            } => {
+                let subject = Subject {
+                    name,
+                    entries: *entries,
+                };
+                match folder {
+                    Some(folder) => write!(f, "~ move {} within {:?}", subject, folder),
+                    None => write!(f, "~ move {} within the top level", subject),
+                }
+            }
+            PlanStep::Ungroup { name } => write!(f, "- ungroup {:?}", name),
+            PlanStep::RemoveFolder { name, playlists } => {
+                write!(f, "- remove folder {:?}", name)?;
+                match playlists {
+                    0 => Ok(()),
+                    1 => write!(f, " with 1 playlist"),
+                    playlists => write!(f, " with {} playlists", playlists),
+                }
+            }
+            PlanStep::Remove { name } => write!(f, "- remove {:?}", name),
+            PlanStep::UpdateItem { name } => write!(f, "~ update {:?}", name),
+            PlanStep::UpdateList => write!(f, "~ update the list attributes"),
+        }
+    }
+}
+
+impl fmt::Display for Plan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.steps.is_empty() {
+            return writeln!(f, "no changes");
+        }
+
+        for step in &self.steps {
+            writeln!(f, "{}", step)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::api::folders::RootList;
+    use crate::editor::RootListEditor;
+
+    const ROOT_LIST: &str = r#"{"revision":"AAAA","contents":{"items":[{"uri":"spotify:start-group:aaaaaaaaaaaaaaaa:Evening"},{"uri":"spotify:playlist:5aNzxEEkRE9MgNkiuXmpOR"},{"uri":"spotify:end-group:aaaaaaaaaaaaaaaa"},{"uri":"spotify:playlist:3FKTkhbClLGgKdPpbx3aHy"},{"uri":"spotify:playlist:37i9dQZF1DXcBWIGoYBM5M"},{"uri":"spotify:playlist:4ZVR7tK0mFxuWpmYcsGqvR"},{"uri":"spotify:playlist:1B9o7mER9kfxbmsRH9ko4z"}],"metaItems":[{},{"attributes":{"name":"Dinner"}},{},{"attributes":{"name":"Chill"}},{"attributes":{"name":"Deep"}},{"attributes":{"name":"Flow"}},{"attributes":{"name":"Old Mix"}}]}}"#;
+
+    fn root_list() -> RootList {
+        serde_json::from_str(ROOT_LIST).expect("Couldn't parse rootlist")
+    }
+
+    fn lines(root_list: &RootList, editor: &RootListEditor) -> Vec<String> {
+        let plan = root_list
+            .plan(&editor.build())
+            .expect("failed to plan changes");
+
+        plan.to_string().lines().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn test_plan() {
+        let rl = root_list();
+        let mut editor = RootListEditor::new(&rl);
+        editor
+            .create_folder("Focus")
+            .unwrap()
+            .move_to("Deep", "Focus")
+            .unwrap()
+            .move_to("Flow", "Focus")
+            .unwrap()
+            .move_to("Chill", "Evening")
+            .unwrap()
+            .remove("Old Mix")
+            .unwrap();
+
+        assert_eq!(
+            lines(&rl, &editor),
+            [
+                r#"+ folder "Focus""#,
+                r#"~ move "Deep" into "Focus""#,
+                r#"~ move "Flow" into "Focus""#,
+                r#"~ move "Chill" into "Evening""#,
+                r#"- remove "Old Mix""#,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_folder_around() {
+        let rl = root_list();
+        let mut request = rl.new_request();
+        request
+            .add("Focus", &"bbbbbbbbbbbbbbbb".parse().unwrap(), 3, 7)
+            .unwrap();
+        let plan = rl.plan(&request.build()).expect("failed to plan changes");
+
+        assert_eq!(plan.to_string(), "+ folder \"Focus\" around 3 playlists\n");
+
+        let mut request = rl.new_request();
+        request
+            .add("Focus", &"bbbbbbbbbbbbbbbb".parse().unwrap(), 0, 5)
+            .unwrap();
+        let plan = rl.plan(&request.build()).expect("failed to plan changes");
+
+        assert_eq!(plan.to_string(), "+ folder \"Focus\" around 2 entries\n");
+    }
+
+    #[test]
+    fn test_folder_edits() {
+        let rl = root_list();
+        let mut editor = RootListEditor::new(&rl);
+        editor
+            .rename("Evening", "Night")
+            .unwrap()
+            .ungroup("Night")
+            .unwrap();
+
+        assert_eq!(
+            lines(&rl, &editor),
+            [
+                r#"~ rename folder "Evening" to "Night""#,
+                r#"- ungroup "Night""#,
+            ]
+        );
+
+        let mut request = rl.new_request();
+        request
+            .remove(0, 3)
+            .mov(3, 0, 1)
+            .add_playlist(&"2mAbfNeMQI46Vf1aZGJGKL".parse().unwrap(), 4);
+        let plan = rl.plan(&request.build()).expect("failed to plan changes");
+
+        assert_eq!(
+            plan.to_string(),
+            "- remove folder \"Evening\" with 1 playlist\n\
+             ~ move \"Old Mix\" within the top level\n\
+             + follow \"spotify:playlist:2mAbfNeMQI46Vf1aZGJGKL\"\n"
+        );
+    }
+}
@@ -0,0 +1,450 @@
+//! Property tests of [`FolderRequest`] edits against a tree model of the root list
+//!
+//! Root lists are built from random trees of folders and playlists, and random high level
+//! edits (create a folder around siblings, move, remove, ungroup) are applied both to the
+//! tree and, as ops, to the root list.
+use std::sync::Arc;
+
+use proptest::collection::vec;
+use proptest::prelude::*;
+use proptest::sample::Index;
+
+use crate::api::folders::{FolderRequest, OperationItem, RootList};
+use crate::entries::Entry;
+use crate::generators::{Clock, FixedClock};
+use crate::uri::{SpotifyId, SpotifyUri};
+
+const NOW: u64 = 1665582465479;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Playlist(u128),
+    Folder {
+        id: u64,
+        name: String,
+        children: Vec<Node>,
+    },
+}
+
+impl Node {
+    fn uris(&self, uris: &mut Vec<String>) {
+        match self {
+            Node::Playlist(id) => uris.push(playlist_uri(*id)),
+            Node::Folder { id, name, children } => {
+                uris.push(format!("spotify:start-group:{:016x}:{}", id, name));
+                for child in children {
+                    child.uris(uris);
+                }
+                uris.push(format!("spotify:end-group:{:016x}", id));
+            }
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Node::Playlist(_) => 1,
+            Node::Folder { children, .. } => 2 + children.iter().map(Node::len).sum::<usize>(),
+        }
+    }
+
+    /// Gives every folder and playlist a unique id
+    fn number(&mut self, next: &mut u64) {
+        *next += 1;
+        match self {
+            Node::Playlist(id) => *id = *next as u128,
+            Node::Folder { id, children, .. } => {
+                *id = *next;
+                for child in children {
+                    child.number(next);
+                }
+            }
+        }
+    }
+}
+
+fn playlist_uri(id: u128) -> String {
+    SpotifyUri::Playlist(SpotifyId::from_gid(id.to_be_bytes())).to_string()
+}
+
+/// Root list items, the model of a root list
+#[derive(Debug, Clone, PartialEq)]
+struct Tree(Vec<Node>);
+
+/// A node with the position of its items in the root list
+#[derive(Debug, Clone)]
+struct Located {
+    parent: Vec<usize>,
+    index: usize,
+    pos: usize,
+    len: usize,
+}
+
+/// A place a node can be inserted at, `pos` is the root list index in front of it
+#[derive(Debug, Clone)]
+struct Slot {
+    parent: Vec<usize>,
+    index: usize,
+    pos: usize,
+}
+
+impl Tree {
+    fn uris(&self) -> Vec<String> {
+        let mut uris = vec![];
+        for node in &self.0 {
+            node.uris(&mut uris);
+        }
+
+        uris
+    }
+
+    fn playlists(&self) -> Vec<String> {
+        let mut playlists: Vec<String> = self
+            .uris()
+            .into_iter()
+            .filter(|uri| uri.starts_with("spotify:playlist:"))
+            .collect();
+        playlists.sort();
+
+        playlists
+    }
+
+    fn children_mut(&mut self, parent: &[usize]) -> &mut Vec<Node> {
+        let mut children = &mut self.0;
+        for &index in parent {
+            let Node::Folder {
+                children: inner, ..
+            } = &mut children[index]
+            else {
+                unreachable!("parents are folders");
+            };
+            children = inner;
+        }
+
+        children
+    }
+
+    fn children_ref(&self, parent: &[usize]) -> &Vec<Node> {
+        let mut children = &self.0;
+        for &index in parent {
+            let Node::Folder {
+                children: inner, ..
+            } = &children[index]
+            else {
+                unreachable!("parents are folders");
+            };
+            children = inner;
+        }
+
+        children
+    }
+
+    /// Every node in root list order
+    fn nodes(&self) -> Vec<Located> {
+        fn walk(
+            children: &[Node],
+            parent: &mut Vec<usize>,
+            pos: &mut usize,
+            out: &mut Vec<Located>,
+        ) {
+            for (index, node) in children.iter().enumerate() {
+                out.push(Located {
+                    parent: parent.clone(),
+                    index,
+                    pos: *pos,
+                    len: node.len(),
+                });
+                match node {
+                    Node::Playlist(_) => *pos += 1,
+                    Node::Folder { children, .. } => {
+                        *pos += 1;
+                        parent.push(index);
+                        walk(children, parent, pos, out);
+                        parent.pop();
+                        *pos += 1;
+                    }
+                }
+            }
+        }
+
+        let mut nodes = vec![];
+        walk(&self.0, &mut vec![], &mut 0, &mut nodes);
+
+        nodes
+    }
+
+    fn slots(&self) -> Vec<Slot> {
+        let mut slots = vec![];
+        let mut add_slots = |parent: &[usize], children: &[Node], start: usize| {
+            let mut pos = start;
+            for index in 0..=children.len() {
+                slots.push(Slot {
+                    parent: parent.to_vec(),
+                    index,
+                    pos,
+                });
+                pos += children.get(index).map_or(0, Node::len);
+            }
+        };
+
+        add_slots(&[], &self.0, 0);
+        for node in self.nodes() {
+            let mut path = node.parent.clone();
+            path.push(node.index);
+            if let Node::Folder { children, .. } = &self.children_ref(&node.parent)[node.index] {
+                add_slots(&path, children, node.pos + 1);
+            }
+        }
+
+        slots
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Edit {
+    /// Puts `count` siblings, starting at a node, into a new folder
+    CreateFolder {
+        first: Index,
+        count: usize,
+    },
+    Move {
+        node: Index,
+        to: Index,
+    },
+    Remove {
+        node: Index,
+    },
+    Ungroup {
+        folder: Index,
+    },
+}
+
+/// Applies `edit` to `tree` and adds it to `request`, returns the request that undoes it or
+/// `None` when the edit doesn't apply to the tree
+fn apply_edit(
+    edit: &Edit,
+    tree: &mut Tree,
+    request: &mut FolderRequest,
+    clock: &Arc<FixedClock>,
+    next_id: &mut u64,
+) -> Option<FolderRequest> {
+    let nodes = tree.nodes();
+    let mut undo = FolderRequest::new("");
+    undo.set_clock(clock.clone());
+
+    match edit {
+        Edit::CreateFolder { first, count } => {
+            let node = first.get(&nodes);
+            let siblings = tree.children_mut(&node.parent);
+            let count = (*count).min(siblings.len() - node.index);
+            let children: Vec<Node> = siblings.drain(node.index..node.index + count).collect();
+            let len = children.iter().map(Node::len).sum::<usize>();
+
+            *next_id += 1;
+            let name = format!("Folder {}", next_id);
+            siblings.insert(
+                node.index,
+                Node::Folder {
+                    id: *next_id,
+                    name: name.clone(),
+                    children,
+                },
+            );
+
+            let start = node.pos as u32;
+            let end = start + len as u32;
+            request
+                .add(
+                    &name,
+                    &format!("{:016x}", next_id).parse().unwrap(),
+                    start,
+                    end + 1,
+                )
+                .unwrap();
+            undo.remove(end + 1, 1).remove(start, 1);
+        }
+        Edit::Move { node, to } => {
+            let node = node.get(&nodes);
+            let slots: Vec<Slot> = tree
+                .slots()
+                .into_iter()
+                .filter(|slot| slot.pos <= node.pos || slot.pos >= node.pos + node.len)
+                .collect();
+            let slot = to.get(&slots);
+
+            let moved = tree.children_mut(&node.parent).remove(node.index);
+            let mut index = slot.index;
+            if slot.parent == node.parent && slot.index > node.index {
+                index -= 1;
+            }
+            // Slots inside later siblings of the moved node shift with it
+            let mut parent = slot.parent.clone();
+            if parent.len() > node.parent.len()
+                && parent[..node.parent.len()] == node.parent[..]
+                && parent[node.parent.len()] > node.index
+            {
+                parent[node.parent.len()] -= 1;
+            }
+            tree.children_mut(&parent).insert(index, moved);
+
+            let (from, to, len) = (node.pos as u32, slot.pos as u32, node.len as u32);
+            request.mov(from, to, len);
+            if to > from {
+                undo.mov(to - len, from, len);
+            } else {
+                undo.mov(to, from + len, len);
+            }
+        }
+        Edit::Remove { node } => {
+            let node = node.get(&nodes);
+            let mut uris = vec![];
+            tree.children_mut(&node.parent)
+                .remove(node.index)
+                .uris(&mut uris);
+
+            request.remove(node.pos as u32, node.len as u32);
+            for (offset, uri) in uris.iter().enumerate() {
+                undo.add_item(
+                    OperationItem::new(uri, clock.now()),
+                    (node.pos + offset) as u32,
+                );
+            }
+        }
+        Edit::Ungroup { folder } => {
+            let folders: Vec<&Located> = nodes
+                .iter()
+                .filter(|node| {
+                    matches!(
+                        tree.children_ref(&node.parent)[node.index],
+                        Node::Folder { .. }
+                    )
+                })
+                .collect();
+            if folders.is_empty() {
+                return None;
+            }
+            let node = *folder.get(&folders);
+
+            let siblings = tree.children_mut(&node.parent);
+            let Node::Folder { id, name, children } = siblings.remove(node.index) else {
+                unreachable!("filtered to folders");
+            };
+            siblings.splice(node.index..node.index, children);
+
+            let (start, end) = (node.pos as u32, (node.pos + node.len - 1) as u32);
+            request.remove(end, 1).remove(start, 1);
+            undo.add(&name, &format!("{:016x}", id).parse().unwrap(), start, end)
+                .unwrap();
+        }
+    }
+
+    Some(undo)
+}
+
+fn root_list(tree: &Tree, clock: &Arc<FixedClock>) -> RootList {
+    let mut root_list: RootList = serde_json::from_str(
+        r#"{"revision":"AAAA","length":0,"contents":{"pos":0,"truncated":false,"items":[],"metaItems":[]}}"#,
+    )
+    .expect("Couldn't parse rootlist");
+    root_list.set_clock(clock.clone());
+
+    let mut request = root_list.new_request();
+    for (index, uri) in tree.uris().iter().enumerate() {
+        request.add_item(OperationItem::new(uri, clock.now()), index as u32);
+    }
+    root_list
+        .apply_changes(&request.build())
+        .expect("failed to build root list");
+
+    root_list
+}
+
+fn uris(root_list: &RootList) -> Vec<String> {
+    root_list
+        .entries()
+        .map(|entry| entry.raw_uri().to_string())
+        .collect()
+}
+
+fn is_balanced(root_list: &RootList) -> bool {
+    let mut open = vec![];
+    for entry in root_list.entries() {
+        match entry.entry {
+            Entry::FolderStart { id, .. } => open.push(id),
+            Entry::FolderEnd { id } if open.pop() != Some(id) => return false,
+            _ => {}
+        }
+    }
+
+    open.is_empty()
+}
+
+fn node() -> impl Strategy<Value = Node> {
+    Just(Node::Playlist(0)).prop_recursive(4, 32, 5, |inner| {
+        ("[A-Za-z0-9 ]{0,8}", vec(inner, 0..5)).prop_map(|(name, children)| Node::Folder {
+            id: 0,
+            name,
+            children,
+        })
+    })
+}
+
+fn tree() -> impl Strategy<Value = Tree> {
+    vec(node(), 1..8).prop_map(|mut nodes| {
+        let mut next = 0;
+        for node in &mut nodes {
+            node.number(&mut next);
+        }
+
+        Tree(nodes)
+    })
+}
+
+fn edit() -> impl Strategy<Value = Edit> {
+    prop_oneof![
+        (any::<Index>(), 0..4usize).prop_map(|(first, count)| Edit::CreateFolder { first, count }),
+        (any::<Index>(), any::<Index>()).prop_map(|(node, to)| Edit::Move { node, to }),
+        any::<Index>().prop_map(|node| Edit::Remove { node }),
+        any::<Index>().prop_map(|folder| Edit::Ungroup { folder }),
+    ]
+}
+
+proptest! {
+    #[test]
+    fn edits_match_model(tree in tree(), edits in vec(edit(), 1..8)) {
+        let clock = Arc::new(FixedClock::from_millis(NOW));
+        let mut model = tree.clone();
+        let mut root_list = root_list(&tree, &clock);
+        let mut batch = root_list.new_request();
+        let mut next_id = 1 << 32;
+
+        for edit in &edits {
+            if model.0.is_empty() {
+                break;
+            }
+
+            let before = root_list.clone();
+            let playlists = model.playlists();
+            apply_edit(edit, &mut model.clone(), &mut batch, &clock, &mut next_id.clone());
+            let mut request = root_list.new_request();
+            let Some(undo) = apply_edit(edit, &mut model, &mut request, &clock, &mut next_id) else {
+                continue;
+            };
+            root_list.apply_changes(&request.build()).expect("failed to apply edit");
+
+            prop_assert!(is_balanced(&root_list), "unbalanced after {:?}", edit);
+            prop_assert_eq!(uris(&root_list), model.uris());
+            if !matches!(edit, Edit::Remove { .. }) {
+                prop_assert_eq!(model.playlists(), playlists);
+            }
+
+            let mut undone = root_list.clone();
+            undone.apply_changes(&undo.build()).expect("failed to undo edit");
+            prop_assert_eq!(undone, before);
+        }
+
+        // The same edits sent in a single request
+        let mut batched = self::root_list(&tree, &clock);
+        batched.apply_changes(&batch.build()).expect("failed to apply edits");
+        prop_assert_eq!(uris(&batched), model.uris());
+    }
+}
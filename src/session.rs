@@ -1,72 +1,635 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+use serde_with::formats::Flexible;
+use serde_with::TimestampMilliSeconds;
+
 use crate::api;
+use crate::api::dealer::AccessTokenSource;
+use crate::api::folders::PagedList;
+use crate::generators::{Clock, Generators, IdGenerator};
+use crate::uri::{SpotifyId, SpotifyUri};
 use crate::Result;
 
+const ROOT_LIST_PAGE_SIZE: u32 = 500;
+const PLAYLIST_PAGE_SIZE: u32 = 100;
+/// How often a new playlist is added to the root list before giving up on revision conflicts
+const PLACEMENT_ATTEMPTS: usize = 3;
+
+/// Base urls of the spotify services a [`Session`] talks to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Endpoints {
+    pub open: String,
+    pub client_token: String,
+    pub spclient: String,
+    pub dealer: String,
+    /// The web api, where dealer connections are registered
+    pub api: String,
+}
+
+impl Default for Endpoints {
+    fn default() -> Self {
+        Self {
+            open: "https://open.spotify.com".to_string(),
+            client_token: "https://clienttoken.spotify.com".to_string(),
+            spclient: "https://spclient.wg.spotify.com".to_string(),
+            dealer: "wss://dealer.spotify.com".to_string(),
+            api: "https://api.spotify.com".to_string(),
+        }
+    }
+}
+
+/// How the root list, playlists and changes are encoded on the wire
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WireFormat {
+    #[default]
+    Json,
+    /// `application/x-protobuf`, what the spclient endpoints speak natively
+    #[cfg(feature = "protobuf")]
+    Protobuf,
+}
+
+impl WireFormat {
+    fn accept(self) -> &'static str {
+        match self {
+            WireFormat::Json => "application/json",
+            #[cfg(feature = "protobuf")]
+            WireFormat::Protobuf => api::protobuf::CONTENT_TYPE,
+        }
+    }
+
+    fn content_type(self) -> &'static str {
+        match self {
+            WireFormat::Json => "application/json;charset=UTF-8", // TODO: parse names in this charsets
+            #[cfg(feature = "protobuf")]
+            WireFormat::Protobuf => api::protobuf::CONTENT_TYPE,
+        }
+    }
+}
+
+/// The tokens a [`Session`] authenticates with, to reuse them until they expire
+#[serde_with::serde_as]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SessionTokens {
+    #[serde(rename = "accessToken")]
+    pub access_token: String,
+    #[serde(rename = "clientToken")]
+    pub client_token: String,
+    /// When the access token expires
+    #[serde_as(as = "TimestampMilliSeconds<String, Flexible>")]
+    #[serde(rename = "expiresAt")]
+    pub expires_at: SystemTime,
+}
+
+impl SessionTokens {
+    /// Whether the access token is still valid at `time`
+    pub fn is_valid_at(&self, time: SystemTime) -> bool {
+        time < self.expires_at
+    }
+}
+
+/// Hands out the access token until it expires, there is nothing to log in again with
+impl AccessTokenSource for SessionTokens {
+    fn access_token(&self) -> Pin<Box<dyn Future<Output = Result<String>> + Send + '_>> {
+        Box::pin(async move {
+            match self.is_valid_at(SystemTime::now()) {
+                true => Ok(self.access_token.clone()),
+                false => Err("the access token expired, create a new session".into()),
+            }
+        })
+    }
+}
+
+/// Logs in again with the cookies of a [`Session`] once its access token expires
+struct Login {
+    http_client: reqwest::Client,
+    open: String,
+    dc: String,
+    key: String,
+    /// The latest access token and when it expires
+    current: tokio::sync::Mutex<(String, SystemTime)>,
+}
+
+impl AccessTokenSource for Login {
+    fn access_token(&self) -> Pin<Box<dyn Future<Output = Result<String>> + Send + '_>> {
+        Box::pin(async move {
+            let mut current = self.current.lock().await;
+            if SystemTime::now() >= current.1 {
+                let res = api::session::get_access_token(
+                    &self.http_client,
+                    &self.open,
+                    &self.dc,
+                    &self.key,
+                )
+                .await?;
+                *current = (res.access_token, res.expiration_time);
+            }
+
+            Ok(current.0.clone())
+        })
+    }
+}
+
+impl std::fmt::Debug for Login {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Login")
+            .field("open", &self.open)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Whether `error`, returned by [`Session::send_changes`], means the changes were based on an
+/// outdated revision, i.e. the root list was changed somewhere else in the meantime
+pub fn is_revision_conflict(error: &(dyn std::error::Error + 'static)) -> bool {
+    error
+        .downcast_ref::<reqwest::Error>()
+        .and_then(reqwest::Error::status)
+        == Some(reqwest::StatusCode::CONFLICT)
+}
+
 #[derive(Debug)]
 pub struct Session {
     user_id: String,
 
     http_client: reqwest::Client,
+    endpoints: Endpoints,
+    wire_format: WireFormat,
 
     access_token: String,
     client_token: String,
+    expires_at: SystemTime,
+
+    /// Access tokens for the dealer connections, which outlive the session's own token
+    access_tokens: Arc<dyn AccessTokenSource>,
+
+    generators: Generators,
+    dry_run: bool,
 }
 
 impl Session {
     /// Creates a new session for spotify private api
     ///
     pub async fn new(dc: &str, key: &str, user_id: &str) -> Result<Self> {
+        Self::with_endpoints(dc, key, user_id, Endpoints::default()).await
+    }
+
+    /// Creates a new session that talks to `endpoints` instead of the spotify servers
+    pub async fn with_endpoints(
+        dc: &str,
+        key: &str,
+        user_id: &str,
+        endpoints: Endpoints,
+    ) -> Result<Self> {
         let http_client = reqwest::Client::new();
-        let access_token_resp = api::session::get_access_token(&http_client, dc, key).await?;
-        let client_token =
-            api::session::get_client_token(&http_client, &access_token_resp.client_id).await?;
+        let access_token_resp =
+            api::session::get_access_token(&http_client, &endpoints.open, dc, key).await?;
+        let client_token = api::session::get_client_token(
+            &http_client,
+            &endpoints.client_token,
+            &access_token_resp.client_id,
+        )
+        .await?;
+
+        let access_tokens = Arc::new(Login {
+            http_client: http_client.clone(),
+            open: endpoints.open.clone(),
+            dc: dc.to_string(),
+            key: key.to_string(),
+            current: tokio::sync::Mutex::new((
+                access_token_resp.access_token.clone(),
+                access_token_resp.expiration_time,
+            )),
+        });
 
         Ok(Self {
             user_id: user_id.to_string(),
             http_client,
+            endpoints,
+            wire_format: WireFormat::default(),
             access_token: access_token_resp.access_token,
             client_token,
+            expires_at: access_token_resp.expiration_time,
+            access_tokens,
+            generators: Generators::default(),
+            dry_run: false,
         })
     }
 
+    /// Creates a session from the tokens of an earlier one, without logging in again
+    pub fn from_tokens(user_id: &str, tokens: &SessionTokens, endpoints: Endpoints) -> Self {
+        Self {
+            user_id: user_id.to_string(),
+            http_client: reqwest::Client::new(),
+            endpoints,
+            wire_format: WireFormat::default(),
+            access_token: tokens.access_token.clone(),
+            client_token: tokens.client_token.clone(),
+            expires_at: tokens.expires_at,
+            access_tokens: Arc::new(tokens.clone()),
+            generators: Generators::default(),
+            dry_run: false,
+        }
+    }
+
+    /// The tokens the session authenticates with, see [`Session::from_tokens`]
+    pub fn tokens(&self) -> SessionTokens {
+        SessionTokens {
+            access_token: self.access_token.clone(),
+            client_token: self.client_token.clone(),
+            expires_at: self.expires_at,
+        }
+    }
+
+    pub fn user_id(&self) -> &str {
+        &self.user_id
+    }
+
+    /// Encoding used for the root list, playlists and changes, JSON by default
+    pub fn set_wire_format(&mut self, wire_format: WireFormat) {
+        self.wire_format = wire_format;
+    }
+
+    /// Clock the fetched root lists and playlists stamp added items with
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.generators.clock = clock;
+    }
+
+    /// Id generator the fetched root lists create folder ids with
+    pub fn set_id_generator(&mut self, ids: Arc<dyn IdGenerator>) {
+        self.generators.ids = ids;
+    }
+
+    /// In dry run mode [`Session::send_changes`] logs the [`Plan`](crate::Plan) of the
+    /// changes against the current root list instead of sending them, playlist changes are
+    /// skipped as well and creating playlists fails
+    ///
+    /// The plan goes to the [`log`] facade at info level, it is up to the application to
+    /// install a logger and decide where it ends up.
+    pub fn set_dry_run(&mut self, dry_run: bool) {
+        self.dry_run = dry_run;
+    }
+
+    /// Fetches the whole root list, paging through it when the server truncates it
     pub async fn get_root_list(&self) -> Result<api::folders::RootList> {
-        let res = self.http_client
-            .get(format!("https://spclient.wg.spotify.com/playlist/v2/user/{}/rootlist?decorate=revision%2Clength%2Cattributes%2Ctimestamp%2Cowner", self.user_id))
-            .header("Accept", "application/json")
+        self.get_root_list_with(&api::query::RootListQuery::default())
+            .await
+    }
+
+    /// Fetches the part of the root list selected by `query`
+    pub async fn get_root_list_with(
+        &self,
+        query: &api::query::RootListQuery,
+    ) -> Result<api::folders::RootList> {
+        let mut root_list: api::folders::RootList = self
+            .get_list(
+                &format!("playlist/v2/user/{}/rootlist", self.user_id),
+                query,
+                ROOT_LIST_PAGE_SIZE,
+            )
+            .await?;
+
+        // In case the server sends meta items without being asked for them
+        if !query.meta_items {
+            root_list.contents.meta_items.clear();
+        }
+        root_list.generators = self.generators.clone();
+
+        Ok(root_list)
+    }
+
+    /// Fetches a playlist with all of its items, paging through long playlists
+    pub async fn get_playlist(&self, id: &SpotifyId) -> Result<api::playlist::Playlist> {
+        let mut playlist: api::playlist::Playlist = self
+            .get_list(
+                &format!("playlist/v2/playlist/{}", id),
+                &api::query::RootListQuery::default(),
+                PLAYLIST_PAGE_SIZE,
+            )
+            .await?;
+        playlist.generators = self.generators.clone();
+
+        Ok(playlist)
+    }
+
+    /// Fetches the pages of the list at `path` that `query` selects and merges them
+    async fn get_list<T: PagedList>(
+        &self,
+        path: &str,
+        query: &api::query::RootListQuery,
+        page_size: u32,
+    ) -> Result<T> {
+        // Index after the last selected item, lengths past the end of the list are cut off
+        let end = query.length.map(|length| query.from.saturating_add(length));
+        let page_length = |from: u32| match end {
+            Some(end) => page_size.min(end.saturating_sub(from)),
+            None => page_size,
+        };
+
+        let mut list: T = self
+            .get_list_page(path, query, query.from, page_length(query.from))
+            .await?;
+
+        while let Some(from) = list.next_page_index() {
+            if end.is_some_and(|end| from >= end) {
+                break;
+            }
+
+            let page = self
+                .get_list_page(path, query, from, page_length(from))
+                .await?;
+            list.merge_page(page)?;
+        }
+
+        Ok(list)
+    }
+
+    async fn get_list_page<T: PagedList>(
+        &self,
+        path: &str,
+        query: &api::query::RootListQuery,
+        from: u32,
+        length: u32,
+    ) -> Result<T> {
+        let res = self
+            .http_client
+            .get(format!(
+                "{}/{}?{}",
+                self.endpoints.spclient,
+                path,
+                query.to_query_string(from, length)
+            ))
+            .header("Accept", self.wire_format.accept())
             .header("app-platform", "WebPlayer")
             .header("authorization", format!("Bearer {}", self.access_token))
             .header("client-token", &self.client_token)
             .send()
             .await?
-            .text()
+            .error_for_status()?
+            .bytes()
             .await?;
 
-        Ok(serde_json::from_str(&res)?)
+        match self.wire_format {
+            WireFormat::Json => Ok(serde_json::from_slice(&res)?),
+            #[cfg(feature = "protobuf")]
+            WireFormat::Protobuf => T::from_protobuf(&res),
+        }
     }
 
-    pub async fn send_changes(&self, changes: &api::folders::Changes) -> Result<()> {
-        self.http_client
-            .post(format!(
-                "https://spclient.wg.spotify.com/playlist/v2/user/{}/rootlist/changes",
-                self.user_id
+    /// Fetches the operations that were applied to the root list since `since_revision`,
+    /// see [`RootListSync::apply`](api::folders::RootListSync::apply)
+    pub async fn get_root_list_diff(
+        &self,
+        since_revision: &str,
+    ) -> Result<api::folders::RootListSync> {
+        let res = self
+            .http_client
+            .get(format!(
+                "{}/playlist/v2/user/{}/rootlist/diff",
+                self.endpoints.spclient, self.user_id
             ))
+            .query(&[("revision", since_revision), ("handlesContent", "")])
             .header("Accept", "application/json")
             .header("app-platform", "WebPlayer")
             .header("authorization", format!("Bearer {}", self.access_token))
             .header("client-token", &self.client_token)
-            .header("content-type", "application/json;charset=UTF-8") // TODO: parse names in this charsets
-            .body(serde_json::to_string(changes)?)
             .send()
             .await?
-            .error_for_status()?;
+            .error_for_status()?
+            .text()
+            .await?;
+
+        let res: api::folders::RootListDiffResponse = serde_json::from_str(&res)?;
+
+        res.diff.ok_or_else(|| {
+            format!(
+                "no diff since revision {}, fetch the whole root list instead",
+                since_revision
+            )
+            .into()
+        })
+    }
+
+    /// Subscribes to root list changes made by other devices through the dealer websocket
+    ///
+    /// Reconnects fetch a new access token once the current one expired. That needs the cookies
+    /// of [`Session::new`], subscriptions of sessions created with [`Session::from_tokens`]
+    /// stop receiving events when their tokens expire.
+    pub fn subscribe_rootlist(&self) -> api::dealer::RootListSubscription {
+        self.subscribe_rootlist_with(self.access_tokens.clone())
+    }
+
+    /// Like [`Session::subscribe_rootlist`], with the access tokens of the connections taken
+    /// from `access_tokens`
+    pub fn subscribe_rootlist_with(
+        &self,
+        access_tokens: Arc<dyn AccessTokenSource>,
+    ) -> api::dealer::RootListSubscription {
+        api::dealer::subscribe_rootlist(
+            api::dealer::DealerEndpoints {
+                dealer: self.endpoints.dealer.clone(),
+                notifications: format!("{}/v1/me/notifications/user", self.endpoints.api),
+            },
+            self.http_client.clone(),
+            access_tokens,
+            &self.user_id,
+            api::dealer::PING_INTERVAL,
+        )
+    }
+
+    pub async fn send_changes(&self, changes: &api::folders::Changes) -> Result<()> {
+        if self.dry_run {
+            let root_list = self.get_root_list().await?;
+            let plan = root_list.plan(changes)?;
+            log::info!("dry run, not sending to the root list:\n{}", plan);
+
+            return Ok(());
+        }
+
+        self.post_changes(
+            &format!("playlist/v2/user/{}/rootlist/changes", self.user_id),
+            changes,
+        )
+        .await?;
 
         Ok(())
     }
+
+    /// Sends `changes` to the root list and returns its new revision
+    async fn send_root_list_changes(&self, changes: &api::folders::Changes) -> Result<String> {
+        let res = self
+            .post_changes(
+                &format!("playlist/v2/user/{}/rootlist/changes", self.user_id),
+                changes,
+            )
+            .await?;
+
+        match self.wire_format {
+            WireFormat::Json => {
+                let res: api::folders::ChangesResponse = serde_json::from_slice(&res)?;
+
+                Ok(res.revision)
+            }
+            #[cfg(feature = "protobuf")]
+            WireFormat::Protobuf => api::protobuf::decode_revision(&res),
+        }
+    }
+
+    /// Sends changes built with a [`PlaylistRequest`](api::playlist::PlaylistRequest) to the
+    /// playlist `id`
+    pub async fn send_playlist_changes(
+        &self,
+        id: &SpotifyId,
+        changes: &api::folders::Changes,
+    ) -> Result<()> {
+        if self.dry_run {
+            log::info!(
+                "dry run, not sending {} operations to playlist {}",
+                changes.ops().count(),
+                id
+            );
+
+            return Ok(());
+        }
+
+        self.post_changes(&format!("playlist/v2/playlist/{}/changes", id), changes)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn post_changes(&self, path: &str, changes: &api::folders::Changes) -> Result<Vec<u8>> {
+        let body = match self.wire_format {
+            WireFormat::Json => serde_json::to_vec(changes)?,
+            #[cfg(feature = "protobuf")]
+            WireFormat::Protobuf => api::protobuf::encode_changes(changes)?,
+        };
+
+        // Separate statements, so the futures stay `Send`
+        let res = self.post(path, self.wire_format, body).await?;
+
+        Ok(res.bytes().await?.to_vec())
+    }
+
+    async fn post(
+        &self,
+        path: &str,
+        wire_format: WireFormat,
+        body: Vec<u8>,
+    ) -> Result<reqwest::Response> {
+        Ok(self
+            .http_client
+            .post(format!("{}/{}", self.endpoints.spclient, path))
+            .header("Accept", wire_format.accept())
+            .header("app-platform", "WebPlayer")
+            .header("authorization", format!("Bearer {}", self.access_token))
+            .header("client-token", &self.client_token)
+            .header("content-type", wire_format.content_type())
+            .body(body)
+            .send()
+            .await?
+            .error_for_status()?)
+    }
+
+    /// Creates a playlist and adds it to the top of the root list
+    pub async fn create_playlist(
+        &self,
+        name: &str,
+        description: &str,
+        public: bool,
+        collaborative: bool,
+    ) -> Result<api::playlist::CreatedPlaylist> {
+        self.create_playlist_at(
+            name,
+            description,
+            public,
+            collaborative,
+            &api::playlist::PlaylistPlacement::Index(0),
+        )
+        .await
+    }
+
+    /// Creates a playlist and adds it to the root list at `placement`
+    ///
+    /// The placement is resolved before the playlist is created and retried when the root
+    /// list changes in between. If the playlist is created but can't be added, the error is a
+    /// [`PlaylistPlacementError`](crate::PlaylistPlacementError) with the playlist's uri.
+    pub async fn create_playlist_at(
+        &self,
+        name: &str,
+        description: &str,
+        public: bool,
+        collaborative: bool,
+        placement: &api::playlist::PlaylistPlacement,
+    ) -> Result<api::playlist::CreatedPlaylist> {
+        if self.dry_run {
+            return Err("can't create playlists in dry run mode".into());
+        }
+
+        let root_list = self
+            .get_root_list_with(api::query::RootListQuery::new().meta_items(false))
+            .await?;
+        placement.index(&root_list)?;
+
+        let body = serde_json::to_vec(&api::playlist::CreatePlaylist::new(
+            name,
+            description,
+            collaborative,
+        ))?;
+        let res = self
+            .post("playlist/v2/playlist", WireFormat::Json, body)
+            .await?;
+        let res = res.text().await?;
+        let res: api::playlist::CreatePlaylistResponse = serde_json::from_str(&res)?;
+
+        match self
+            .place_playlist(&res.uri, public, placement, root_list)
+            .await
+        {
+            Ok(root_list_revision) => Ok(api::playlist::CreatedPlaylist {
+                uri: res.uri,
+                root_list_revision,
+            }),
+            Err(e) => Err(api::playlist::PlaylistPlacementError::new(res.uri, e).into()),
+        }
+    }
+
+    /// Adds the playlist `uri` to the root list, refetching it after a revision conflict
+    async fn place_playlist(
+        &self,
+        uri: &SpotifyUri,
+        public: bool,
+        placement: &api::playlist::PlaylistPlacement,
+        mut root_list: api::folders::RootList,
+    ) -> Result<String> {
+        let mut attempt = 1;
+        loop {
+            let mut request = root_list.new_request();
+            let item = api::folders::OperationItem::from_uri(uri, request.now()).public(public);
+            let changes = request.add_item(item, placement.index(&root_list)?).build();
+
+            match self.send_root_list_changes(&changes).await {
+                Ok(revision) => return Ok(revision),
+                Err(e) if attempt < PLACEMENT_ATTEMPTS && is_revision_conflict(e.as_ref()) => {}
+                Err(e) => return Err(e),
+            }
+
+            attempt += 1;
+            root_list = self
+                .get_root_list_with(api::query::RootListQuery::new().meta_items(false))
+                .await?;
+        }
+    }
 }
 
 // TODO: Write a system test framework to work with a real spotify connection
 #[cfg(test)]
 mod tests {
-    use crate::session::Session;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use crate::session::{Endpoints, Session, SessionTokens};
 
     async fn session_from_env() -> Session {
         let dc = std::env::var("SPOTIFY_DC").expect("failed to get SPOTIFY_DC from ENV");
@@ -98,11 +661,385 @@ mod tests {
         let root_list = s.get_root_list().await.expect("failed to get root list");
         let changes = root_list
             .new_request()
-            .add("TestFolder", &root_list.generate_folder_uri(), 0, 2)
+            .add("TestFolder", &root_list.generate_folder_id().unwrap(), 0, 2)
+            .unwrap()
             .build();
 
         s.send_changes(&changes)
             .await
             .expect("failed to send changes");
     }
+
+    fn local_session(url: &str) -> Session {
+        Session::from_tokens(
+            "user",
+            &SessionTokens {
+                access_token: "access-token".to_string(),
+                client_token: "client-token".to_string(),
+                expires_at: UNIX_EPOCH,
+            },
+            Endpoints {
+                open: url.to_string(),
+                client_token: url.to_string(),
+                spclient: url.to_string(),
+                dealer: url.to_string(),
+                api: url.to_string(),
+            },
+        )
+    }
+
+    /// Sessions are used from multi threaded servers, where every future has to be `Send`
+    #[test]
+    fn test_futures_are_send() {
+        fn assert_send<T: Send>(_: T) {}
+
+        let tokens = SessionTokens {
+            access_token: "access-token".to_string(),
+            client_token: "client-token".to_string(),
+            expires_at: UNIX_EPOCH,
+        };
+        let session = Session::from_tokens("user", &tokens, Endpoints::default());
+        let id = "5aNzxEEkRE9MgNkiuXmpOR".parse().unwrap();
+        let changes = crate::api::folders::Changes::default();
+
+        assert_send(Session::new("dc", "key", "user"));
+        assert_send(session.get_root_list());
+        assert_send(session.get_playlist(&id));
+        assert_send(session.get_root_list_diff("AAAA"));
+        assert_send(session.send_changes(&changes));
+        assert_send(session.send_playlist_changes(&id, &changes));
+        assert_send(session.create_playlist("name", "", false, false));
+    }
+
+    #[test]
+    fn test_tokens() {
+        let tokens = SessionTokens {
+            access_token: "access-token".to_string(),
+            client_token: "client-token".to_string(),
+            expires_at: UNIX_EPOCH + Duration::from_millis(1665495078416),
+        };
+
+        let json = serde_json::to_string(&tokens).expect("failed to serialize tokens");
+        assert_eq!(
+            json,
+            r#"{"accessToken":"access-token","clientToken":"client-token","expiresAt":"1665495078416"}"#
+        );
+        let parsed: SessionTokens = serde_json::from_str(&json).expect("failed to parse tokens");
+        assert_eq!(parsed, tokens);
+
+        let session = Session::from_tokens("user", &tokens, Endpoints::default());
+        assert_eq!(session.tokens(), tokens);
+        assert!(tokens.is_valid_at(UNIX_EPOCH));
+        assert!(!tokens.is_valid_at(tokens.expires_at));
+    }
+
+    #[tokio::test]
+    async fn test_root_list_diff() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("GET", "/playlist/v2/user/user/rootlist/diff")
+            .match_query(mockito::Matcher::UrlEncoded(
+                "revision".to_string(),
+                "AAAAAQ/+rev".to_string(),
+            ))
+            .match_header("authorization", "Bearer access-token")
+            .with_body(r#"{"revision":"AAAAAg/+rev","diff":{"fromRevision":"AAAAAQ/+rev","toRevision":"AAAAAg/+rev","ops":[{"kind":"MOV","mov":{"fromIndex":1,"toIndex":0,"length":1}},{"kind":"ADD","add":{"fromIndex":2,"items":[{"uri":"spotify:playlist:3","attributes":{"addedBy":"","timestamp":"1665495078416","seenAt":"0","public":false,"formatAttributes":[]}}]}}]}}"#)
+            .create_async()
+            .await;
+
+        let mut root_list: crate::api::folders::RootList = serde_json::from_str(r#"{"revision":"AAAAAQ/+rev","length":2,"contents":{"pos":0,"truncated":false,"items":[{"uri":"spotify:playlist:1"},{"uri":"spotify:playlist:2"}],"metaItems":[{},{}]}}"#).expect("Couldn't parse rootlist");
+
+        let sync = local_session(&server.url())
+            .get_root_list_diff("AAAAAQ/+rev")
+            .await
+            .expect("failed to get root list diff");
+        sync.apply(&mut root_list).expect("failed to apply diff");
+
+        mock.assert_async().await;
+        assert_eq!(root_list.revision, "AAAAAg/+rev");
+        assert_eq!(
+            root_list
+                .contents
+                .items
+                .iter()
+                .map(|x| x.uri.as_str())
+                .collect::<Vec<_>>(),
+            vec![
+                "spotify:playlist:2",
+                "spotify:playlist:1",
+                "spotify:playlist:3"
+            ]
+        );
+
+        assert!(sync.apply(&mut root_list).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_root_list_diff_unavailable() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/playlist/v2/user/user/rootlist/diff")
+            .match_query(mockito::Matcher::Any)
+            .with_body(r#"{"revision":"AAAAAg/+rev"}"#)
+            .create_async()
+            .await;
+
+        let res = local_session(&server.url())
+            .get_root_list_diff("AAAAAQ/+rev")
+            .await;
+
+        assert!(res.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_get_playlist() {
+        let mut server = mockito::Server::new_async().await;
+        let first = server
+            .mock("GET", "/playlist/v2/playlist/5aNzxEEkRE9MgNkiuXmpOR")
+            .match_query(mockito::Matcher::UrlEncoded(
+                "from".to_string(),
+                "0".to_string(),
+            ))
+            .match_header("authorization", "Bearer access-token")
+            .with_body(r#"{"revision":"AAAAAQ/+rev","length":2,"attributes":{"name":"Focus"},"contents":{"pos":0,"truncated":true,"items":[{"uri":"spotify:track:5aNzxEEkRE9MgNkiuXmpOR","attributes":{"addedBy":"user","timestamp":"1665495078416"}}]}}"#)
+            .create_async()
+            .await;
+        let second = server
+            .mock("GET", "/playlist/v2/playlist/5aNzxEEkRE9MgNkiuXmpOR")
+            .match_query(mockito::Matcher::UrlEncoded(
+                "from".to_string(),
+                "1".to_string(),
+            ))
+            .with_body(r#"{"revision":"AAAAAQ/+rev","contents":{"pos":1,"truncated":false,"items":[{"uri":"spotify:episode:7N42dgm5tFLK9N8MT7fHC7","attributes":{"addedBy":"friend","timestamp":"1665495079000"}}]}}"#)
+            .create_async()
+            .await;
+
+        let playlist = local_session(&server.url())
+            .get_playlist(&"5aNzxEEkRE9MgNkiuXmpOR".parse().unwrap())
+            .await
+            .expect("failed to get playlist");
+
+        first.assert_async().await;
+        second.assert_async().await;
+        assert_eq!(playlist.revision, "AAAAAQ/+rev");
+        assert_eq!(playlist.name(), Some("Focus"));
+        assert_eq!(
+            playlist
+                .items()
+                .map(|item| (item.uri, item.added_by()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("spotify:track:5aNzxEEkRE9MgNkiuXmpOR", Some("user")),
+                ("spotify:episode:7N42dgm5tFLK9N8MT7fHC7", Some("friend"))
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_send_playlist_changes() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/playlist/v2/playlist/5aNzxEEkRE9MgNkiuXmpOR/changes")
+            .match_header("authorization", "Bearer access-token")
+            .match_body(mockito::Matcher::PartialJsonString(
+                r#"{"baseRevision":"AAAAAQ/+rev","deltas":[{"ops":[{"kind":"MOV","mov":{"fromIndex":2,"toIndex":0,"length":1}}]}]}"#.to_string(),
+            ))
+            .create_async()
+            .await;
+
+        let changes = crate::api::playlist::PlaylistRequest::new("AAAAAQ/+rev")
+            .mov(2, 0, 1)
+            .build();
+        local_session(&server.url())
+            .send_playlist_changes(&"5aNzxEEkRE9MgNkiuXmpOR".parse().unwrap(), &changes)
+            .await
+            .expect("failed to send changes");
+
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_create_playlist_in_folder() {
+        let mut server = mockito::Server::new_async().await;
+        let create = server
+            .mock("POST", "/playlist/v2/playlist")
+            .match_body(mockito::Matcher::PartialJsonString(
+                r#"{"ops":[{"kind":"UPDATE_LIST_ATTRIBUTES","updateListAttributes":{"newAttributes":{"values":{"name":"Deep","description":"no lyrics","collaborative":true}}}}]}"#.to_string(),
+            ))
+            .with_body(r#"{"uri":"spotify:playlist:5aNzxEEkRE9MgNkiuXmpOR","revision":"AAAAAQ/+new"}"#)
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/playlist/v2/user/user/rootlist")
+            .match_query(mockito::Matcher::Any)
+            .with_body(r#"{"revision":"AAAAAQ/+rev","contents":{"pos":0,"truncated":false,"items":[{"uri":"spotify:playlist:3FKTkhbClLGgKdPpbx3aHy"},{"uri":"spotify:start-group:123456789abcdefa:Focus"},{"uri":"spotify:end-group:123456789abcdefa"}]}}"#)
+            .create_async()
+            .await;
+        let add = server
+            .mock("POST", "/playlist/v2/user/user/rootlist/changes")
+            .match_body(mockito::Matcher::PartialJsonString(
+                r#"{"baseRevision":"AAAAAQ/+rev","deltas":[{"ops":[{"kind":"ADD","add":{"fromIndex":2,"items":[{"uri":"spotify:playlist:5aNzxEEkRE9MgNkiuXmpOR","attributes":{"public":true}}]}}]}]}"#.to_string(),
+            ))
+            .with_body(r#"{"revision":"AAAAAg/+rev"}"#)
+            .create_async()
+            .await;
+
+        let created = local_session(&server.url())
+            .create_playlist_at(
+                "Deep",
+                "no lyrics",
+                true,
+                true,
+                &crate::PlaylistPlacement::InFolder("123456789abcdefa".parse().unwrap()),
+            )
+            .await
+            .expect("failed to create playlist");
+
+        create.assert_async().await;
+        add.assert_async().await;
+        assert_eq!(
+            created.uri.to_string(),
+            "spotify:playlist:5aNzxEEkRE9MgNkiuXmpOR"
+        );
+        assert_eq!(created.root_list_revision, "AAAAAg/+rev");
+    }
+
+    #[tokio::test]
+    async fn test_create_playlist_conflict() {
+        let mut server = mockito::Server::new_async().await;
+        let create = server
+            .mock("POST", "/playlist/v2/playlist")
+            .with_body(
+                r#"{"uri":"spotify:playlist:5aNzxEEkRE9MgNkiuXmpOR","revision":"AAAAAQ/+new"}"#,
+            )
+            .expect(2)
+            .create_async()
+            .await;
+        // The first fetch sees the old revision, every later one the new revision
+        server
+            .mock("GET", "/playlist/v2/user/user/rootlist")
+            .match_query(mockito::Matcher::Any)
+            .with_body(r#"{"revision":"AAAAAQ/+old","contents":{"items":[{"uri":"spotify:start-group:123456789abcdefa:Focus"},{"uri":"spotify:end-group:123456789abcdefa"}]}}"#)
+            .expect(1)
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/playlist/v2/user/user/rootlist")
+            .match_query(mockito::Matcher::Any)
+            .with_body(r#"{"revision":"AAAAAQ/+rev","contents":{"items":[{"uri":"spotify:playlist:3FKTkhbClLGgKdPpbx3aHy"},{"uri":"spotify:start-group:123456789abcdefa:Focus"},{"uri":"spotify:end-group:123456789abcdefa"}]}}"#)
+            .create_async()
+            .await;
+        server
+            .mock("POST", "/playlist/v2/user/user/rootlist/changes")
+            .match_body(mockito::Matcher::PartialJsonString(
+                r#"{"baseRevision":"AAAAAQ/+old"}"#.to_string(),
+            ))
+            .with_status(409)
+            .create_async()
+            .await;
+        let add = server
+            .mock("POST", "/playlist/v2/user/user/rootlist/changes")
+            .match_body(mockito::Matcher::PartialJsonString(
+                r#"{"baseRevision":"AAAAAQ/+rev","deltas":[{"ops":[{"kind":"ADD","add":{"fromIndex":2}}]}]}"#.to_string(),
+            ))
+            .with_body(r#"{"revision":"AAAAAg/+rev"}"#)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let s = local_session(&server.url());
+        let placement = crate::PlaylistPlacement::InFolder("123456789abcdefa".parse().unwrap());
+        let created = s
+            .create_playlist_at("Deep", "", false, false, &placement)
+            .await
+            .expect("failed to create playlist");
+        assert_eq!(created.root_list_revision, "AAAAAg/+rev");
+        add.assert_async().await;
+
+        // A folder that doesn't exist fails before anything is created
+        let missing = crate::PlaylistPlacement::InFolder("fedcba9876543210".parse().unwrap());
+        assert!(s
+            .create_playlist_at("Deep", "", false, false, &missing)
+            .await
+            .is_err());
+
+        // Once adding keeps failing, the error still names the created playlist
+        server
+            .mock("POST", "/playlist/v2/user/user/rootlist/changes")
+            .with_status(500)
+            .create_async()
+            .await;
+        let err = s
+            .create_playlist_at("Deep", "", false, false, &placement)
+            .await
+            .expect_err("adding the playlist should fail");
+        let err = err
+            .downcast_ref::<crate::PlaylistPlacementError>()
+            .expect("expected a placement error");
+        assert_eq!(
+            err.uri.to_string(),
+            "spotify:playlist:5aNzxEEkRE9MgNkiuXmpOR"
+        );
+        create.assert_async().await;
+    }
+
+    #[cfg(feature = "protobuf")]
+    #[tokio::test]
+    async fn test_protobuf_wire_format() {
+        use prost::Message;
+
+        use crate::api::protobuf::proto;
+        use crate::session::WireFormat;
+
+        let revision = vec![0, 0, 0, 1];
+        let root_list = proto::SelectedListContent {
+            revision: Some(revision.clone()),
+            contents: Some(proto::ListItems {
+                pos: 0,
+                truncated: false,
+                items: vec![proto::Item {
+                    uri: "spotify:playlist:5aNzxEEkRE9MgNkiuXmpOR".to_string(),
+                    attributes: None,
+                }],
+                meta_items: vec![],
+            }),
+            ..Default::default()
+        };
+        let changed = proto::SelectedListContent {
+            revision: Some(vec![0, 0, 0, 2]),
+            ..Default::default()
+        };
+
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/playlist/v2/user/user/rootlist")
+            .match_query(mockito::Matcher::Any)
+            .match_header("accept", "application/x-protobuf")
+            .with_body(root_list.encode_to_vec())
+            .create_async()
+            .await;
+        let changes = server
+            .mock("POST", "/playlist/v2/user/user/rootlist/changes")
+            .match_header("content-type", "application/x-protobuf")
+            .with_body(changed.encode_to_vec())
+            .create_async()
+            .await;
+
+        let mut session = local_session(&server.url());
+        session.set_wire_format(WireFormat::Protobuf);
+
+        let root_list = session
+            .get_root_list()
+            .await
+            .expect("failed to get root list");
+        assert_eq!(root_list.revision, "AAAAAQ==");
+        assert_eq!(root_list.len(), 1);
+
+        let revision = session
+            .send_root_list_changes(&root_list.new_request().mov(0, 1, 1).build())
+            .await
+            .expect("failed to send changes");
+        changes.assert_async().await;
+        assert_eq!(revision, "AAAAAg==");
+    }
 }
@@ -0,0 +1,534 @@
+//! Recording real [`Session`] traffic once and replaying it in tests
+//!
+//! A [`Recorder`] is a proxy in front of the spotify servers, a session created with
+//! [`Recorder::endpoints`] works as usual while every request and response goes into a
+//! [`Cassette`]. Cassettes only keep the method, path and body of requests, so cookies and
+//! authorization headers are never written, and the access token, client id and client
+//! token found in the responses are replaced with [`REDACTED`] everywhere.
+//!
+//! ```no_run
+//! use spotify_private_api::testing::cassette::{Player, Recorder};
+//! use spotify_private_api::{Endpoints, Session};
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let recorder = Recorder::start(Endpoints::default()).expect("failed to start recorder");
+//!     recorder.redact("my-user-id", "user");
+//!
+//!     let session = Session::with_endpoints("dc", "key", "my-user-id", recorder.endpoints())
+//!         .await
+//!         .expect("failed to create session");
+//!     session.get_root_list().await.expect("failed to get root list");
+//!     recorder.cassette().save("rootlist.json").expect("failed to save cassette");
+//!
+//!     // Later, without credentials
+//!     let player = Player::load("rootlist.json").expect("failed to start player");
+//!     let session = player.session("user").await.expect("failed to create session");
+//!     session.get_root_list().await.expect("failed to get root list");
+//! }
+//! ```
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use base64::Engine;
+use hyper::header::{CONTENT_LENGTH, CONTENT_TYPE, HOST};
+use hyper::{Body, Request, Response, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::task::JoinHandle;
+
+use super::{local_endpoints, response, spawn_server};
+use crate::session::{Endpoints, Session};
+use crate::Result;
+
+/// What recorded tokens are replaced with
+pub const REDACTED: &str = "REDACTED";
+
+/// Requests and the responses they got, in the order they were sent
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+pub struct Cassette {
+    pub interactions: Vec<Interaction>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Interaction {
+    pub request: RecordedRequest,
+    pub response: RecordedResponse,
+}
+
+/// Bodies that aren't UTF-8, e.g. protobuf, are kept as base64 and aren't redacted
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct RecordedRequest {
+    pub method: String,
+    /// Path and query
+    pub path: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub body: String,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub base64: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct RecordedResponse {
+    pub status: u16,
+    #[serde(
+        rename = "contentType",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub content_type: Option<String>,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub body: String,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub base64: bool,
+}
+
+impl Cassette {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        Ok(serde_json::from_slice(&std::fs::read(path)?)?)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(self)? + "\n")?;
+
+        Ok(())
+    }
+
+    /// Replaces `secret` with `replacement` in every path and text body
+    pub fn redact(&mut self, secret: &str, replacement: &str) {
+        if secret.is_empty() {
+            return;
+        }
+
+        for interaction in &mut self.interactions {
+            let request = &mut interaction.request;
+            request.path = request.path.replace(secret, replacement);
+            if !request.base64 {
+                request.body = request.body.replace(secret, replacement);
+            }
+
+            let response = &mut interaction.response;
+            if !response.base64 {
+                response.body = response.body.replace(secret, replacement);
+            }
+        }
+    }
+}
+
+/// Forwards requests to the spotify servers and records them, see the [module docs](self)
+///
+/// The dealer and the web api it registers connections with aren't proxied,
+/// [`Recorder::endpoints`] keep the upstream ones.
+#[derive(Debug)]
+pub struct Recorder {
+    addr: SocketAddr,
+    upstream: Arc<Endpoints>,
+    recording: Arc<Mutex<Recording>>,
+    task: JoinHandle<()>,
+}
+
+#[derive(Debug, Default)]
+struct Recording {
+    cassette: Cassette,
+    /// Secrets and their replacements
+    secrets: Vec<(String, String)>,
+}
+
+impl Recorder {
+    pub fn start(upstream: Endpoints) -> Result<Self> {
+        let upstream = Arc::new(upstream);
+        let recording = Arc::new(Mutex::new(Recording::default()));
+        let http_client = reqwest::Client::new();
+
+        let (service_upstream, service_recording) = (upstream.clone(), recording.clone());
+        let (addr, task) = spawn_server(move |req| {
+            forward(
+                http_client.clone(),
+                service_upstream.clone(),
+                service_recording.clone(),
+                req,
+            )
+        })?;
+
+        Ok(Self {
+            addr,
+            upstream,
+            recording,
+            task,
+        })
+    }
+
+    pub fn endpoints(&self) -> Endpoints {
+        Endpoints {
+            dealer: self.upstream.dealer.clone(),
+            api: self.upstream.api.clone(),
+            ..local_endpoints(self.addr)
+        }
+    }
+
+    /// Also replaces `secret`, e.g. the user id, with `replacement` in the cassette
+    pub fn redact(&self, secret: &str, replacement: &str) {
+        self.recording
+            .lock()
+            .unwrap()
+            .secrets
+            .push((secret.to_string(), replacement.to_string()));
+    }
+
+    /// The redacted interactions recorded so far
+    pub fn cassette(&self) -> Cassette {
+        let recording = self.recording.lock().unwrap();
+        let mut cassette = recording.cassette.clone();
+        for (secret, replacement) in &recording.secrets {
+            cassette.redact(secret, replacement);
+        }
+
+        cassette
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+async fn forward(
+    http_client: reqwest::Client,
+    upstream: Arc<Endpoints>,
+    recording: Arc<Mutex<Recording>>,
+    req: Request<Body>,
+) -> std::result::Result<Response<Body>, Infallible> {
+    match record(&http_client, &upstream, &recording, req).await {
+        Ok(res) => Ok(res),
+        Err(err) => Ok(response(StatusCode::BAD_GATEWAY, err.to_string())),
+    }
+}
+
+async fn record(
+    http_client: &reqwest::Client,
+    upstream: &Endpoints,
+    recording: &Mutex<Recording>,
+    req: Request<Body>,
+) -> Result<Response<Body>> {
+    let (mut parts, body) = req.into_parts();
+    let body = hyper::body::to_bytes(body).await?;
+    let path = parts
+        .uri
+        .path_and_query()
+        .map_or("/", |path| path.as_str())
+        .to_string();
+    let base_url = match parts.uri.path() {
+        "/get_access_token" => &upstream.open,
+        "/v1/clienttoken" => &upstream.client_token,
+        _ => &upstream.spclient,
+    };
+
+    parts.headers.remove(HOST);
+    parts.headers.remove(CONTENT_LENGTH);
+    let res = http_client
+        .request(parts.method.clone(), format!("{}{}", base_url, path))
+        .headers(parts.headers)
+        .body(body.to_vec())
+        .send()
+        .await?;
+    let status = res.status();
+    let content_type = res
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+    let res_body = res.bytes().await?;
+
+    let (request_body, request_base64) = encode_body(&body);
+    let (response_body, response_base64) = encode_body(&res_body);
+    let interaction = Interaction {
+        request: RecordedRequest {
+            method: parts.method.to_string(),
+            path,
+            body: request_body,
+            base64: request_base64,
+        },
+        response: RecordedResponse {
+            status: status.as_u16(),
+            content_type: content_type.clone(),
+            body: response_body,
+            base64: response_base64,
+        },
+    };
+
+    let mut recording = recording.lock().unwrap();
+    for secret in tokens(parts.uri.path(), &res_body) {
+        recording.secrets.push((secret, REDACTED.to_string()));
+    }
+    recording.cassette.interactions.push(interaction);
+
+    let mut res = response(status, res_body);
+    if let Some(content_type) = content_type {
+        res.headers_mut()
+            .insert(CONTENT_TYPE, content_type.parse()?);
+    }
+
+    Ok(res)
+}
+
+/// Tokens and ids handed out by the token endpoints
+fn tokens(path: &str, body: &[u8]) -> Vec<String> {
+    let pointers: &[&str] = match path {
+        "/get_access_token" => &["/accessToken", "/clientId"],
+        "/v1/clienttoken" => &["/granted_token/token"],
+        _ => &[],
+    };
+    let Ok(json) = serde_json::from_slice::<Value>(body) else {
+        return vec![];
+    };
+
+    pointers
+        .iter()
+        .filter_map(|pointer| json.pointer(pointer)?.as_str())
+        .filter(|token| !token.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Serves the responses of a [`Cassette`]
+///
+/// A request gets the first unplayed response recorded for the same method and path, request
+/// bodies aren't compared since changes carry the time they were made. Requests without one
+/// get `404 Not Found`.
+#[derive(Debug)]
+pub struct Player {
+    addr: SocketAddr,
+    unplayed: Arc<Mutex<Vec<Interaction>>>,
+    task: JoinHandle<()>,
+}
+
+impl Player {
+    pub fn start(cassette: Cassette) -> Result<Self> {
+        let unplayed = Arc::new(Mutex::new(cassette.interactions));
+
+        let service_unplayed = unplayed.clone();
+        let (addr, task) = spawn_server(move |req| play(service_unplayed.clone(), req))?;
+
+        Ok(Self {
+            addr,
+            unplayed,
+            task,
+        })
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        Self::start(Cassette::load(path)?)
+    }
+
+    /// Endpoints that point every service at this player, there is no dealer
+    pub fn endpoints(&self) -> Endpoints {
+        local_endpoints(self.addr)
+    }
+
+    /// A session logged in as `user_id`, which has to match the one in the recorded paths
+    pub async fn session(&self, user_id: &str) -> Result<Session> {
+        Session::with_endpoints(REDACTED, REDACTED, user_id, self.endpoints()).await
+    }
+
+    /// Interactions that weren't played yet
+    pub fn unplayed(&self) -> Vec<Interaction> {
+        self.unplayed.lock().unwrap().clone()
+    }
+}
+
+impl Drop for Player {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+async fn play(
+    unplayed: Arc<Mutex<Vec<Interaction>>>,
+    req: Request<Body>,
+) -> std::result::Result<Response<Body>, Infallible> {
+    let method = req.method().as_str();
+    let path = req.uri().path_and_query().map_or("/", |path| path.as_str());
+
+    let mut unplayed = unplayed.lock().unwrap();
+    let Some(position) = unplayed.iter().position(|interaction| {
+        interaction.request.method == method && interaction.request.path == path
+    }) else {
+        return Ok(response(
+            StatusCode::NOT_FOUND,
+            format!("no recorded response for {} {}", method, path),
+        ));
+    };
+    let recorded = unplayed.remove(position).response;
+
+    let body = match decode_body(&recorded.body, recorded.base64) {
+        Ok(body) => body,
+        Err(err) => return Ok(response(StatusCode::INTERNAL_SERVER_ERROR, err.to_string())),
+    };
+    let status = StatusCode::from_u16(recorded.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+    let mut res = response(status, body);
+    if let Some(content_type) = recorded.content_type.and_then(|value| value.parse().ok()) {
+        res.headers_mut().insert(CONTENT_TYPE, content_type);
+    }
+
+    Ok(res)
+}
+
+fn encode_body(body: &[u8]) -> (String, bool) {
+    match std::str::from_utf8(body) {
+        Ok(body) => (body.to_string(), false),
+        Err(_) => (base64::engine::general_purpose::STANDARD.encode(body), true),
+    }
+}
+
+fn decode_body(body: &str, base64: bool) -> Result<Vec<u8>> {
+    if base64 {
+        Ok(base64::engine::general_purpose::STANDARD.decode(body)?)
+    } else {
+        Ok(body.as_bytes().to_vec())
+    }
+}
+
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Cassette, Player, Recorder, REDACTED};
+    use crate::session::Session;
+    use crate::testing::FakeServer;
+
+    /// Written by hand in the shape of a recording, not recorded from a real account. Its
+    /// responses are trimmed down, e.g. two items in a single ADD and a short info block, so it
+    /// only covers the replay, not the shapes the real servers send.
+    const SYNTHETIC_ROOT_LIST_CASSETTE: &str = include_str!("cassettes/synthetic-rootlist.json");
+
+    #[tokio::test]
+    async fn test_record_and_replay() {
+        let server = FakeServer::start().await.expect("failed to start server");
+        let recorder = Recorder::start(server.endpoints()).expect("failed to start recorder");
+        recorder.redact(FakeServer::USER_ID, "user");
+
+        let s = Session::with_endpoints(
+            FakeServer::DC,
+            FakeServer::KEY,
+            FakeServer::USER_ID,
+            recorder.endpoints(),
+        )
+        .await
+        .expect("failed to create session");
+        let root_list = s.get_root_list().await.expect("failed to get root list");
+        let changes = root_list
+            .new_request()
+            .add("TestFolder", &"123456789abcdefa".parse().unwrap(), 0, 1)
+            .unwrap()
+            .build();
+        s.send_changes(&changes)
+            .await
+            .expect("failed to send changes");
+        let recorded = s.get_root_list().await.expect("failed to get root list");
+
+        let cassette = recorder.cassette();
+        assert_eq!(cassette.interactions.len(), 5);
+        let json = serde_json::to_string(&cassette).expect("failed to serialize cassette");
+        for secret in [
+            FakeServer::ACCESS_TOKEN,
+            FakeServer::CLIENT_TOKEN,
+            FakeServer::DC,
+            FakeServer::KEY,
+            FakeServer::USER_ID,
+            "fake-client-id",
+        ] {
+            assert!(!json.contains(secret), "{} wasn't redacted", secret);
+        }
+        assert!(json.contains(REDACTED));
+
+        let player = Player::start(cassette).expect("failed to start player");
+        let s = player
+            .session("user")
+            .await
+            .expect("failed to create session");
+        assert_eq!(
+            s.get_root_list().await.expect("failed to get root list"),
+            root_list
+        );
+        s.send_changes(&changes)
+            .await
+            .expect("failed to send changes");
+        assert_eq!(
+            s.get_root_list().await.expect("failed to get root list"),
+            recorded
+        );
+        assert_eq!(player.unplayed(), vec![]);
+    }
+
+    /// Replays the synthetic cassette, what real responses look like is up to
+    /// `test_record_root_list` against a real account
+    #[tokio::test]
+    async fn test_replay_synthetic_root_list() {
+        let cassette: Cassette =
+            serde_json::from_str(SYNTHETIC_ROOT_LIST_CASSETTE).expect("failed to parse cassette");
+        let player = Player::start(cassette).expect("failed to start player");
+        let s = player
+            .session("user")
+            .await
+            .expect("failed to create session");
+
+        let root_list = s.get_root_list().await.expect("failed to get root list");
+        assert_eq!(root_list.revision, "AAAAELqqrKuzaoeUKYP7gEzCzrx3h0rD");
+        assert_eq!(root_list.len(), 4);
+        assert_eq!(
+            root_list
+                .find_by_playlist_id(&"5aNzxEEkRE9MgNkiuXmpOR".parse().unwrap())
+                .and_then(|entry| entry.entry.name()),
+            Some("My Playlist #2")
+        );
+
+        let changes = root_list
+            .new_request()
+            .add("TestFolder", &"123456789abcdefa".parse().unwrap(), 0, 1)
+            .unwrap()
+            .build();
+        s.send_changes(&changes)
+            .await
+            .expect("failed to send changes");
+
+        let root_list = s.get_root_list().await.expect("failed to get root list");
+        assert_eq!(root_list.revision, "AAAAEYXQz4lWSwPz5t1kq0S6X2dcIXh2");
+        assert_eq!(root_list.len(), 6);
+        assert_eq!(player.unplayed(), vec![]);
+
+        // Everything was played
+        assert!(s.get_root_list().await.is_err());
+    }
+
+    #[cfg_attr(not(feature = "system-tests"), ignore)]
+    #[tokio::test]
+    async fn test_record_root_list() {
+        let dc = std::env::var("SPOTIFY_DC").expect("failed to get SPOTIFY_DC from ENV");
+        let key = std::env::var("SPOTIFY_KEY").expect("failed to get SPOTIFY_KEY from ENV");
+        let user_id =
+            std::env::var("SPOTIFY_USER_ID").expect("failed to get SPOTIFY_USER_ID from ENV");
+
+        let recorder = Recorder::start(Default::default()).expect("failed to start recorder");
+        recorder.redact(&user_id, "user");
+        let s = Session::with_endpoints(&dc, &key, &user_id, recorder.endpoints())
+            .await
+            .expect("failed to create session");
+        s.get_root_list().await.expect("failed to get root list");
+
+        let json = serde_json::to_string(&recorder.cassette()).expect("failed to serialize");
+        for secret in [&dc, &key, &user_id] {
+            assert!(!json.contains(secret.as_str()));
+        }
+        if let Ok(path) = std::env::var("SPOTIFY_CASSETTE") {
+            recorder
+                .cassette()
+                .save(path)
+                .expect("failed to save cassette");
+        }
+    }
+}
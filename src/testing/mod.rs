@@ -0,0 +1,462 @@
+//! An in-process stand-in for the spotify servers, enabled by the `testing` feature
+//!
+//! ```no_run
+//! use spotify_private_api::testing::FakeServer;
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let server = FakeServer::start().await.expect("failed to start fake server");
+//!     let session = server.session().await.expect("failed to create session");
+//!
+//!     let root_list = session.get_root_list().await.expect("failed to get root list");
+//!     let changes = root_list
+//!         .new_request()
+//!         .add("New Folder", &root_list.generate_folder_id().unwrap(), 0, 1).unwrap()
+//!         .build();
+//!     session.send_changes(&changes).await.expect("failed to send changes");
+//!
+//!     assert_eq!(server.root_list().len(), 2);
+//! }
+//! ```
+pub mod cassette;
+
+use std::collections::hash_map::DefaultHasher;
+use std::convert::Infallible;
+use std::future::Future;
+use std::hash::{Hash, Hasher};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+
+use base64::Engine;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, StatusCode};
+use tokio::task::JoinHandle;
+
+use crate::api::folders::{Changes, RootList};
+use crate::session::{Endpoints, Session};
+use crate::Result;
+
+/// Revision of the root list a [`FakeServer::start`] serves
+pub const INITIAL_REVISION: &str = "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA";
+
+/// Serves `get_access_token`, `clienttoken` and the root list of a single user, and applies
+/// the changes it receives the way spotify does
+///
+/// Changes have to be based on the current revision, stale ones are rejected with
+/// `409 Conflict`. Lists are served as JSON only.
+#[derive(Debug)]
+pub struct FakeServer {
+    addr: SocketAddr,
+    state: Arc<Mutex<State>>,
+    task: JoinHandle<()>,
+}
+
+#[derive(Debug)]
+struct State {
+    root_list: RootList,
+    changes: Vec<Changes>,
+}
+
+impl FakeServer {
+    pub const DC: &'static str = "fake-dc";
+    pub const KEY: &'static str = "fake-key";
+    pub const USER_ID: &'static str = "fake-user";
+    pub const ACCESS_TOKEN: &'static str = "fake-access-token";
+    pub const CLIENT_TOKEN: &'static str = "fake-client-token";
+
+    /// Starts a server with an empty root list
+    pub async fn start() -> Result<Self> {
+        let root_list = serde_json::from_str(&format!(
+            r#"{{"revision":"{}","length":0,"contents":{{"pos":0,"truncated":false,"items":[],"metaItems":[]}}}}"#,
+            INITIAL_REVISION
+        ))?;
+
+        Self::with_root_list(root_list).await
+    }
+
+    pub async fn with_root_list(root_list: RootList) -> Result<Self> {
+        let state = Arc::new(Mutex::new(State {
+            root_list,
+            changes: vec![],
+        }));
+
+        let service_state = state.clone();
+        let (addr, task) = spawn_server(move |req| handle(service_state.clone(), req))?;
+
+        Ok(Self { addr, state, task })
+    }
+
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// Endpoints that point every service at this server, there is no dealer
+    pub fn endpoints(&self) -> Endpoints {
+        local_endpoints(self.addr)
+    }
+
+    /// A session logged in as [`FakeServer::USER_ID`]
+    pub async fn session(&self) -> Result<Session> {
+        Session::with_endpoints(Self::DC, Self::KEY, Self::USER_ID, self.endpoints()).await
+    }
+
+    /// The root list as it is now
+    pub fn root_list(&self) -> RootList {
+        self.state.lock().unwrap().root_list.clone()
+    }
+
+    /// Every changes request that was applied, in order
+    pub fn received_changes(&self) -> Vec<Changes> {
+        self.state.lock().unwrap().changes.clone()
+    }
+}
+
+impl Drop for FakeServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+async fn handle(
+    state: Arc<Mutex<State>>,
+    req: Request<Body>,
+) -> std::result::Result<Response<Body>, Infallible> {
+    let (parts, body) = req.into_parts();
+    let header = |name: &str| {
+        parts
+            .headers
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default()
+    };
+    let authorized = header("authorization") == format!("Bearer {}", FakeServer::ACCESS_TOKEN);
+    let cookies = format!("sp_dc={};sp_key={}", FakeServer::DC, FakeServer::KEY);
+    let logged_in = header("cookie") == cookies;
+
+    let body = match hyper::body::to_bytes(body).await {
+        Ok(body) => body,
+        Err(err) => return Ok(response(StatusCode::BAD_REQUEST, err.to_string())),
+    };
+
+    let root_list_path = format!("/playlist/v2/user/{}/rootlist", FakeServer::USER_ID);
+    let changes_path = format!("{}/changes", root_list_path);
+    let mut state = state.lock().unwrap();
+
+    let res = match (&parts.method, parts.uri.path()) {
+        (&Method::GET, "/get_access_token") if logged_in => response(
+            StatusCode::OK,
+            format!(
+                r#"{{"clientId":"fake-client-id","accessToken":"{}","accessTokenExpirationTimestampMs":"4102444800000","isAnonymous":false}}"#,
+                FakeServer::ACCESS_TOKEN
+            ),
+        ),
+        (&Method::POST, "/v1/clienttoken") => response(
+            StatusCode::OK,
+            format!(
+                r#"{{"response_type":"RESPONSE_GRANTED_TOKEN_RESPONSE","granted_token":{{"token":"{}","expires_after_seconds":1209600,"refresh_after_seconds":1123200}}}}"#,
+                FakeServer::CLIENT_TOKEN
+            ),
+        ),
+        (_, path) if path.starts_with("/playlist/") && !authorized => {
+            response(StatusCode::UNAUTHORIZED, "missing access token")
+        }
+        (&Method::GET, path) if path == root_list_path => {
+            let query = parts.uri.query().unwrap_or_default();
+            let param = |name: &str| {
+                query
+                    .split('&')
+                    .filter_map(|param| param.split_once('='))
+                    .find(|(key, _)| *key == name)
+                    .and_then(|(_, value)| value.parse::<usize>().ok())
+            };
+
+            page(&state.root_list, param("from"), param("length"))
+        }
+        (&Method::POST, path) if path == changes_path => state.apply(&body),
+        _ => response(StatusCode::NOT_FOUND, "not found"),
+    };
+
+    Ok(res)
+}
+
+impl State {
+    fn apply(&mut self, body: &[u8]) -> Response<Body> {
+        let changes: Changes = match serde_json::from_slice(body) {
+            Ok(changes) => changes,
+            Err(err) => return response(StatusCode::BAD_REQUEST, err.to_string()),
+        };
+
+        if changes.base_revision != self.root_list.revision {
+            return response(
+                StatusCode::CONFLICT,
+                format!(
+                    "base revision {} is not the current revision {}",
+                    changes.base_revision, self.root_list.revision
+                ),
+            );
+        }
+
+        let mut root_list = self.root_list.clone();
+        if let Err(err) = root_list.apply_changes(&changes) {
+            return response(StatusCode::BAD_REQUEST, err.to_string());
+        }
+        root_list.revision = next_revision(&root_list.revision, body);
+
+        let res = format!(r#"{{"revision":"{}"}}"#, root_list.revision);
+        self.root_list = root_list;
+        self.changes.push(changes);
+
+        response(StatusCode::OK, res)
+    }
+}
+
+/// The items `from..from + length` of `root_list`
+fn page(root_list: &RootList, from: Option<usize>, length: Option<usize>) -> Response<Body> {
+    let mut page = root_list.clone();
+    let items = &mut page.contents.items;
+    let from = from.unwrap_or(0).min(items.len());
+    let to = length.map_or(items.len(), |length| {
+        from.saturating_add(length).min(items.len())
+    });
+
+    page.contents.truncated = to < items.len();
+    page.contents.pos = from as u32;
+    page.contents.items = items[from..to].to_vec();
+    page.contents.meta_items = page
+        .contents
+        .meta_items
+        .get(from..to)
+        .map(<[_]>::to_vec)
+        .unwrap_or_default();
+
+    match serde_json::to_string(&page) {
+        Ok(page) => response(StatusCode::OK, page),
+        Err(err) => response(StatusCode::INTERNAL_SERVER_ERROR, err.to_string()),
+    }
+}
+
+/// Spotify revisions are a big endian counter followed by a hash of the list
+fn next_revision(revision: &str, changes: &[u8]) -> String {
+    let engine = base64::engine::general_purpose::STANDARD;
+    let previous = engine.decode(revision).unwrap_or_default();
+    let counter = previous
+        .get(..4)
+        .map_or(0, |counter| u32::from_be_bytes(counter.try_into().unwrap()));
+
+    let mut revision = (counter + 1).to_be_bytes().to_vec();
+    for salt in 0u8..3 {
+        let mut hasher = DefaultHasher::new();
+        (salt, &previous, changes).hash(&mut hasher);
+        revision.extend_from_slice(&hasher.finish().to_be_bytes());
+    }
+    revision.truncate(24);
+
+    engine.encode(revision)
+}
+
+/// Serves `handler` on a free local port until the returned task is aborted
+fn spawn_server<H, F>(handler: H) -> Result<(SocketAddr, JoinHandle<()>)>
+where
+    H: Fn(Request<Body>) -> F + Clone + Send + Sync + 'static,
+    F: Future<Output = std::result::Result<Response<Body>, Infallible>> + Send + 'static,
+{
+    let server = hyper::Server::try_bind(&SocketAddr::from(([127, 0, 0, 1], 0)))?.serve(
+        make_service_fn(move |_| {
+            let handler = handler.clone();
+
+            async move { Ok::<_, Infallible>(service_fn(handler)) }
+        }),
+    );
+    let addr = server.local_addr();
+    let task = tokio::spawn(async move {
+        let _ = server.await;
+    });
+
+    Ok((addr, task))
+}
+
+fn local_endpoints(addr: SocketAddr) -> Endpoints {
+    let url = format!("http://{}", addr);
+
+    Endpoints {
+        open: url.clone(),
+        client_token: url.clone(),
+        spclient: url.clone(),
+        dealer: format!("ws://{}", addr),
+        api: url,
+    }
+}
+
+fn response(status: StatusCode, body: impl Into<Body>) -> Response<Body> {
+    let mut res = Response::new(body.into());
+    *res.status_mut() = status;
+
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use hyper::StatusCode;
+
+    use super::{page, FakeServer, INITIAL_REVISION};
+    use crate::api::folders::RootList;
+    use crate::generators::FixedClock;
+    use crate::session::Session;
+
+    #[tokio::test]
+    async fn test_create_folder() {
+        let server = FakeServer::start().await.expect("failed to start server");
+        let mut s = server.session().await.expect("failed to create session");
+        // The wire only keeps milliseconds
+        s.set_clock(Arc::new(FixedClock::from_millis(1665582465479)));
+
+        let root_list = s.get_root_list().await.expect("failed to get root list");
+        assert_eq!(root_list.revision, INITIAL_REVISION);
+
+        let changes = root_list
+            .new_request()
+            .add("TestFolder", &"123456789abcdefa".parse().unwrap(), 0, 1)
+            .unwrap()
+            .build();
+        s.send_changes(&changes)
+            .await
+            .expect("failed to send changes");
+
+        let root_list = s.get_root_list().await.expect("failed to get root list");
+        assert_eq!(root_list, server.root_list());
+        assert_ne!(root_list.revision, INITIAL_REVISION);
+        assert_eq!(
+            root_list
+                .entries()
+                .map(|entry| entry.raw_uri().to_string())
+                .collect::<Vec<_>>(),
+            vec![
+                "spotify:start-group:123456789abcdefa:TestFolder",
+                "spotify:end-group:123456789abcdefa"
+            ]
+        );
+        assert_eq!(server.received_changes(), vec![changes.clone()]);
+
+        // The same changes are stale now
+        let err = s.send_changes(&changes).await.unwrap_err();
+        assert!(crate::session::is_revision_conflict(err.as_ref()));
+        assert_eq!(server.received_changes().len(), 1);
+    }
+
+    /// Keeps the messages logged by the tests
+    struct Recorder(Mutex<Vec<String>>);
+
+    impl log::Log for Recorder {
+        fn enabled(&self, _: &log::Metadata) -> bool {
+            true
+        }
+
+        fn log(&self, record: &log::Record) {
+            self.0.lock().unwrap().push(record.args().to_string());
+        }
+
+        fn flush(&self) {}
+    }
+
+    static RECORDER: Recorder = Recorder(Mutex::new(vec![]));
+
+    #[tokio::test]
+    async fn test_dry_run() {
+        let _ = log::set_logger(&RECORDER);
+        log::set_max_level(log::LevelFilter::Info);
+
+        let server = FakeServer::start().await.expect("failed to start server");
+        let mut s = server.session().await.expect("failed to create session");
+        s.set_dry_run(true);
+
+        let root_list = s.get_root_list().await.expect("failed to get root list");
+        let changes = root_list
+            .new_request()
+            .add("TestFolder", &"123456789abcdefa".parse().unwrap(), 0, 1)
+            .unwrap()
+            .build();
+        s.send_changes(&changes)
+            .await
+            .expect("failed to send changes");
+
+        assert!(server.received_changes().is_empty());
+        assert_eq!(server.root_list().revision, INITIAL_REVISION);
+        assert!(RECORDER
+            .0
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|message| message.ends_with("+ folder \"TestFolder\"\n")));
+        assert!(s.create_playlist("Test", "", false, false).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_paging() {
+        let server = FakeServer::start().await.expect("failed to start server");
+        let s = server.session().await.expect("failed to create session");
+
+        let root_list = s.get_root_list().await.expect("failed to get root list");
+        let mut request = root_list.new_request();
+        for i in 0..600 {
+            request
+                .add(
+                    &format!("Folder {}", i),
+                    &format!("{:016x}", i).parse().unwrap(),
+                    0,
+                    1,
+                )
+                .unwrap();
+        }
+        s.send_changes(&request.build())
+            .await
+            .expect("failed to send changes");
+
+        let root_list = s.get_root_list().await.expect("failed to get root list");
+        assert_eq!(root_list.len(), 1200);
+        assert_eq!(root_list, server.root_list());
+
+        // A length past the end of the list, and past u32::MAX, reads up to the end
+        let tail = s
+            .get_root_list_with(
+                crate::api::query::RootListQuery::new()
+                    .from(1000)
+                    .length(u32::MAX),
+            )
+            .await
+            .expect("failed to get root list");
+        assert_eq!(tail.len(), 200);
+        assert_eq!(tail.contents.items[..], root_list.contents.items[1000..]);
+    }
+
+    #[tokio::test]
+    async fn test_page_bounds() {
+        let server = FakeServer::start().await.expect("failed to start server");
+        let root_list = server.root_list();
+
+        let res = page(&root_list, Some(usize::MAX), Some(usize::MAX));
+        assert_eq!(res.status(), StatusCode::OK);
+        let body = hyper::body::to_bytes(res.into_body())
+            .await
+            .expect("failed to read page");
+        let page: RootList = serde_json::from_slice(&body).expect("failed to parse page");
+        assert!(page.is_empty());
+        assert!(!page.contents.truncated);
+    }
+
+    #[tokio::test]
+    async fn test_rejects_bad_cookies() {
+        let server = FakeServer::start().await.expect("failed to start server");
+
+        let res = Session::with_endpoints(
+            "wrong-dc",
+            FakeServer::KEY,
+            FakeServer::USER_ID,
+            server.endpoints(),
+        )
+        .await;
+
+        assert!(res.is_err());
+    }
+}
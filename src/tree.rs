@@ -0,0 +1,207 @@
+use serde::Serialize;
+
+use crate::api::folders::RootList;
+use crate::entries::Entry;
+use crate::uri::FolderId;
+
+/// A folder or an item of the root list, see [`RootList::tree`]
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum TreeNode {
+    Folder(FolderNode),
+    Playlist(ItemNode),
+    /// Anything else the root list holds, e.g. local files or unparsable uris
+    Other(ItemNode),
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct FolderNode {
+    pub id: FolderId,
+    pub name: String,
+    /// Index of the folder's start
+    pub index: usize,
+    /// Index of the folder's end, `None` if the root list never closes the folder
+    #[serde(rename = "endIndex")]
+    pub end_index: Option<usize>,
+    pub children: Vec<TreeNode>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct ItemNode {
+    pub uri: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub index: usize,
+}
+
+impl TreeNode {
+    pub fn index(&self) -> usize {
+        match self {
+            TreeNode::Folder(folder) => folder.index,
+            TreeNode::Playlist(item) | TreeNode::Other(item) => item.index,
+        }
+    }
+
+    /// Folder name or playlist name
+    pub fn name(&self) -> Option<&str> {
+        match self {
+            TreeNode::Folder(folder) => Some(&folder.name),
+            TreeNode::Playlist(item) | TreeNode::Other(item) => item.name.as_deref(),
+        }
+    }
+
+    /// Number of root list items the node spans, a folder's start and end included
+    pub fn span(&self) -> usize {
+        match self {
+            TreeNode::Folder(FolderNode {
+                index,
+                end_index: Some(end_index),
+                ..
+            }) => end_index - index + 1,
+            TreeNode::Folder(folder) => {
+                1 + folder.children.iter().map(TreeNode::span).sum::<usize>()
+            }
+            TreeNode::Playlist(_) | TreeNode::Other(_) => 1,
+        }
+    }
+}
+
+impl RootList {
+    /// The folders of the root list as a tree
+    ///
+    /// End markers without a matching start are left out, folders that are never closed
+    /// contain everything after their start.
+    pub fn tree(&self) -> Vec<TreeNode> {
+        let mut root = vec![];
+        // Open folders, innermost last
+        let mut open: Vec<FolderNode> = vec![];
+
+        for entry in self.entries() {
+            let node = match entry.entry {
+                Entry::FolderStart { id, name } => {
+                    open.push(FolderNode {
+                        id: FolderId::from_checked(id),
+                        name: name.to_string(),
+                        index: entry.index,
+                        end_index: None,
+                        children: vec![],
+                    });
+                    continue;
+                }
+                Entry::FolderEnd { id } => {
+                    let Some(position) = open.iter().rposition(|folder| folder.id.as_str() == id)
+                    else {
+                        continue;
+                    };
+
+                    // Folders opened inside and never closed end here as well
+                    while open.len() > position + 1 {
+                        let folder = open.pop().unwrap();
+                        push(&mut open, &mut root, TreeNode::Folder(folder));
+                    }
+                    let mut folder = open.pop().unwrap();
+                    folder.end_index = Some(entry.index);
+                    TreeNode::Folder(folder)
+                }
+                Entry::Playlist { .. } => TreeNode::Playlist(ItemNode {
+                    uri: entry.raw_uri().to_string(),
+                    name: entry.entry.name().map(str::to_string),
+                    index: entry.index,
+                }),
+                Entry::Unknown(uri) => TreeNode::Other(ItemNode {
+                    uri: uri.to_string(),
+                    name: None,
+                    index: entry.index,
+                }),
+            };
+
+            push(&mut open, &mut root, node);
+        }
+
+        while let Some(folder) = open.pop() {
+            push(&mut open, &mut root, TreeNode::Folder(folder));
+        }
+
+        root
+    }
+}
+
+fn push(open: &mut [FolderNode], root: &mut Vec<TreeNode>, node: TreeNode) {
+    match open.last_mut() {
+        Some(parent) => parent.children.push(node),
+        None => root.push(node),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TreeNode;
+    use crate::api::folders::RootList;
+
+    fn root_list(uris: &[&str]) -> RootList {
+        let items: Vec<_> = uris
+            .iter()
+            .map(|uri| serde_json::json!({ "uri": uri }))
+            .collect();
+
+        serde_json::from_value(serde_json::json!({
+            "revision": "AAAA",
+            "contents": { "items": items }
+        }))
+        .expect("Couldn't parse rootlist")
+    }
+
+    fn shape(nodes: &[TreeNode]) -> String {
+        nodes
+            .iter()
+            .map(|node| match node {
+                TreeNode::Folder(folder) => format!(
+                    "{}@{}..{:?}[{}]",
+                    folder.name,
+                    folder.index,
+                    folder.end_index,
+                    shape(&folder.children)
+                ),
+                TreeNode::Playlist(item) | TreeNode::Other(item) => item.index.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
+    #[test]
+    fn test_tree() {
+        let rl = root_list(&[
+            "spotify:start-group:aaaaaaaaaaaaaaaa:Work",
+            "spotify:start-group:bbbbbbbbbbbbbbbb:Focus",
+            "spotify:playlist:5aNzxEEkRE9MgNkiuXmpOR",
+            "spotify:end-group:bbbbbbbbbbbbbbbb",
+            "spotify:end-group:aaaaaaaaaaaaaaaa",
+            "spotify:local:artist:album:title:1",
+        ]);
+
+        let tree = rl.tree();
+        assert_eq!(shape(&tree), "Work@0..Some(4)[Focus@1..Some(3)[2]],5");
+        assert_eq!(tree[0].span(), 5);
+        assert!(matches!(tree[1], TreeNode::Other(_)));
+    }
+
+    #[test]
+    fn test_unbalanced_tree() {
+        let rl = root_list(&[
+            "spotify:end-group:cccccccccccccccc",
+            "spotify:start-group:aaaaaaaaaaaaaaaa:Outer",
+            "spotify:start-group:bbbbbbbbbbbbbbbb:Unclosed",
+            "spotify:playlist:5aNzxEEkRE9MgNkiuXmpOR",
+            "spotify:end-group:aaaaaaaaaaaaaaaa",
+            "spotify:start-group:dddddddddddddddd:Open",
+            "spotify:playlist:3FKTkhbClLGgKdPpbx3aHy",
+        ]);
+
+        let tree = rl.tree();
+        assert_eq!(
+            shape(&tree),
+            "Outer@1..Some(4)[Unclosed@2..None[3]],Open@5..None[6]"
+        );
+        assert_eq!(tree[1].span(), 2);
+    }
+}
@@ -0,0 +1,507 @@
+use std::error;
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+const BASE62_ALPHABET: &[u8; 62] =
+    b"0123456789abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
+const BASE62_LENGTH: usize = 22;
+const HEX_LENGTH: usize = 32;
+
+const URI_PREFIX: &str = "spotify:";
+const URL_PREFIXES: [&str; 3] = [
+    "https://open.spotify.com/",
+    "http://open.spotify.com/",
+    "open.spotify.com/",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseUriError {
+    input: String,
+    reason: &'static str,
+}
+
+impl ParseUriError {
+    fn new(input: &str, reason: &'static str) -> Self {
+        Self {
+            input: input.to_string(),
+            reason,
+        }
+    }
+}
+
+impl fmt::Display for ParseUriError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid spotify uri {:?}: {}", self.input, self.reason)
+    }
+}
+
+impl error::Error for ParseUriError {}
+
+/// 16 byte spotify id (GID), written as 22 base62 characters in uris
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SpotifyId(u128);
+
+impl SpotifyId {
+    pub fn from_base62(id: &str) -> Result<Self, ParseUriError> {
+        if id.len() != BASE62_LENGTH {
+            return Err(ParseUriError::new(id, "base62 id must be 22 characters"));
+        }
+
+        let mut value = 0u128;
+        for c in id.bytes() {
+            let digit = BASE62_ALPHABET
+                .iter()
+                .position(|x| *x == c)
+                .ok_or_else(|| ParseUriError::new(id, "invalid base62 character"))?;
+
+            value = value
+                .checked_mul(62)
+                .and_then(|value| value.checked_add(digit as u128))
+                .ok_or_else(|| ParseUriError::new(id, "base62 id is out of range"))?;
+        }
+
+        Ok(Self(value))
+    }
+
+    pub fn to_base62(self) -> String {
+        let mut value = self.0;
+        let mut id = [b'0'; BASE62_LENGTH];
+
+        for c in id.iter_mut().rev() {
+            *c = BASE62_ALPHABET[(value % 62) as usize];
+            value /= 62;
+        }
+
+        String::from_utf8_lossy(&id).into_owned()
+    }
+
+    pub fn from_hex(id: &str) -> Result<Self, ParseUriError> {
+        if id.len() != HEX_LENGTH || !id.bytes().all(|c| c.is_ascii_hexdigit()) {
+            return Err(ParseUriError::new(id, "hex id must be 32 hex characters"));
+        }
+
+        u128::from_str_radix(id, 16)
+            .map(Self)
+            .map_err(|_| ParseUriError::new(id, "invalid hex id"))
+    }
+
+    pub fn to_hex(self) -> String {
+        format!("{:032x}", self.0)
+    }
+
+    pub fn from_gid(gid: [u8; 16]) -> Self {
+        Self(u128::from_be_bytes(gid))
+    }
+
+    pub fn to_gid(self) -> [u8; 16] {
+        self.0.to_be_bytes()
+    }
+}
+
+impl fmt::Display for SpotifyId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_base62())
+    }
+}
+
+impl FromStr for SpotifyId {
+    type Err = ParseUriError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_base62(s)
+    }
+}
+
+/// Id of a root list folder, generated ones are 16 lowercase hex digits
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct FolderId(String);
+
+impl FolderId {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// An id [`GroupUri::parse`] already checked
+    pub(crate) fn from_checked(id: &str) -> Self {
+        Self(id.to_string())
+    }
+}
+
+impl fmt::Display for FolderId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl FromStr for FolderId {
+    type Err = ParseUriError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self(parse_folder_id(s, s)?.to_string()))
+    }
+}
+
+/// The start or end of a folder with the parts borrowed from the uri, what
+/// [`SpotifyUri::StartGroup`] and [`SpotifyUri::EndGroup`] are parsed from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum GroupUri<'a> {
+    Start { id: &'a str, name: &'a str },
+    End { id: &'a str },
+}
+
+impl<'a> GroupUri<'a> {
+    pub(crate) fn parse(uri: &'a str) -> Result<Self, ParseUriError> {
+        match split_kind(uri)? {
+            ("start-group", rest) => {
+                let (id, name) = rest
+                    .split_once(':')
+                    .ok_or_else(|| ParseUriError::new(uri, "missing folder name"))?;
+
+                Ok(GroupUri::Start {
+                    id: parse_folder_id(uri, id)?,
+                    name,
+                })
+            }
+            ("end-group", id) => Ok(GroupUri::End {
+                id: parse_folder_id(uri, id)?,
+            }),
+            _ => Err(ParseUriError::new(uri, "not a folder uri")),
+        }
+    }
+
+    pub(crate) fn to_uri(self) -> SpotifyUri {
+        match self {
+            GroupUri::Start { id, name } => SpotifyUri::StartGroup {
+                id: FolderId::from_checked(id),
+                name: name.to_string(),
+            },
+            GroupUri::End { id } => SpotifyUri::EndGroup {
+                id: FolderId::from_checked(id),
+            },
+        }
+    }
+}
+
+/// A spotify uri, e.g. `spotify:playlist:37i9dQZF1DXcBWIGoYBM5M`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum SpotifyUri {
+    Playlist(SpotifyId),
+    Track(SpotifyId),
+    Episode(SpotifyId),
+    Album(SpotifyId),
+    Artist(SpotifyId),
+    User(String),
+    /// Local file, the fields are kept url encoded as they appear in the uri
+    Local {
+        artist: String,
+        album: String,
+        title: String,
+        duration: u32,
+    },
+    /// Start of a root list folder
+    StartGroup {
+        id: FolderId,
+        name: String,
+    },
+    /// End of a root list folder
+    EndGroup {
+        id: FolderId,
+    },
+}
+
+impl SpotifyUri {
+    /// Parses an `open.spotify.com` link, e.g. `https://open.spotify.com/playlist/{id}?si=...`
+    pub fn from_url(url: &str) -> Result<Self, ParseUriError> {
+        let path = URL_PREFIXES
+            .iter()
+            .find_map(|prefix| url.strip_prefix(prefix))
+            .ok_or_else(|| ParseUriError::new(url, "not an open.spotify.com url"))?;
+        let path = path.split(['?', '#']).next().unwrap_or_default();
+
+        let mut segments = path.split('/').filter(|segment| !segment.is_empty());
+        let mut kind = segments.next();
+        if kind.is_some_and(|kind| kind.starts_with("intl-")) {
+            kind = segments.next();
+        }
+
+        let (Some(kind), Some(id), None) = (kind, segments.next(), segments.next()) else {
+            return Err(ParseUriError::new(url, "expected /{type}/{id}"));
+        };
+
+        match kind {
+            "user" => Self::parse_user(url, id),
+            _ => Self::parse_id(url, kind, id),
+        }
+        .map_err(|err| ParseUriError::new(url, err.reason))
+    }
+
+    /// The id of playlists, tracks, episodes, albums and artists
+    pub fn id(&self) -> Option<SpotifyId> {
+        match self {
+            SpotifyUri::Playlist(id)
+            | SpotifyUri::Track(id)
+            | SpotifyUri::Episode(id)
+            | SpotifyUri::Album(id)
+            | SpotifyUri::Artist(id) => Some(*id),
+            _ => None,
+        }
+    }
+
+    fn parse_id(input: &str, kind: &str, id: &str) -> Result<Self, ParseUriError> {
+        let id = SpotifyId::from_base62(id).map_err(|err| ParseUriError::new(input, err.reason))?;
+
+        match kind {
+            "playlist" => Ok(SpotifyUri::Playlist(id)),
+            "track" => Ok(SpotifyUri::Track(id)),
+            "episode" => Ok(SpotifyUri::Episode(id)),
+            "album" => Ok(SpotifyUri::Album(id)),
+            "artist" => Ok(SpotifyUri::Artist(id)),
+            _ => Err(ParseUriError::new(input, "unknown uri type")),
+        }
+    }
+
+    fn parse_user(input: &str, user: &str) -> Result<Self, ParseUriError> {
+        if user.is_empty() || user.contains(':') {
+            return Err(ParseUriError::new(input, "invalid user name"));
+        }
+
+        Ok(SpotifyUri::User(user.to_string()))
+    }
+}
+
+/// Splits `spotify:{type}:{rest}` into the type and the rest
+fn split_kind(input: &str) -> Result<(&str, &str), ParseUriError> {
+    input
+        .strip_prefix(URI_PREFIX)
+        .ok_or_else(|| ParseUriError::new(input, "missing spotify: prefix"))?
+        .split_once(':')
+        .ok_or_else(|| ParseUriError::new(input, "missing uri type"))
+}
+
+fn parse_folder_id<'a>(input: &str, id: &'a str) -> Result<&'a str, ParseUriError> {
+    if id.is_empty() || !id.bytes().all(|c| c.is_ascii_alphanumeric()) {
+        return Err(ParseUriError::new(input, "invalid folder id"));
+    }
+
+    Ok(id)
+}
+
+impl FromStr for SpotifyUri {
+    type Err = ParseUriError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, rest) = split_kind(s)?;
+
+        match kind {
+            "user" => Self::parse_user(s, rest),
+            "start-group" | "end-group" => Ok(GroupUri::parse(s)?.to_uri()),
+            "local" => {
+                let fields: Vec<&str> = rest.split(':').collect();
+                let [artist, album, title, duration] = fields[..] else {
+                    return Err(ParseUriError::new(
+                        s,
+                        "local uri needs artist, album, title and duration",
+                    ));
+                };
+
+                Ok(SpotifyUri::Local {
+                    artist: artist.to_string(),
+                    album: album.to_string(),
+                    title: title.to_string(),
+                    duration: duration
+                        .parse()
+                        .map_err(|_| ParseUriError::new(s, "invalid local duration"))?,
+                })
+            }
+            _ => Self::parse_id(s, kind, rest),
+        }
+    }
+}
+
+impl fmt::Display for SpotifyUri {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SpotifyUri::Playlist(id) => write!(f, "spotify:playlist:{}", id),
+            SpotifyUri::Track(id) => write!(f, "spotify:track:{}", id),
+            SpotifyUri::Episode(id) => write!(f, "spotify:episode:{}", id),
+            SpotifyUri::Album(id) => write!(f, "spotify:album:{}", id),
+            SpotifyUri::Artist(id) => write!(f, "spotify:artist:{}", id),
+            SpotifyUri::User(user) => write!(f, "spotify:user:{}", user),
+            SpotifyUri::Local {
+                artist,
+                album,
+                title,
+                duration,
+            } => write!(
+                f,
+                "spotify:local:{}:{}:{}:{}",
+                artist, album, title, duration
+            ),
+            SpotifyUri::StartGroup { id, name } => write!(f, "spotify:start-group:{}:{}", id, name),
+            SpotifyUri::EndGroup { id } => write!(f, "spotify:end-group:{}", id),
+        }
+    }
+}
+
+impl Serialize for FolderId {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for FolderId {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let id = String::deserialize(deserializer)?;
+        id.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+impl Serialize for SpotifyUri {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for SpotifyUri {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let uri = String::deserialize(deserializer)?;
+        uri.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FolderId, GroupUri, SpotifyId, SpotifyUri};
+
+    const PLAYLIST_ID: &str = "5aNzxEEkRE9MgNkiuXmpOR";
+    const PLAYLIST_HEX: &str = "aa01eba7b9cbca90eb3d25694e114875";
+
+    #[test]
+    fn test_id_conversions() {
+        let id = SpotifyId::from_base62(PLAYLIST_ID).expect("Couldn't parse id");
+
+        assert_eq!(id.to_hex(), PLAYLIST_HEX);
+        assert_eq!(id.to_base62(), PLAYLIST_ID);
+        assert_eq!(SpotifyId::from_hex(PLAYLIST_HEX), Ok(id));
+        assert_eq!(SpotifyId::from_gid(id.to_gid()), id);
+        assert_eq!(id.to_gid()[0], 0xaa);
+
+        assert_eq!(
+            SpotifyId::from_gid([0; 16]).to_base62(),
+            "0000000000000000000000"
+        );
+        assert_eq!(
+            SpotifyId::from_gid([0xff; 16]).to_base62(),
+            "7N42dgm5tFLK9N8MT7fHC7"
+        );
+    }
+
+    #[test]
+    fn test_invalid_ids() {
+        assert!(SpotifyId::from_base62("5aNzxEEkRE9MgNkiuXmpO").is_err());
+        assert!(SpotifyId::from_base62("5aNzxEEkRE9MgNkiuXmp-R").is_err());
+        assert!(SpotifyId::from_base62("zzzzzzzzzzzzzzzzzzzzzz").is_err());
+        assert!(SpotifyId::from_hex("aa01eba7b9cbca90eb3d25694e11487").is_err());
+        assert!(SpotifyId::from_hex("+a01eba7b9cbca90eb3d25694e114875").is_err());
+    }
+
+    #[test]
+    fn test_uri_round_trip() {
+        for uri in [
+            "spotify:playlist:5aNzxEEkRE9MgNkiuXmpOR",
+            "spotify:track:6rqhFgbbKwnb9MLmUQDhG6",
+            "spotify:episode:512ojhOuo1ktJprKbVcKyQ",
+            "spotify:album:4aawyAB9vmqN3uQ7FjRGTy",
+            "spotify:artist:0OdUWJ0sBjDrqHygGUXeCF",
+            "spotify:user:31h5mfzvglpwfevvaens2flw7smu",
+            "spotify:local:Artist:Album+Name:Title%3A+Part:215",
+            "spotify:local:::Title:0",
+            "spotify:start-group:123456789abcdefa:My+Folder:With+Colon",
+            "spotify:end-group:123456789abcdefa",
+        ] {
+            let parsed: SpotifyUri = uri.parse().expect(uri);
+            assert_eq!(parsed.to_string(), uri);
+        }
+    }
+
+    #[test]
+    fn test_uri_parse() {
+        assert_eq!(
+            "spotify:start-group:123456789abcdefa:A:B".parse(),
+            Ok(SpotifyUri::StartGroup {
+                id: "123456789abcdefa".parse().unwrap(),
+                name: "A:B".to_string()
+            })
+        );
+        assert_eq!(
+            GroupUri::parse("spotify:start-group:123456789abcdefa:A:B"),
+            Ok(GroupUri::Start {
+                id: "123456789abcdefa",
+                name: "A:B"
+            })
+        );
+        assert_eq!(
+            GroupUri::parse("spotify:end-group:123456789abcdefa"),
+            Ok(GroupUri::End {
+                id: "123456789abcdefa"
+            })
+        );
+        assert!(GroupUri::parse("spotify:playlist:5aNzxEEkRE9MgNkiuXmpOR").is_err());
+        assert!("".parse::<FolderId>().is_err());
+        assert!("abc:def".parse::<FolderId>().is_err());
+
+        for invalid in [
+            "spotify:playlist:5aNzxEEkRE9MgNkiuXmpORR",
+            "spotify:playlist:",
+            "playlist:5aNzxEEkRE9MgNkiuXmpOR",
+            "spotify:show:5aNzxEEkRE9MgNkiuXmpOR",
+            "spotify:user:",
+            "spotify:user:a:playlist:5aNzxEEkRE9MgNkiuXmpOR",
+            "spotify:local:a:b:c",
+            "spotify:local:a:b:c:d",
+            "spotify:start-group:123456789abcdefa",
+            "spotify:start-group::name",
+            "spotify:end-group:",
+        ] {
+            assert!(invalid.parse::<SpotifyUri>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_from_url() {
+        let playlist = SpotifyUri::Playlist(SpotifyId::from_base62(PLAYLIST_ID).unwrap());
+
+        assert_eq!(
+            SpotifyUri::from_url("https://open.spotify.com/playlist/5aNzxEEkRE9MgNkiuXmpOR?si=abc"),
+            Ok(playlist.clone())
+        );
+        assert_eq!(
+            SpotifyUri::from_url("open.spotify.com/intl-de/playlist/5aNzxEEkRE9MgNkiuXmpOR/"),
+            Ok(playlist)
+        );
+        assert_eq!(
+            SpotifyUri::from_url("https://open.spotify.com/user/31h5mfzvglpwfevvaens2flw7smu"),
+            Ok(SpotifyUri::User("31h5mfzvglpwfevvaens2flw7smu".to_string()))
+        );
+
+        assert!(
+            SpotifyUri::from_url("https://example.com/playlist/5aNzxEEkRE9MgNkiuXmpOR").is_err()
+        );
+        assert!(SpotifyUri::from_url("https://open.spotify.com/playlist").is_err());
+        assert!(SpotifyUri::from_url("https://open.spotify.com/playlist/a/b").is_err());
+    }
+
+    #[test]
+    fn test_uri_serde() {
+        let uri: SpotifyUri =
+            serde_json::from_str(r#""spotify:playlist:5aNzxEEkRE9MgNkiuXmpOR""#).unwrap();
+
+        assert_eq!(
+            serde_json::to_string(&uri).unwrap(),
+            r#""spotify:playlist:5aNzxEEkRE9MgNkiuXmpOR""#
+        );
+        assert!(serde_json::from_str::<SpotifyUri>(r#""spotify:nope""#).is_err());
+    }
+}